@@ -0,0 +1,142 @@
+//! Instructions of a function as they are emitted.
+//!
+//! The body of every function is collected as a list of [`Line`]s instead
+//! of text, so the peephole pass can look at the operands of an instruction
+//! and what it reads and writes before it is printed in nasm syntax.
+
+use std::fmt;
+
+use crate::Mir::Ty;
+
+use super::Gen;
+
+// the address of a memory operand
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Base {
+    Reg(&'static str),
+    // a label addressed relative to rip
+    Rel(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Mem {
+    // the size prefix, None when the other operand gives the size
+    pub(super) ty: Option<Ty>,
+    pub(super) base: Base,
+    // index register and scale
+    pub(super) index: Option<(&'static str, u8)>,
+    pub(super) disp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Arg {
+    // named by its 64 bit name, used at the width of ty
+    Reg(&'static str, Ty),
+    Imm(i64),
+    Mem(Mem),
+    // a jump or call target
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Line {
+    Label(String),
+    Inst(&'static str, Vec<Arg>),
+    // a call with the registers it passes args in
+    Call(String, Vec<&'static str>),
+    // inline asm, nothing is known about what it does
+    Raw(String),
+}
+
+// the registers the emitter loads operands into, they never hold a value
+// from one instruction of the IR to the next
+pub(super) const SCRATCH: &[&str] = &["rax", "rcx", "rdx", "rsi"];
+
+impl Arg {
+    pub(super) fn reg(name: &'static str, ty: Ty) -> Arg {
+        Arg::Reg(name, ty)
+    }
+
+    // [base - offset] of the frame
+    pub(super) fn frame(ty: Option<Ty>, offset: i64) -> Arg {
+        Arg::Mem(Mem { ty, base: Base::Reg("rbp"), index: None, disp: -offset })
+    }
+
+    pub(super) fn at(ty: Option<Ty>, reg: &'static str) -> Arg {
+        Arg::Mem(Mem { ty, base: Base::Reg(reg), index: None, disp: 0 })
+    }
+
+    pub(super) fn rel(label: String) -> Arg {
+        Arg::Mem(Mem { ty: None, base: Base::Rel(label), index: None, disp: 0 })
+    }
+
+    pub(super) fn is_mem(&self) -> bool {
+        matches!(self, Arg::Mem(_))
+    }
+
+    // the width of the operand when it has one
+    pub(super) fn ty(&self) -> Option<Ty> {
+        match self {
+            Arg::Reg(_, ty) => Some(*ty),
+            Arg::Mem(mem) => mem.ty,
+            Arg::Imm(_) | Arg::Label(_) => None,
+        }
+    }
+
+    // registers read to get at the operand, the register itself or the
+    // ones of the address
+    pub(super) fn uses(&self, reg: &str) -> bool {
+        match self {
+            Arg::Reg(name, _) => *name == reg,
+            Arg::Mem(mem) => matches!(mem.base, Base::Reg(base) if base == reg) || mem.index.is_some_and(|(index, _)| index == reg),
+            Arg::Imm(_) | Arg::Label(_) => false,
+        }
+    }
+
+    // registers in the address of a memory operand
+    pub(super) fn addr_uses(&self, reg: &str) -> bool {
+        self.is_mem() && self.uses(reg)
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Reg(name, ty) => write!(f, "{}", Gen::reg(name, *ty)),
+            Arg::Imm(value) => write!(f, "{}", value),
+            Arg::Label(label) => write!(f, "{}", label),
+            Arg::Mem(mem) => {
+                if let Some(ty) = mem.ty {
+                    write!(f, "{} ", Gen::get_word(ty))?;
+                }
+                match &mem.base {
+                    Base::Reg(reg) => write!(f, "[{}", reg)?,
+                    Base::Rel(label) => write!(f, "[rel {}", label)?,
+                }
+                if let Some((index, scale)) = mem.index {
+                    write!(f, " + {}*{}", index, scale)?;
+                }
+                match mem.disp {
+                    0 => write!(f, "]"),
+                    disp if disp < 0 => write!(f, " - {}]", -disp),
+                    disp => write!(f, " + {}]", disp),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Label(label) => write!(f, "{}:", label),
+            Line::Inst(op, args) if args.is_empty() => write!(f, "    {}", op),
+            Line::Inst(op, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "    {} {}", op, args.join(", "))
+            }
+            Line::Call(target, _) => write!(f, "    call {}", target),
+            Line::Raw(line) => write!(f, "    {}", line),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Stack frame layout.
+//!
+//! Every slot of an IR function gets a place below rbp. The slots of a scope
+//! sit past the ones of every scope enclosing it, and sibling scopes start at
+//! the same offset since they are never open at the same time.
+//!
+//! Past the deepest slot every virtual register the allocator put on the
+//! stack gets an 8 byte home, followed by the callee saved registers it
+//! handed out, which the prologue saves and the epilogue restores.
+
+use super::*;
+use super::regalloc;
+use crate::Mir;
+
+struct Layout<'a> {
+    func: &'a Mir::Function,
+    // scopes nested directly in every scope
+    children: Vec<Vec<usize>>,
+    frame: Frame,
+}
+
+impl Layout<'_> {
+    // places the slots of scope and everything nested in it from pos on,
+    // returns the deepest offset reached
+    fn scope(&mut self, scope: usize, mut pos: u32) -> u32 {
+        for (index, slot) in self.func.slots.iter().enumerate() {
+            if slot.scope != scope {
+                continue;
+            }
+            // the slot is the offset of its lowest byte
+            let align = slot.align.clamp(1, 8);
+            pos = (pos + slot.size).div_ceil(align) * align;
+            self.frame.slots[index] = pos;
+        }
+        let mut end = pos;
+        for child in self.children[scope].clone() {
+            end = end.max(self.scope(child, pos));
+        }
+        end
+    }
+}
+
+impl Gen {
+    pub(crate) fn layout_frame(func: &Mir::Function) -> Frame {
+        let mut children = vec![Vec::new(); func.scopes.len()];
+        for (index, parent) in func.scopes.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(index);
+            }
+        }
+        let mut layout = Layout {
+            func,
+            children,
+            frame: Frame { slots: vec![0; func.slots.len()], ..Frame::default() },
+        };
+        let mut pos = layout.scope(0, 0).div_ceil(8) * 8;
+        let allocation = regalloc::allocate(func);
+        layout.frame.vregs = allocation.regs.iter().map(|reg| reg.map(Home::Reg)).collect();
+        for vreg in allocation.spilled {
+            pos += 8;
+            layout.frame.vregs[vreg] = Some(Home::Stack(pos));
+        }
+        for reg in regalloc::CALLEE_SAVED {
+            if allocation.regs.contains(&Some(*reg)) {
+                pos += 8;
+                layout.frame.saved.push((*reg, pos));
+            }
+        }
+        layout.frame.size = pos.div_ceil(16) * 16;
+        layout.frame
+    }
+}
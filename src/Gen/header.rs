@@ -0,0 +1,115 @@
+//! C header generation.
+//!
+//! Writes the prototypes of every exported function together with the
+//! enums and structs of the file, so objects built from `.v` code can be linked into
+//! a C program. Struct fields are laid out the same way [`Gen`] lays them
+//! out: every field takes `element_size` bytes, which C gets through
+//! `_Alignas`.
+
+use super::*;
+
+use crate::Ir::stmt::{Arg, TypeInfo};
+
+impl Gen {
+    pub fn gen_header(&mut self, guard: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.collect_decls();
+        let mut out = String::new();
+        let _ = writeln!(out, "#ifndef {}", guard);
+        let _ = writeln!(out, "#define {}", guard);
+        let _ = writeln!(out);
+
+        for i in self.m_ast.iter() {
+            if let Stmt::InitEnum(v) = i {
+                let _ = writeln!(out, "enum {} {{", v.name.value.as_ref().unwrap());
+                for enumerator in v.enumerators.iter() {
+                    let _ = writeln!(out, "    {} = {},",
+                        enumerator.name.value.as_ref().unwrap(),
+                        enumerator.value.expect("enum was not checked by sema"),
+                    );
+                }
+                let _ = writeln!(out, "}};");
+                let _ = writeln!(out);
+            }
+        }
+
+        for i in self.m_ast.iter() {
+            if let Stmt::InitStruct(v) = i {
+                let element_size = self.structs.get(&v.name).unwrap().element_size;
+                let mut fields: Vec<&StructArg> = v.elements.values().collect();
+                fields.sort_by_key(|field| field.pos);
+                let _ = writeln!(out, "struct {} {{", v.name);
+                for field in fields {
+                    let size = if field.pointer_depth > 0 { 8 } else { self.get_size(field.arg_type.token) };
+                    let align = if size < element_size { format!("_Alignas({}) ", element_size) } else { String::new() };
+                    let _ = writeln!(out, "    {}{}{} {};",
+                        align,
+                        Gen::c_type(field.arg_type.token, None)?,
+                        "*".repeat(field.pointer_depth as usize),
+                        field.name.value.as_ref().unwrap(),
+                    );
+                }
+                let _ = writeln!(out, "}};");
+                let _ = writeln!(out);
+            }
+        }
+
+        for i in self.m_ast.iter() {
+            if let Stmt::InitFunc(v) = i {
+                if !v.exported {
+                    continue;
+                }
+                let _ = writeln!(out, "{};", Gen::c_prototype(v.name.value.as_ref().unwrap(), &v.return_type, &v.args, false)?);
+            }
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "#endif");
+        Ok(out)
+    }
+
+    pub(crate) fn c_prototype(name: &str, return_type: &TypeInfo, args: &[Arg], variadic: bool) -> Result<String, String> {
+        let mut args: Vec<String> = args.iter().map(Gen::c_arg).collect::<Result<_, _>>()?;
+        if variadic {
+            args.push("...".to_string());
+        }
+        let args = if args.is_empty() { "void".to_string() } else { args.join(", ") };
+        Ok(format!("{}{} {}({})",
+            Gen::c_type(return_type.var_type, return_type.struct_name.as_deref())?,
+            "*".repeat(return_type.pointer_depth as usize),
+            name,
+            args,
+        ))
+    }
+
+    fn c_arg(arg: &Arg) -> Result<String, String> {
+        Ok(format!("{}{} {}",
+            Gen::c_type(arg.arg_type.token, arg.struct_name.as_deref())?,
+            "*".repeat(arg.pointer_depth as usize),
+            arg.name.value.as_ref().unwrap(),
+        ))
+    }
+
+    fn c_type(token: TokenType, struct_name: Option<&str>) -> Result<String, String> {
+        let name = match token {
+            TokenType::IntType => "int".to_string(),
+            TokenType::CharType => "char".to_string(),
+            TokenType::ShortType => "short".to_string(),
+            TokenType::LongType => "long".to_string(),
+            TokenType::UCharType => "unsigned char".to_string(),
+            TokenType::UShortType => "unsigned short".to_string(),
+            TokenType::UIntType => "unsigned int".to_string(),
+            TokenType::ULongType => "unsigned long".to_string(),
+            TokenType::Bool => "_Bool".to_string(),
+            TokenType::Void => "void".to_string(),
+            TokenType::Struct | TokenType::Enum => {
+                let keyword = if token == TokenType::Struct { "struct" } else { "enum" };
+                match struct_name {
+                    Some(v) => format!("{} {}", keyword, v),
+                    None => return Err(format!("{} type without a name has no C type", keyword)),
+                }
+            }
+            _ => return Err(format!("no C type for: {:?}", token)),
+        };
+        Ok(name)
+    }
+}
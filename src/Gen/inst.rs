@@ -0,0 +1,249 @@
+use super::*;
+use super::asm::{Arg, Line};
+use crate::Mir::{BinOp, CastKind, Cond, Inst, Operand, VReg};
+
+impl Gen {
+    // a virtual register as an operand of the width of ty, its register or
+    // its home
+    pub(super) fn loc(&self, vreg: VReg, ty: Ty) -> Arg {
+        match self.frame.vregs[vreg] {
+            Some(Home::Reg(reg)) => Arg::reg(reg, ty),
+            Some(Home::Stack(offset)) => Arg::frame(Some(ty), offset as i64),
+            None => panic!("%{} of {} has no home", vreg, self.current_func),
+        }
+    }
+
+    fn in_reg(&self, vreg: VReg) -> bool {
+        matches!(self.frame.vregs[vreg], Some(Home::Reg(_)))
+    }
+
+    pub(super) fn load(&mut self, reg: &'static str, ty: Ty, operand: Operand) {
+        let src = match operand {
+            Operand::Reg(v) => self.loc(v, ty),
+            Operand::Imm(value) => Arg::Imm(value),
+        };
+        self.op("mov", vec![Arg::reg(reg, ty), src]);
+    }
+
+    // an immediate the instruction can take as it is or the register a
+    // value is in, anything else is loaded into reg first
+    fn operand(&mut self, reg: &'static str, ty: Ty, operand: Operand) -> Arg {
+        match operand {
+            Operand::Imm(value) if i32::try_from(value).is_ok() => Arg::Imm(value),
+            Operand::Reg(v) if self.in_reg(v) => self.loc(v, ty),
+            operand => {
+                self.load(reg, ty, operand);
+                Arg::reg(reg, ty)
+            }
+        }
+    }
+
+    fn store(&mut self, dst: VReg, reg: &'static str, ty: Ty) {
+        self.op("mov", vec![self.loc(dst, ty), Arg::reg(reg, ty)]);
+    }
+
+    // a byte in a slot, offset is from the start of the slot
+    fn slot_addr(&self, slot: Mir::SlotId, offset: u32, ty: Option<Ty>) -> Arg {
+        Arg::frame(ty, (self.frame.slots[slot] - offset) as i64)
+    }
+
+    fn set_op(cond: Cond) -> &'static str {
+        match cond {
+            Cond::Eq => "sete",
+            Cond::Ne => "setne",
+            Cond::Slt => "setl",
+            Cond::Sle => "setle",
+            Cond::Sgt => "setg",
+            Cond::Sge => "setge",
+            Cond::Ult => "setb",
+            Cond::Ule => "setbe",
+            Cond::Ugt => "seta",
+            Cond::Uge => "setae",
+        }
+    }
+
+    // quotient in rax and remainder in rdx, a byte divides ax into al and ah
+    fn emit_div(&mut self, ty: Ty, signed: bool) {
+        match (ty, signed) {
+            (Ty::I8, true) => self.op("cbw", Vec::new()),
+            (Ty::I8, false) => self.op("movzx", vec![Arg::reg("rax", Ty::I16), Arg::reg("rax", Ty::I8)]),
+            (Ty::I16, true) => self.op("cwd", Vec::new()),
+            (Ty::I32, true) => self.op("cdq", Vec::new()),
+            (Ty::I64, true) => self.op("cqo", Vec::new()),
+            (_, false) => self.op("xor", vec![Arg::reg("rdx", Ty::I32), Arg::reg("rdx", Ty::I32)]),
+        }
+        let op = if signed { "idiv" } else { "div" };
+        self.op(op, vec![Arg::reg("rcx", ty)]);
+    }
+}
+
+impl Inst {
+    pub(super) fn eval(&self, gen_helper: &mut Gen, func: &Mir::Function, module: &Mir::Module) {
+        match self {
+            Inst::Phi { .. } => panic!("phi left in {}, the IR has to be out of ssa", func.name),
+            Inst::Copy { dst, ty, src } => {
+                // the allocator joined the two
+                if let Operand::Reg(v) = src
+                    && gen_helper.in_reg(*dst)
+                    && gen_helper.frame.vregs[*v] == gen_helper.frame.vregs[*dst] {
+                    return;
+                }
+                let src = gen_helper.operand("rax", *ty, *src);
+                gen_helper.op("mov", vec![gen_helper.loc(*dst, *ty), src]);
+            }
+            Inst::Bin { dst, op, ty, lhs, rhs } => {
+                gen_helper.load("rax", *ty, *lhs);
+                let mut res = "rax";
+                match op {
+                    BinOp::Add | BinOp::Sub => {
+                        let rhs = gen_helper.operand("rcx", *ty, *rhs);
+                        let op = if *op == BinOp::Add { "add" } else { "sub" };
+                        gen_helper.op(op, vec![Arg::reg("rax", *ty), rhs]);
+                    }
+                    // there is no two operand imul of bytes, the low byte
+                    // of the 32 bit product is the same
+                    BinOp::Mul => {
+                        let mul_ty = if *ty == Ty::I8 { Ty::I32 } else { *ty };
+                        let rhs = gen_helper.operand("rcx", mul_ty, *rhs);
+                        gen_helper.op("imul", vec![Arg::reg("rax", mul_ty), rhs]);
+                    }
+                    BinOp::SDiv | BinOp::UDiv => {
+                        gen_helper.load("rcx", *ty, *rhs);
+                        gen_helper.emit_div(*ty, *op == BinOp::SDiv);
+                    }
+                    BinOp::SRem | BinOp::URem => {
+                        gen_helper.load("rcx", *ty, *rhs);
+                        gen_helper.emit_div(*ty, *op == BinOp::SRem);
+                        // ah down into al
+                        if *ty == Ty::I8 {
+                            gen_helper.op("shr", vec![Arg::reg("rax", Ty::I16), Arg::Imm(8)]);
+                        }
+                        else {
+                            res = "rdx";
+                        }
+                    }
+                }
+                gen_helper.store(*dst, res, *ty);
+            }
+            Inst::Neg { dst, ty, src } => {
+                gen_helper.load("rax", *ty, *src);
+                gen_helper.op("neg", vec![Arg::reg("rax", *ty)]);
+                gen_helper.store(*dst, "rax", *ty);
+            }
+            Inst::Cmp { dst, cond, ty, lhs, rhs } => {
+                gen_helper.load("rax", *ty, *lhs);
+                let rhs = gen_helper.operand("rcx", *ty, *rhs);
+                gen_helper.op("cmp", vec![Arg::reg("rax", *ty), rhs]);
+                gen_helper.op(Gen::set_op(*cond), vec![Arg::reg("rax", Ty::I8)]);
+                gen_helper.store(*dst, "rax", Ty::I8);
+            }
+            Inst::Cast { dst, kind, from, to, src } => {
+                match (src, kind) {
+                    (Operand::Imm(value), CastKind::Zext) => {
+                        let mask = u64::MAX >> (64 - from.size() * 8);
+                        let value = to.wrap((*value as u64 & mask) as i128);
+                        gen_helper.load("rax", *to, Operand::Imm(value));
+                    }
+                    (Operand::Imm(value), _) => gen_helper.load("rax", *to, Operand::Imm(to.wrap(*value as i128))),
+                    // the low bytes of the home are the narrower value
+                    (Operand::Reg(v), CastKind::Trunc) => gen_helper.load("rax", *to, Operand::Reg(*v)),
+                    (Operand::Reg(v), CastKind::Sext) => {
+                        let op = if *from == Ty::I32 { "movsxd" } else { "movsx" };
+                        gen_helper.op(op, vec![Arg::reg("rax", *to), gen_helper.loc(*v, *from)]);
+                    }
+                    (Operand::Reg(v), CastKind::Zext) => {
+                        // writing the low 32 bits clears the upper ones
+                        if *from == Ty::I32 {
+                            gen_helper.op("mov", vec![Arg::reg("rax", Ty::I32), gen_helper.loc(*v, Ty::I32)]);
+                        }
+                        else {
+                            gen_helper.op("movzx", vec![Arg::reg("rax", *to), gen_helper.loc(*v, *from)]);
+                        }
+                    }
+                }
+                gen_helper.store(*dst, "rax", *to);
+            }
+            Inst::SlotAddr { dst, slot } => {
+                gen_helper.op("lea", vec![Arg::reg("rax", Ty::I64), gen_helper.slot_addr(*slot, 0, None)]);
+                gen_helper.store(*dst, "rax", Ty::I64);
+            }
+            Inst::StrAddr { dst, index } => {
+                gen_helper.op("lea", vec![Arg::reg("rax", Ty::I64), Arg::rel(format!("str_{}",index))]);
+                gen_helper.store(*dst, "rax", Ty::I64);
+            }
+            Inst::LoadSlot { dst, ty, slot, offset } => {
+                gen_helper.op("mov", vec![Arg::reg("rax", *ty), gen_helper.slot_addr(*slot, *offset, Some(*ty))]);
+                gen_helper.store(*dst, "rax", *ty);
+            }
+            Inst::StoreSlot { ty, slot, offset, src } => {
+                let src = gen_helper.operand("rax", *ty, *src);
+                gen_helper.op("mov", vec![gen_helper.slot_addr(*slot, *offset, Some(*ty)), src]);
+            }
+            Inst::Load { dst, ty, ptr } => {
+                gen_helper.load("rsi", Ty::I64, *ptr);
+                gen_helper.op("mov", vec![Arg::reg("rax", *ty), Arg::at(Some(*ty), "rsi")]);
+                gen_helper.store(*dst, "rax", *ty);
+            }
+            Inst::Store { ty, ptr, src } => {
+                gen_helper.load("rsi", Ty::I64, *ptr);
+                let src = gen_helper.operand("rax", *ty, *src);
+                gen_helper.op("mov", vec![Arg::at(Some(*ty), "rsi"), src]);
+            }
+            Inst::Call { dst, callee, args } => {
+                let sig = module.signatures.get(callee).unwrap_or_else(|| panic!("call to unkown function: {}", callee));
+                // args past the sixth are pushed right to left, so pad first
+                // if there is an odd amount of them to keep the call aligned
+                let stack_args = args.len().saturating_sub(Gen::ARG_REGS);
+                let padding = if stack_args % 2 == 1 { 8 } else { 0 };
+                if padding != 0 {
+                    gen_helper.op("sub", vec![Arg::reg("rsp", Ty::I64), Arg::Imm(padding as i64)]);
+                }
+                for (_, arg) in args.iter().skip(Gen::ARG_REGS).rev() {
+                    match arg {
+                        Operand::Reg(v) => gen_helper.op("push", vec![gen_helper.loc(*v, Ty::I64)]),
+                        Operand::Imm(value) => {
+                            gen_helper.op("mov", vec![Arg::reg("rax", Ty::I64), Arg::Imm(*value)]);
+                            gen_helper.op("push", vec![Arg::reg("rax", Ty::I64)]);
+                        }
+                    }
+                }
+                let mut regs = Vec::new();
+                for (index, (ty, arg)) in args.iter().take(Gen::ARG_REGS).enumerate() {
+                    gen_helper.load(Gen::ARG_REGS_64[index], *ty, *arg);
+                    regs.push(Gen::ARG_REGS_64[index]);
+                }
+                if sig.variadic {
+                    // al holds the amount of vector registers used by a variadic call
+                    gen_helper.op("xor", vec![Arg::reg("rax", Ty::I32), Arg::reg("rax", Ty::I32)]);
+                    regs.push("rax");
+                }
+                let target = if sig.external { format!("{} wrt ..plt",callee) } else { callee.clone() };
+                gen_helper.code.push(Line::Call(target, regs));
+                let cleanup = stack_args as u32 * 8 + padding;
+                if cleanup != 0 {
+                    gen_helper.op("add", vec![Arg::reg("rsp", Ty::I64), Arg::Imm(cleanup as i64)]);
+                }
+                if let Some(dst) = dst {
+                    gen_helper.store(*dst, "rax", func.vregs[*dst]);
+                }
+            }
+            Inst::Asm { lines, vars } => {
+                for line in lines.iter() {
+                    let mut buf = String::new();
+                    let mut iter = line.chars();
+                    while let Some(j) = iter.next() {
+                        if j != '(' {
+                            buf.push(j);
+                            continue;
+                        }
+                        let name: String = iter.by_ref().take_while(|c| *c != ')').collect();
+                        let (_, slot) = vars.iter().find(|(var, _)| *var == name)
+                            .unwrap_or_else(|| panic!("unkown var: {}", name));
+                        buf.push_str(&gen_helper.slot_addr(*slot, 0, None).to_string());
+                    }
+                    gen_helper.code.push(Line::Raw(buf));
+                }
+            }
+        }
+    }
+}
@@ -1,31 +1,44 @@
-use core::panic;
-use std::rc::Rc;
 use std::{collections::HashMap, fmt::Write};
 
-use crate::Ir::expr::RpnExpr;
 use crate::Ir::Stmt;
 use crate::Ir::r#gen::*;
 use crate::Ir::stmt::StructArg;
-use crate::Ir::stmt::TypeInfo;
+use crate::Mir;
+use crate::Mir::Ty;
 use crate::Tokenizer::TokenType;
 
+use asm::{Arg, Line};
 
-mod gen_expr;
-mod gen_stmt;
-mod expr;
-mod stmt;
+
+mod asm;
+mod frame;
+mod header;
+mod inst;
+mod peephole;
+mod regalloc;
+mod term;
 
 pub struct Gen {
     m_ast: Vec<Stmt>,
-    m_vars: HashMap<String,VarData>,
     m_out: String,
-    depth_size: usize,
-    scope_stack: Vec<i32>,
-    m_stack_pos: u32,
+    // layout of the function being emitted
+    frame: Frame,
+    // body of the function being emitted
+    code: Vec<Line>,
+    // the block after the last one of the function, the epilogue every
+    // return jumps to
+    epilogue: Mir::BlockId,
     structs: HashMap<String, StructData>,
     functions: HashMap<String, FuncData>,
     current_func: String,
     id: usize,
+    // emit the _start stub, without it main is a global called by
+    // whatever the object gets linked with (crt when linking libc)
+    entry_stub: bool,
+    // jump tables of switches, emitted into .rodata with their entries
+    jump_tables: Vec<(String, Vec<String>)>,
+    // what the passes found, they don't stop the compilation
+    warnings: Vec<String>,
 }
 
 
@@ -34,18 +47,20 @@ pub struct Gen {
 impl Gen {
 
 
-    pub fn new(m_ast: Vec<Stmt>) -> Gen {
+    pub fn new(m_ast: Vec<Stmt>, entry_stub: bool) -> Gen {
         Gen {
             m_ast,
-            m_vars: HashMap::new(),
             m_out: String::new(),
-            depth_size: 0,
-            scope_stack: Vec::new(),
-            m_stack_pos: 0,
+            frame: Frame::default(),
+            code: Vec::new(),
+            epilogue: 0,
             structs: HashMap::new(),
             functions: HashMap::new(),
             current_func: String::new(),
             id: 0,
+            jump_tables: Vec::new(),
+            warnings: Vec::new(),
+            entry_stub,
         }
     }
 
@@ -54,6 +69,14 @@ impl Gen {
         let _ = writeln!(self.m_out, "{}", s);
     }
 
+    fn op(&mut self, op: &'static str, args: Vec<Arg>) {
+        self.code.push(Line::Inst(op, args));
+    }
+
+    fn label(&mut self, label: String) {
+        self.code.push(Line::Label(label));
+    }
+
     fn get_id(&mut self) -> usize {
         self.id += 1;
         self.id
@@ -61,188 +84,184 @@ impl Gen {
 
     fn get_size(&self, token: TokenType) -> u32 {
         match token {
-            TokenType::IntType => 4,
-            TokenType::CharType => 1,
-            TokenType::ShortType => 2,
-            TokenType::LongType => 8,
+            TokenType::IntType | TokenType::UIntType | TokenType::Enum => 4,
+            TokenType::CharType | TokenType::UCharType | TokenType::Bool => 1,
+            TokenType::ShortType | TokenType::UShortType => 2,
+            TokenType::LongType | TokenType::ULongType => 8,
             _ => panic!("trying to get size of unexpected type: {:?}",token),
         }
     }
 
-    fn alloc(&mut self, ty: TokenType) -> u32 {
-        let size: u32 = self.get_size(ty);
-        self.m_stack_pos += size;
-        self.m_stack_pos
+    pub(crate) fn gen_ir(&mut self, passes: &Mir::PassManager) -> Result<Mir::Module, Box<dyn std::error::Error>> {
+        self.collect_decls();
+        let mut module = Mir::lower(&self.m_ast, &self.structs, &self.functions);
+        if let Err(errors) = Mir::verify(&module) {
+            return Err(format!("malformed IR:\n{}", errors.join("\n")).into());
+        }
+        passes.run(&mut module)?;
+        self.warnings.append(&mut module.warnings);
+        Ok(module)
     }
 
-
-    fn calc_stack_size(&self,exprs: &[Stmt]) -> u32 {
-        let total: u32 = exprs
-            .iter()
-            .map(|e| match e {
-                Stmt::CreateVar(v) => self.get_size(v.Type),
-
-                Stmt::CreateStruct(v) => {
-                    let struct_data = self.structs.get(&v.struct_name).expect(&format!("no struct with name: {:?}",v.struct_name));
-                    let size = struct_data.elements.len() as u32 * struct_data.element_size;
-                    size
-                }
-
-                Stmt::InitArray(v) => {
-                    let arr_size: u32 = v.size.value.clone().unwrap().parse().unwrap();
-                    self.get_size(v.arr_type.token) * arr_size
-                },
-                Stmt::IfStmt(v) => {
-                    std::cmp::max(self.calc_stack_size(&v.data), self.calc_stack_size(&v.else_data))
-                }
-                Stmt::WhileStmt(v) => {
-                    self.calc_stack_size(&v.data)
-                }
-
-                Stmt::ForStmt(v) => {
-                    self.calc_stack_size(&v.data)
-                }
-                _ => {
-                    0
-                },
-            })
-            .sum();
-        total
+    pub(crate) fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
     }
 
-    pub fn gen_asm(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+    pub(crate) fn gen_asm(&mut self, passes: &Mir::PassManager) -> Result<String, Box<dyn std::error::Error>> {
+        let module = self.gen_ir(passes)?;
+
+        let mut externs: Vec<&String> = module.signatures.iter()
+            .filter(|(_, sig)| sig.external)
+            .map(|(name, _)| name)
+            .collect();
+        externs.sort();
+        let externs: Vec<String> = externs.iter().map(|name| format!("extern {}",name)).collect();
+        for i in externs {
+            self.emit(i);
+        }
+
         self.emit("section .text".to_string());
-        self.emit("global _start".to_string());
-        self.emit("_start:".to_string());
-        self.emit("    sub rsp, 8".to_string());
-        self.emit("    call main".to_string());
-        self.emit("    add rsp, 8".to_string());
-        self.emit("    mov rax, 60".to_string());
-        self.emit("    xor rdi, rdi".to_string());
-        self.emit("    syscall".to_string());
-        self.gen_stmts()?;
+        if self.entry_stub {
+            self.emit("global _start".to_string());
+            self.emit("_start:".to_string());
+            // rsp is 16 byte aligned here, so the call leaves main with the usual
+            // rsp % 16 == 8 at entry
+            self.emit("    call main".to_string());
+            self.emit("    mov rax, 60".to_string());
+            self.emit("    xor rdi, rdi".to_string());
+            self.emit("    syscall".to_string());
+        }
+        for func in module.functions.iter() {
+            self.gen_function(func, &module, passes.level() > 0);
+        }
+        self.emit_rodata(&module.strings);
         Ok(self.m_out.clone())
     }
 
-
-    fn is_num(token: TokenType) -> bool {
-        match token {
-            TokenType::IntType => true,
-            TokenType::LongType => true,
-            TokenType::ShortType => true,
-            _ => false,
+    fn gen_function(&mut self, func: &Mir::Function, module: &Mir::Module, optimize: bool) {
+        // without our stub main gets called the same way C code calls an exported function
+        if func.exported || (!self.entry_stub && func.name == "main") {
+            self.emit(format!("global {}",func.name));
         }
-    } 
-
-
-    fn get_rdx_register(token: TokenType) -> String {
-        match token {
-            TokenType::IntType => "edx".to_string(),
-            TokenType::ShortType => "dx".to_string(),
-            TokenType::LongType => "rdx".to_string(),
-            TokenType::CharType => "dl".to_string(),
-            TokenType::Struct => "rdx".to_string(),
-            _ => panic!("not a type: {:?}", token),
+        self.emit(format!("{}:",func.name));
+        self.current_func = func.name.clone();
+        self.frame = Gen::layout_frame(func);
+        self.epilogue = func.blocks.len();
+        let (rbp, rsp) = (Arg::reg("rbp", Ty::I64), Arg::reg("rsp", Ty::I64));
+        self.op("push", vec![rbp.clone()]);
+        self.op("mov", vec![rbp, rsp.clone()]);
+        if self.frame.size != 0 {
+            self.op("sub", vec![rsp, Arg::Imm(self.frame.size as i64)]);
         }
-    }
-
-    fn get_rax_register(token: TokenType) -> String {
-        match token {
-            TokenType::IntType => "eax".to_string(),
-            TokenType::ShortType => "ax".to_string(),
-            TokenType::LongType => "rax".to_string(),
-            TokenType::CharType => "al".to_string(),
-            TokenType::Struct => "rax".to_string(),
-            _ => panic!("not a type: {:?}", token),
+        for (reg, offset) in self.frame.saved.clone() {
+            self.op("mov", vec![Arg::frame(None, offset as i64), Arg::reg(reg, Ty::I64)]);
         }
-    }
-
-    fn get_rsi_regsiter(token: TokenType) -> String {
-        match token {
-            TokenType::IntType => "esi".to_string(),
-            TokenType::ShortType => "si".to_string(),
-            TokenType::LongType => "rsi".to_string(),
-            TokenType::CharType => "sil".to_string(),
-            TokenType::Struct => "rsi".to_string(),
-            _ => panic!("not a type: {:?}", token),
+        for (index, param) in func.params.iter().enumerate() {
+            let ty = func.vregs[*param];
+            // an arg the body never reads has nowhere to go
+            if self.frame.vregs[*param].is_none() {
+                continue;
+            }
+            if index < Gen::ARG_REGS {
+                self.op("mov", vec![self.loc(*param, ty), Arg::reg(Gen::ARG_REGS_64[index], ty)]);
+            }
+            else {
+                // 7th arg and onward were pushed by the caller
+                let arg = Arg::Mem(asm::Mem { ty: None, base: asm::Base::Reg("rbp"), index: None, disp: Gen::stack_arg_pos(index) as i64 });
+                self.op("mov", vec![Arg::reg("rax", Ty::I64), arg]);
+                self.op("mov", vec![self.loc(*param, ty), Arg::reg("rax", ty)]);
+            }
         }
-    }
-
-    fn get_rbx_register(token: TokenType) -> String {
-        match token {
-            TokenType::IntType => "ebx".to_string(),
-            TokenType::ShortType => "bx".to_string(),
-            TokenType::LongType => "rbx".to_string(),
-            TokenType::CharType => "bl".to_string(),
-            TokenType::Struct => "rbx".to_string(),
-            _ => panic!("not a type: {:?}", token),
+        for (index, block) in func.blocks.iter().enumerate() {
+            self.label(self.block_label(index));
+            for inst in block.insts.iter() {
+                inst.eval(self, func, module);
+            }
+            block.term.eval(self, index);
+        }
+        self.label(self.block_label(self.epilogue));
+        self.emit_epilogue();
+        let mut code = std::mem::take(&mut self.code);
+        if optimize {
+            peephole::run(&mut code);
         }
+        for line in code {
+            self.emit(line.to_string());
+        }
+        self.current_func = String::new();
+        self.frame = Frame::default();
     }
 
-    fn convert_reg_to_size(reg: &str) -> u32 {
-        match reg {
-            "rax" => 8,
-            "eax" => 4,
-            "ax" => 2,
-            "al" => 1,
-            "rbx" => 8,
-            "ebx" => 4,
-            "bx" => 2,
-            "bl" => 1,
-            __ => panic!("unkown reg at convert_reg_to_size: {}",reg),
+    fn block_label(&self, block: Mir::BlockId) -> String {
+        if block == self.epilogue {
+            return format!("{}.ret",self.current_func);
         }
-        
+        format!("{}.bb{}",self.current_func, block)
     }
 
-    fn convert_size_to_type(size: u32) -> TokenType {
-        match size {
-            1 => TokenType::CharType,
-            2 => TokenType::ShortType,
-            4 => TokenType::IntType,
-            8 => TokenType::LongType,
-            _ => panic!("unknown size in convert_size_to_type")
+    fn emit_epilogue(&mut self) {
+        for (reg, offset) in self.frame.saved.clone() {
+            self.op("mov", vec![Arg::reg(reg, Ty::I64), Arg::frame(None, offset as i64)]);
         }
+        self.op("mov", vec![Arg::reg("rsp", Ty::I64), Arg::reg("rbp", Ty::I64)]);
+        self.op("pop", vec![Arg::reg("rbp", Ty::I64)]);
+        self.op("ret", Vec::new());
     }
 
-
-    fn compare_reg(&mut self, lhs: &String, rhs:&String) -> (String, String) {
-        if lhs.starts_with('[') || rhs.starts_with('[') {
-            return (lhs.to_string(),rhs.to_string());
+    fn emit_rodata(&mut self, strings: &[String]) {
+        if strings.is_empty() && self.jump_tables.is_empty() {
+            return;
         }
-        let lhs_size = Gen::convert_reg_to_size(&lhs);
-        let rhs_size = Gen::convert_reg_to_size(&rhs);
-
-
-
-        if lhs_size < rhs_size {
-            let lhs_type = Gen::convert_size_to_type(rhs_size);
-            let correct_reg = Gen::get_rax_register(lhs_type);
-            if lhs_size == 4 {
-                self.emit(format!("    movsxd {}, {}",correct_reg , lhs));
-            } else {
-                self.emit(format!("    movsx {}, {}",correct_reg , lhs));
-            }
-            return (correct_reg, rhs.to_string());
+        self.emit("section .rodata".to_string());
+        // tables first, their dword entries stay aligned
+        let tables: Vec<String> = self.jump_tables.iter()
+            .map(|(label, entries)| format!("{}: dd {}",label, entries.join(", ")))
+            .collect();
+        for i in tables {
+            self.emit(i);
         }
-        if rhs_size < lhs_size {
-            let rhs_type = Gen::convert_size_to_type(lhs_size);
-            let correct_reg = Gen::get_rbx_register(rhs_type);
-            if rhs_size == 4 {
-                self.emit(format!("    movsxd {}, {}",correct_reg , rhs));
-            } else {
-                self.emit(format!("    movsx {}, {}",correct_reg , rhs));
-            }
-            return (lhs.to_string(), correct_reg);
+        // backquoted nasm strings understand the same escapes as C
+        for (index, v) in strings.iter().enumerate() {
+            self.emit(format!("str_{}: db `{}`, 0",index, v.replace('`', "\\`")));
         }
-        return (lhs.to_string(),rhs.to_string());
     }
 
-    fn calc_expr_stack_size(stack: &Vec<ExprStack>) -> u32 {
-        let mut res = 0u32;
-        for reg in stack {
-            res += Gen::convert_reg_to_size(&reg.reg);
+    // a register at the width of ty, named by its 64 bit name
+    fn reg(reg: &str, ty: Ty) -> String {
+        let names = match reg {
+            "rax" => ["rax", "eax", "ax", "al"],
+            "rcx" => ["rcx", "ecx", "cx", "cl"],
+            "rdx" => ["rdx", "edx", "dx", "dl"],
+            "rsi" => ["rsi", "esi", "si", "sil"],
+            "rdi" => ["rdi", "edi", "di", "dil"],
+            "r8" => ["r8", "r8d", "r8w", "r8b"],
+            "r9" => ["r9", "r9d", "r9w", "r9b"],
+            "rbx" => ["rbx", "ebx", "bx", "bl"],
+            "r10" => ["r10", "r10d", "r10w", "r10b"],
+            "r11" => ["r11", "r11d", "r11w", "r11b"],
+            "r12" => ["r12", "r12d", "r12w", "r12b"],
+            "r13" => ["r13", "r13d", "r13w", "r13b"],
+            "r14" => ["r14", "r14d", "r14w", "r14b"],
+            "r15" => ["r15", "r15d", "r15w", "r15b"],
+            "rbp" => ["rbp", "ebp", "bp", "bpl"],
+            "rsp" => ["rsp", "esp", "sp", "spl"],
+            _ => panic!("unkown reg: {}", reg),
+        };
+        match ty {
+            Ty::I64 => names[0],
+            Ty::I32 => names[1],
+            Ty::I16 => names[2],
+            Ty::I8 => names[3],
+        }.to_string()
+    }
+
+    fn get_word(ty: Ty) -> String {
+        match ty {
+            Ty::I32 => "DWORD".to_string(),
+            Ty::I16 => "WORD".to_string(),
+            Ty::I64 => "QWORD".to_string(),
+            Ty::I8 => "BYTE".to_string(),
         }
-        res
     }
 
     fn get_struct_element_size(&self, elements: &HashMap<String,StructArg>) -> u32 {
@@ -259,14 +278,30 @@ impl Gen {
     }
 
 
-    fn gen_stmts(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    // sema already checked the declarations against each other
+    fn collect_decls(&mut self) {
         for i in self.m_ast.iter() {
             match i {
                 Stmt::InitFunc(v) => {
+                    let res = FuncData {
+                        return_type: v.return_type.clone(),
+                        args: v.args.clone(),
+                        variadic: false,
+                        is_extern: false,
+                    };
+                    self.functions.insert(v.name.value.clone().unwrap(), res);
+                }
+                Stmt::FuncDecl(v) => {
                     let name = v.name.value.clone().unwrap();
+                    // a definition in this file wins over the declaration
+                    if self.functions.contains_key(&name) {
+                        continue;
+                    }
                     let res = FuncData {
                         return_type: v.return_type.clone(),
-                        args: v.args.clone()
+                        args: v.args.clone(),
+                        variadic: v.variadic,
+                        is_extern: true,
                     };
                     self.functions.insert(name, res);
                 }
@@ -278,119 +313,95 @@ impl Gen {
                 _ => continue,
             }
         }
-        
-        let mut ast = std::mem::take(&mut self.m_ast);
-        for i in ast.iter_mut() {
-            self.parse_stmt(i);
-        }
-        Ok(())
     }
 
 
-    fn arg_pos(pos: usize, token: TokenType) -> String {
-        match token {
-            TokenType::IntType => match pos {
-                0 => "edi".to_string(),
-                1 => "esi".to_string(),
-                2 => "edx".to_string(),
-                3 => "r10d".to_string(),
-                4 => "r8d".to_string(),
-                _ => panic!("arg_pos unknown arg: {}", pos),
-            },
-            TokenType::LongType => match pos {
-                0 => "rdi".to_string(),
-                1 => "rsi".to_string(),
-                2 => "rdx".to_string(),
-                3 => "r10".to_string(),
-                4 => "r8".to_string(),
-                _ => panic!("arg_pos unknown arg: {}", pos),
-            },
-            TokenType::ShortType => match pos {
-                0 => "di".to_string(),
-                1 => "si".to_string(),
-                2 => "dx".to_string(),
-                3 => "r10w".to_string(),
-                4 => "r8w".to_string(),
-                _ => panic!("arg_pos unknown arg: {}", pos),
-            },
-            TokenType::CharType => match pos {
-                0 => "dil".to_string(),
-                1 => "sil".to_string(),
-                2 => "dl".to_string(),
-                3 => "r10b".to_string(),
-                4 => "r8b".to_string(),
-                _ => panic!("arg_pos unknown arg: {}", pos),
-            },
-            _ => panic!("unknown arg_pos token: {:?}", token),
-        }
-    }
-
-
-    fn get_type_of_expr(&self,expr: &Vec<RpnExpr>) -> TypeInfo {
-        let mut res = TokenType::IntType; // default one
-        let mut pointer_depth = 0;
-        let expr_len = expr.len();
-        for i in expr { 
-            match i {
-
-                RpnExpr::PushVar(v) => {
-                    if v.data.token == TokenType::Var {
-                        let name = v.data.value.as_ref().unwrap();
-                        let var = self.m_vars.get(name).expect(&format!("no variable with name: {}",name));
-
-                        if var.struct_data.is_some() {
-                            panic!("cannot copy a struct");
-                        }
-                        
-                        if self.get_size(res) < self.get_size(var.var_type) || expr_len < 2 {
-                            res = var.var_type;
-                        }
-                        if var.pointer_depth > pointer_depth {
-                            pointer_depth = var.pointer_depth;
-                        }
-                    }
-
-
-                    else if self.get_size(res) < self.get_size(v.data.token) {
-                        res = v.data.token;
-                    }
-                }
-                RpnExpr::GetAddr(v) => {
-                    let name = v.var.value.as_ref().unwrap();
-                    let var_data = self.m_vars.get(name).expect(&format!("no var with name: {}",name));
-                    res = var_data.var_type;
-                    pointer_depth = var_data.pointer_depth + 1;
-                }
+    // System V passes the first six integer args in registers,
+    // everything after that goes on the stack
+    const ARG_REGS: usize = 6;
+    const ARG_REGS_64: [&'static str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
 
-                RpnExpr::Deref(v) => {
-                    let name = v.var.value.as_ref().unwrap();
-                    let var_data = self.m_vars.get(name).expect(&format!("no var with name: {}",name));
-                    res = var_data.var_type;
-                    pointer_depth = var_data.pointer_depth - v.stack_depth;
-                }
+    // offset from rbp of a stack passed arg inside the callee:
+    // [rbp] is the saved rbp, [rbp + 8] the return address
+    fn stack_arg_pos(pos: usize) -> u32 {
+        16 + 8 * (pos - Gen::ARG_REGS) as u32
+    }
+}
 
-                _ => continue,
-            }
-        }
-        TypeInfo { var_type: res, pointer_depth, }
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::Import::Importer;
+    use crate::Mir::PassManager;
+    use crate::Parser::Parser;
+    use crate::Preprocessor::Preprocessor;
+    use crate::Sema::Sema;
+    use crate::Tokenizer::Tokenizer;
+
+    use super::Gen;
+
+    // the asm of one file at the level, the way main compiles it
+    pub(crate) fn compile(src: &str, level: u8) -> String {
+        let mut tokenizer = Tokenizer::new(src.to_string(), Rc::from("test.v"));
+        tokenizer.tokenize();
+        let tokens = Preprocessor::new(&[], &[]).process(tokenizer.m_res, &mut Importer::new(Vec::new())).unwrap();
+        let mut ast = Parser::new(tokens).parse();
+        Sema::new(false).check(&mut ast).expect("sema errors");
+        let passes = PassManager::new(level, None, &[]).unwrap();
+        Gen::new(ast, false).gen_asm(&passes).unwrap()
     }
 
+    #[test]
+    fn dense_and_sparse_switch() {
+        let src = "int dense(int x) {\n    int r = 0;\n    switch (x) {\n\
+            case 1: r = 10;\n        case 2: r = r + 20; break;\n        case 3: r = 30; break;\n\
+            case 5: r = 50; break;\n        default: r = 1;\n    }\n    return r;\n}\n\
+            int sparse(int x) {\n    switch (x) {\n        case 1: return 1;\n        case 100: return 2;\n\
+            case 1000: return 3;\n        case 10000: return 4;\n    }\n    return 0;\n}\n";
+        let asm = compile(src, 0);
+        // 1 to 5 index the table, anything above, or below after the
+        // subtraction wraps, goes to default
+        assert!(asm.contains("    sub rax, 1\n    cmp rax, 4\n    ja dense.bb6\n"));
+        assert!(asm.contains("    lea rdx, [rel switch_table_1]\n"));
+        // the hole at 4 is filled with default
+        let table = "switch_table_1: dd dense.bb2 - switch_1, dense.bb3 - switch_1, dense.bb4 - switch_1, dense.bb6 - switch_1, dense.bb5 - switch_1";
+        let rodata = asm.split("section .rodata\n").nth(1).unwrap();
+        assert!(rodata.contains(table));
+        // case 1 falls through into case 2
+        assert!(asm.contains("dense.bb2:\n    mov DWORD [rbp - 8], 10\ndense.bb3:\n"));
+
+        // the sparse switch compares against the middle case first and
+        // has no table
+        assert!(!asm.contains("switch_table_2"));
+        assert!(asm.contains("switch_2:\n    mov eax, r10d\n    cmp eax, 1000\n    je sparse.bb4\n    jl case_tree_3\n"));
+        assert!(asm.contains("case_tree_3:\n    cmp eax, 1\n    je sparse.bb2\n    cmp eax, 100\n    je sparse.bb3\n    jmp sparse.bb1\n"));
+    }
 
-    fn get_word(token: TokenType) -> String {
-        match token {
-            TokenType::IntType => "DWORD".to_string(),
-            TokenType::ShortType => "WORD".to_string(),
-            TokenType::LongType => "QWORD".to_string(),
-            TokenType::CharType => "BYTE".to_string(),
-            _ => panic!("not a type: {:?}", token),
-        }
+    // what --emit header writes for one file
+    fn header(src: &str) -> String {
+        let mut tokenizer = Tokenizer::new(src.to_string(), Rc::from("test.v"));
+        tokenizer.tokenize();
+        let mut ast = Parser::new(tokenizer.m_res).parse();
+        Sema::new(false).check(&mut ast).expect("sema errors");
+        Gen::new(ast, false).gen_header("TEST_H").unwrap()
     }
 
-    fn add_var(&mut self,key: String, value: VarData) {
-        if self.m_vars.contains_key(&key) {
-            panic!("redefinition of variable: {}",key);
-        }
-        self.m_vars.insert(key, value);
+    #[test]
+    fn header_of_enums_structs_and_exports() {
+        let src = "enum Color { Red, Green = 5, Blue };\nstruct P { char c; long l; int* p; };\nexport int area(struct P* p, enum Color c, unsigned char b) { return 1; }\nint hidden() { return 0; }\n";
+        let expected = "#ifndef TEST_H\n#define TEST_H\n\n\
+            enum Color {\n    Red = 0,\n    Green = 5,\n    Blue = 6,\n};\n\n\
+            struct P {\n    _Alignas(8) char c;\n    long l;\n    int* p;\n};\n\n\
+            int area(struct P* p, enum Color c, unsigned char b);\n\n#endif\n";
+        assert_eq!(header(src), expected);
     }
 
+    #[test]
+    fn pointer_without_initializer() {
+        for level in 0..=2 {
+            let asm = compile("int main() { long* p; int** q; return 0; }", level);
+            assert!(asm.contains("main:"));
+        }
     }
+}
@@ -0,0 +1,486 @@
+//! Peephole optimisation of the emitted instructions.
+//!
+//! The emitter goes through rax, rcx, rdx and rsi for nearly everything, so
+//! its output is full of values moved into a scratch register only to be
+//! moved on, stores reloaded right away and addresses computed into rsi to
+//! be used once. Each rule looks at one or two instructions next to each
+//! other and rewrites them when the scratch register involved isn't read
+//! afterwards, until nothing changes any more:
+//!
+//! - `mov r, r`, a move straight back and moves into a register nothing
+//!   reads are dropped
+//! - a value or address moved into a scratch register and from there to
+//!   where it goes, or into the instruction using it, skips the register
+//! - `mov r, 0` becomes `xor r, r` when the flags aren't needed
+//! - multiplying and dividing by a power of two become shifts
+//!
+//! Whether a register or the flags are still needed is found by following
+//! the instructions and jumps from there. Inline asm could do anything, so
+//! it ends every search.
+
+use std::collections::HashSet;
+
+use crate::Mir::Ty;
+
+use super::asm::{Arg, Base, Line, Mem, SCRATCH};
+use super::regalloc::CALLEE_SAVED;
+
+pub(super) fn run(code: &mut Vec<Line>) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut pos = 0;
+        while pos < code.len() {
+            if rewrite(code, pos) {
+                changed = true;
+            }
+            else {
+                pos += 1;
+            }
+        }
+    }
+}
+
+fn inst(code: &[Line], pos: usize) -> Option<(&'static str, Vec<Arg>)> {
+    match code.get(pos) {
+        Some(Line::Inst(op, args)) => Some((op, args.clone())),
+        _ => None,
+    }
+}
+
+fn is_jump(op: &str) -> bool {
+    op.starts_with('j')
+}
+
+fn reads_flags(op: &str) -> bool {
+    (is_jump(op) && op != "jmp") || op.starts_with("set") || op.starts_with("cmov") || op == "adc" || op == "sbb"
+}
+
+fn writes_flags(op: &str) -> bool {
+    matches!(op, "add" | "sub" | "cmp" | "test" | "and" | "or" | "xor" | "neg" | "imul" | "shl" | "shr" | "sar" | "idiv" | "div")
+}
+
+// instructions reading a register given as their first operand
+fn reads_dst(op: &str) -> bool {
+    !matches!(op, "mov" | "lea" | "movzx" | "movsx" | "movsxd" | "pop") && !op.starts_with("set")
+}
+
+fn find_label(code: &[Line], label: &str) -> Option<usize> {
+    code.iter().position(|line| matches!(line, Line::Label(name) if name == label))
+}
+
+// nothing reads the register from pos on before writing all of it, the
+// lowest written bytes of it were already written over
+fn reg_dead_at(code: &[Line], mut pos: usize, reg: &str, mut written: u32, seen: &mut HashSet<(usize, u32)>) -> bool {
+    while let Some(line) = code.get(pos) {
+        pos += 1;
+        let (op, args) = match line {
+            Line::Label(_) => continue,
+            Line::Raw(_) => return false,
+            // the ones a call doesn't keep are clobbered
+            Line::Call(_, regs) if regs.contains(&reg) => return false,
+            Line::Call(..) if CALLEE_SAVED.contains(&reg) => continue,
+            Line::Call(..) => return true,
+            Line::Inst(op, args) => (*op, args),
+        };
+        match op {
+            "ret" => return !(reg == "rax" || CALLEE_SAVED.contains(&reg)),
+            "cbw" | "cwd" | "cdq" | "cqo" | "idiv" | "div" if reg == "rax" || reg == "rdx" => return false,
+            _ => {}
+        }
+        // writing the low 32 bits clears the upper ones, a byte or a word
+        // leaves the rest as it was
+        let zeroing = op == "xor" && args.len() == 2 && args[0] == args[1];
+        if let Some(Arg::Reg(name, ty)) = args.first()
+            && *name == reg
+            && zeroing {
+            return matches!(ty, Ty::I32 | Ty::I64);
+        }
+        let reads = args.iter().enumerate().any(|(index, arg)| match arg {
+            Arg::Reg(name, ty) => *name == reg && (index != 0 || reads_dst(op)) && ty.size() > written,
+            arg => arg.addr_uses(reg),
+        });
+        if reads {
+            return false;
+        }
+        if is_jump(op) {
+            match args.first() {
+                Some(Arg::Label(label)) => {
+                    let Some(target) = find_label(code, label) else {
+                        return false;
+                    };
+                    if seen.insert((target, written)) && !reg_dead_at(code, target, reg, written, seen) {
+                        return false;
+                    }
+                    if op == "jmp" {
+                        return true;
+                    }
+                }
+                // a jump table, it only goes to the start of blocks
+                _ => return SCRATCH.contains(&reg),
+            }
+            continue;
+        }
+        if let Some(Arg::Reg(name, ty)) = args.first()
+            && *name == reg {
+            if matches!(ty, Ty::I32 | Ty::I64) {
+                return true;
+            }
+            written = written.max(ty.size());
+        }
+    }
+    false
+}
+
+fn reg_dead_after(code: &[Line], pos: usize, reg: &str) -> bool {
+    reg_dead_at(code, pos + 1, reg, 0, &mut HashSet::new())
+}
+
+fn flags_dead_at(code: &[Line], mut pos: usize, seen: &mut HashSet<usize>) -> bool {
+    while let Some(line) = code.get(pos) {
+        pos += 1;
+        let (op, args) = match line {
+            Line::Label(_) => continue,
+            Line::Raw(_) => return false,
+            // nothing expects the flags to survive a call
+            Line::Call(..) => return true,
+            Line::Inst(op, args) => (*op, args),
+        };
+        if reads_flags(op) {
+            return false;
+        }
+        if op == "jmp" {
+            let Some(Arg::Label(label)) = args.first() else {
+                return false;
+            };
+            let Some(target) = find_label(code, label) else {
+                return false;
+            };
+            return !seen.insert(target) || flags_dead_at(code, target, seen);
+        }
+        if writes_flags(op) || op == "ret" {
+            return true;
+        }
+    }
+    false
+}
+
+fn flags_dead_after(code: &[Line], pos: usize) -> bool {
+    flags_dead_at(code, pos + 1, &mut HashSet::new())
+}
+
+fn fits_i32(arg: &Arg) -> bool {
+    !matches!(arg, Arg::Imm(value) if i32::try_from(*value).is_err())
+}
+
+// the scratch register the instruction writes to, with its width
+fn scratch_dst(args: &[Arg]) -> Option<(&'static str, Ty)> {
+    match args.first() {
+        Some(Arg::Reg(name, ty)) if SCRATCH.contains(name) => Some((name, *ty)),
+        _ => None,
+    }
+}
+
+fn log2(value: i64) -> Option<u32> {
+    (value > 1 && value & (value - 1) == 0).then(|| value.trailing_zeros())
+}
+
+// tries every rule on the instruction at pos, true when one changed the code
+fn rewrite(code: &mut Vec<Line>, pos: usize) -> bool {
+    let Some((op, args)) = inst(code, pos) else {
+        return false;
+    };
+    let next = inst(code, pos + 1);
+
+    // mov r, r, except that a 32 bit one clears the upper half
+    if op == "mov" && args[0] == args[1] && matches!(args[0], Arg::Reg(_, ty) if ty != Ty::I32) {
+        code.remove(pos);
+        return true;
+    }
+
+    // into a register nothing reads any more
+    if matches!(op, "mov" | "lea" | "movzx" | "movsx" | "movsxd")
+        && let Arg::Reg(reg, _) = args[0]
+        && reg != "rsp" && reg != "rbp"
+        && reg_dead_after(code, pos, reg) {
+        code.remove(pos);
+        return true;
+    }
+
+    // mov a, b then mov b, a, the second one changes nothing
+    if op == "mov"
+        && let Some(("mov", back)) = &next
+        && back[0] == args[1] && back[1] == args[0]
+        && !moves_back_changes(code, pos, &args) {
+        code.remove(pos + 1);
+        return true;
+    }
+
+    if let Some((reg, ty)) = scratch_dst(&args)
+        && args.len() == 2
+        && let Some((next_op, next_args)) = &next
+        && next_args.len() == 2
+        && reg_dead_after(code, pos + 1, reg) {
+        let src = &args[1];
+        // into the register and on from it
+        if matches!(op, "mov" | "lea" | "movzx" | "movsx" | "movsxd")
+            && *next_op == "mov" && next_args[1] == args[0] {
+            let dst = &next_args[0];
+            let fits = match src {
+                Arg::Imm(_) => !dst.is_mem() || (dst.ty().is_some() && fits_i32(src)),
+                _ => !(dst.is_mem() && src.is_mem()),
+            };
+            if !dst.uses(reg) && fits && (op == "mov" || !dst.is_mem()) {
+                code[pos] = Line::Inst(op, vec![dst.clone(), src.clone()]);
+                code.remove(pos + 1);
+                return true;
+            }
+        }
+        // loaded only to be the source of an instruction
+        if op == "mov"
+            && matches!(*next_op, "add" | "sub" | "cmp" | "imul" | "and" | "or" | "xor" | "test")
+            && next_args[1] == args[0] {
+            let dst = &next_args[0];
+            if !dst.uses(reg) && fits_i32(src) && !(dst.is_mem() && src.is_mem()) {
+                code[pos] = Line::Inst(next_op, vec![dst.clone(), src.clone()]);
+                code.remove(pos + 1);
+                return true;
+            }
+        }
+        // loaded only to be compared
+        if op == "mov"
+            && matches!(*next_op, "cmp" | "test")
+            && next_args[0] == args[0]
+            && !matches!(src, Arg::Imm(_)) {
+            let other = if next_args[1] == args[0] { src.clone() } else { next_args[1].clone() };
+            let sized = src.ty().is_some() || !matches!(other, Arg::Imm(_));
+            if !other.uses(reg) && sized && !(src.is_mem() && other.is_mem()) {
+                code[pos] = Line::Inst(next_op, vec![src.clone(), other]);
+                code.remove(pos + 1);
+                return true;
+            }
+        }
+        // an address in a register used once
+        if ty == Ty::I64 && let Some(addr) = fold_address(op, &args[1], next_args, reg) {
+            code[pos] = Line::Inst(next_op, addr);
+            code.remove(pos + 1);
+            return true;
+        }
+    }
+
+    // mov r, 0 is longer than xor r, r
+    if op == "mov"
+        && let (Arg::Reg(reg, Ty::I32 | Ty::I64), Arg::Imm(0)) = (&args[0], &args[1])
+        && flags_dead_after(code, pos) {
+        let reg = Arg::reg(reg, Ty::I32);
+        code[pos] = Line::Inst("xor", vec![reg.clone(), reg]);
+        return true;
+    }
+
+    if op == "imul"
+        && let [dst @ Arg::Reg(..), Arg::Imm(value)] = args.as_slice()
+        && let Some(shift) = log2(*value)
+        && flags_dead_after(code, pos) {
+        code[pos] = Line::Inst("shl", vec![dst.clone(), Arg::Imm(shift as i64)]);
+        return true;
+    }
+
+    if let Some(lines) = divide_by_shift(code, pos) {
+        code.splice(pos..pos + 3, lines);
+        return true;
+    }
+    false
+}
+
+// the first move put the same value in both, the one back only changes
+// something when it clears the upper half of a register that wasn't clear
+fn moves_back_changes(code: &[Line], pos: usize, args: &[Arg]) -> bool {
+    let (dst, src) = (&args[0], &args[1]);
+    if let Arg::Reg(reg, _) = src && dst.addr_uses(reg) {
+        return true;
+    }
+    if let Arg::Reg(reg, _) = dst && src.addr_uses(reg) {
+        return true;
+    }
+    if !matches!(src, Arg::Reg(_, Ty::I32)) {
+        return false;
+    }
+    // every 32 bit write clears it
+    let written = pos > 0 && matches!(&code[pos - 1], Line::Inst(op, prev)
+        if prev.first() == Some(src) && !matches!(*op, "cmp" | "test" | "push" | "idiv" | "div"));
+    !written
+}
+
+// mov rsi, r or lea rsi, [addr] followed by an instruction going through
+// [rsi] goes through the address itself
+fn fold_address(op: &str, addr: &Arg, next_args: &[Arg], reg: &'static str) -> Option<Vec<Arg>> {
+    let (base, disp) = match (op, addr) {
+        ("mov", Arg::Reg(base, Ty::I64)) => (Base::Reg(base), 0),
+        ("lea", Arg::Mem(Mem { base, index: None, disp, .. })) => (base.clone(), *disp),
+        _ => return None,
+    };
+    let mut folded = Vec::new();
+    let mut found = false;
+    for arg in next_args {
+        match arg {
+            Arg::Mem(mem) if mem.base == Base::Reg(reg) && mem.index.is_none() && !found => {
+                found = true;
+                folded.push(Arg::Mem(Mem { ty: mem.ty, base: base.clone(), index: None, disp: disp + mem.disp }));
+            }
+            arg if arg.uses(reg) => return None,
+            arg => folded.push(arg.clone()),
+        }
+    }
+    found.then_some(folded)
+}
+
+// mov rcx, 2^k, the sign or zero extension into rdx and the division by
+// rcx, when only one of the quotient and the remainder is used
+fn divide_by_shift(code: &[Line], pos: usize) -> Option<Vec<Line>> {
+    let (Some(("mov", load)), Some((extend, zero)), Some((div, divisor))) = (inst(code, pos), inst(code, pos + 1), inst(code, pos + 2)) else {
+        return None;
+    };
+    let (Arg::Reg("rcx", ty), Arg::Imm(value)) = (&load[0], &load[1]) else {
+        return None;
+    };
+    let signed = match (extend, div, ty) {
+        ("cdq", "idiv", Ty::I32) | ("cqo", "idiv", Ty::I64) => true,
+        ("xor", "div", Ty::I32 | Ty::I64) if zero[0] == Arg::reg("rdx", Ty::I32) => false,
+        _ => return None,
+    };
+    let shift = log2(*value)? as i64;
+    let bits = ty.size() as i64 * 8;
+    if divisor[0] != load[0] || !reg_dead_after(code, pos + 2, "rcx") {
+        return None;
+    }
+    let (rax, rdx) = (Arg::reg("rax", *ty), Arg::reg("rdx", *ty));
+    let line = |op: &'static str, dst: &Arg, src: Arg| Line::Inst(op, vec![dst.clone(), src]);
+    // rounding towards zero adds 2^k - 1 to a negative dividend first
+    let bias = vec![
+        line("mov", &rdx, rax.clone()),
+        line("sar", &rdx, Arg::Imm(bits - 1)),
+        line("shr", &rdx, Arg::Imm(bits - shift)),
+    ];
+    if reg_dead_after(code, pos + 2, "rdx") {
+        if !signed {
+            return Some(vec![line("shr", &rax, Arg::Imm(shift))]);
+        }
+        let mut lines = bias;
+        lines.push(line("add", &rax, rdx.clone()));
+        lines.push(line("sar", &rax, Arg::Imm(shift)));
+        return Some(lines);
+    }
+    // the masks have to fit an immediate
+    if !reg_dead_after(code, pos + 2, "rax") || shift > 31 {
+        return None;
+    }
+    if !signed {
+        return Some(vec![line("mov", &rdx, rax.clone()), line("and", &rdx, Arg::Imm(*value - 1))]);
+    }
+    let mut lines = bias;
+    lines.push(line("add", &rdx, rax.clone()));
+    lines.push(line("and", &rdx, Arg::Imm(-*value)));
+    lines.push(line("sub", &rax, rdx.clone()));
+    lines.push(line("mov", &rdx, rax.clone()));
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(op: &'static str, args: Vec<Arg>) -> Line {
+        Line::Inst(op, args)
+    }
+
+    fn reg(name: &'static str) -> Arg {
+        Arg::reg(name, Ty::I32)
+    }
+
+    fn local(offset: i64) -> Arg {
+        Arg::frame(Some(Ty::I32), offset)
+    }
+
+    fn ret() -> Line {
+        op("ret", Vec::new())
+    }
+
+    // the code after the pass, as printed without the indent
+    fn peephole(mut code: Vec<Line>) -> Vec<String> {
+        run(&mut code);
+        code.iter().map(|line| line.to_string().trim().to_string()).collect()
+    }
+
+    #[test]
+    fn drops_moves_to_itself() {
+        assert_eq!(peephole(vec![op("mov", vec![Arg::reg("rbx", Ty::I64), Arg::reg("rbx", Ty::I64)]), ret()]), ["ret"]);
+        // clears the upper half
+        assert_eq!(peephole(vec![op("mov", vec![reg("rbx"), reg("rbx")]), ret()]), ["mov ebx, ebx", "ret"]);
+    }
+
+    #[test]
+    fn drops_moves_nothing_reads() {
+        assert_eq!(peephole(vec![op("mov", vec![reg("rcx"), Arg::Imm(5)]), ret()]), ["ret"]);
+        let code = vec![
+            op("mov", vec![reg("r10"), Arg::Imm(5)]),
+            op("mov", vec![local(8), reg("rax")]),
+            op("add", vec![reg("rax"), reg("r10")]),
+            ret(),
+        ];
+        assert_eq!(peephole(code), ["mov r10d, 5", "mov DWORD [rbp - 8], eax", "add eax, r10d", "ret"]);
+    }
+
+    #[test]
+    fn inline_asm_ends_the_search() {
+        let code = vec![op("mov", vec![reg("rcx"), Arg::Imm(5)]), Line::Raw("nop".to_string()), ret()];
+        assert_eq!(peephole(code), ["mov ecx, 5", "nop", "ret"]);
+    }
+
+    #[test]
+    fn drops_move_straight_back() {
+        let code = vec![
+            op("add", vec![reg("rax"), Arg::Imm(1)]),
+            op("mov", vec![local(8), reg("rax")]),
+            op("mov", vec![reg("rax"), local(8)]),
+            ret(),
+        ];
+        assert_eq!(peephole(code), ["add eax, 1", "mov DWORD [rbp - 8], eax", "ret"]);
+    }
+
+    #[test]
+    fn skips_the_scratch_register() {
+        let code = vec![op("mov", vec![reg("rcx"), local(8)]), op("mov", vec![reg("rbx"), reg("rcx")]), ret()];
+        assert_eq!(peephole(code), ["mov ebx, DWORD [rbp - 8]", "ret"]);
+        // still read after the move on
+        let code = vec![op("mov", vec![reg("rcx"), local(8)]), op("mov", vec![reg("rbx"), reg("rcx")]), op("add", vec![reg("rax"), reg("rcx")]), ret()];
+        assert_eq!(peephole(code), ["mov ecx, DWORD [rbp - 8]", "mov ebx, ecx", "add eax, ecx", "ret"]);
+        let code = vec![op("mov", vec![reg("rcx"), local(8)]), op("add", vec![reg("rbx"), reg("rcx")]), ret()];
+        assert_eq!(peephole(code), ["add ebx, DWORD [rbp - 8]", "ret"]);
+        let code = vec![op("mov", vec![reg("rcx"), local(8)]), op("cmp", vec![reg("rcx"), Arg::Imm(5)]), op("setl", vec![Arg::reg("rax", Ty::I8)]), ret()];
+        assert_eq!(peephole(code), ["cmp DWORD [rbp - 8], 5", "setl al", "ret"]);
+        let code = vec![op("lea", vec![Arg::reg("rsi", Ty::I64), Arg::frame(None, 16)]), op("mov", vec![Arg::at(Some(Ty::I32), "rsi"), reg("rax")]), ret()];
+        assert_eq!(peephole(code), ["mov DWORD [rbp - 16], eax", "ret"]);
+    }
+
+    #[test]
+    fn zeroes_with_xor() {
+        assert_eq!(peephole(vec![op("mov", vec![reg("rbx"), Arg::Imm(0)]), ret()]), ["xor ebx, ebx", "ret"]);
+        // the xor would change the flags the setl reads
+        let code = vec![
+            op("cmp", vec![reg("rax"), Arg::Imm(1)]),
+            op("mov", vec![reg("rbx"), Arg::Imm(0)]),
+            op("setl", vec![Arg::reg("rax", Ty::I8)]),
+            ret(),
+        ];
+        assert_eq!(peephole(code), ["cmp eax, 1", "mov ebx, 0", "setl al", "ret"]);
+    }
+
+    #[test]
+    fn shifts_for_powers_of_two() {
+        assert_eq!(peephole(vec![op("imul", vec![reg("rbx"), Arg::Imm(8)]), ret()]), ["shl ebx, 3", "ret"]);
+        let code = vec![op("mov", vec![reg("rcx"), Arg::Imm(8)]), op("xor", vec![reg("rdx"), reg("rdx")]), op("div", vec![reg("rcx")]), ret()];
+        assert_eq!(peephole(code), ["shr eax, 3", "ret"]);
+        let code = vec![op("mov", vec![reg("rcx"), Arg::Imm(4)]), op("cdq", Vec::new()), op("idiv", vec![reg("rcx")]), ret()];
+        assert_eq!(peephole(code), ["mov edx, eax", "sar edx, 31", "shr edx, 30", "add eax, edx", "sar eax, 2", "ret"]);
+    }
+}
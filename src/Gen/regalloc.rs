@@ -0,0 +1,354 @@
+//! Register allocation.
+//!
+//! Linear scan over the instructions in the order they are emitted: every
+//! virtual register gets one interval from the first to the last point it is
+//! live at, intervals are handed the free registers by their start and when
+//! none is left the one reaching furthest goes to the stack.
+//!
+//! rax, rcx, rdx and rsi are scratch registers of the emitter and never
+//! hold a value. Of the rest, r10, r11, rdi, r8 and r9 don't survive a call
+//! and only take intervals without one, rbx and r12 to r15 survive it but
+//! have to be saved by the prologue. rdi, r8 and r9 are written while the
+//! args of a call are set up and hold the args of the function until the
+//! prologue moved them to their homes, so the args themselves don't go in
+//! them. An interval across inline asm is always on the stack, the asm can
+//! use any register it wants.
+//!
+//! The copies left by leaving SSA form join their source and destination
+//! when the two are never live at the same time, the pair then shares one
+//! interval and the copy moves a register to itself.
+
+use std::collections::HashSet;
+
+use crate::Mir::{Function, Inst, Operand, VReg};
+
+use super::Gen;
+
+// not saved by a call, tried first since they cost nothing to use
+const CALLER_SAVED: &[&str] = &["r10", "r11", "rdi", "r8", "r9"];
+pub(super) const CALLEE_SAVED: &[&str] = &["rbx", "r12", "r13", "r14", "r15"];
+
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    vreg: VReg,
+    start: usize,
+    end: usize,
+    across_call: bool,
+    across_asm: bool,
+    // read while the args of a call or of the function are in their
+    // registers
+    meets_args: bool,
+}
+
+pub(super) struct Allocation {
+    // None for the registers on the stack or used nowhere
+    pub(super) regs: Vec<Option<&'static str>>,
+    // registers that need a home on the stack
+    pub(super) spilled: Vec<VReg>,
+}
+
+// live registers at the start and at the end of every block
+fn liveness(func: &Function) -> (Vec<HashSet<VReg>>, Vec<HashSet<VReg>>) {
+    let mut uses = vec![HashSet::new(); func.blocks.len()];
+    let mut defs = vec![HashSet::new(); func.blocks.len()];
+    for (index, block) in func.blocks.iter().enumerate() {
+        let operands = block.insts.iter()
+            .map(|inst| (inst.operands(), inst.dst()))
+            .chain(std::iter::once((block.term.operands(), None)));
+        for (operands, dst) in operands {
+            for vreg in operands.into_iter().filter_map(|operand| operand.vreg()) {
+                if !defs[index].contains(&vreg) {
+                    uses[index].insert(vreg);
+                }
+            }
+            if let Some(dst) = dst {
+                defs[index].insert(dst);
+            }
+        }
+    }
+    let mut live_in: Vec<HashSet<VReg>> = vec![HashSet::new(); func.blocks.len()];
+    let mut live_out: Vec<HashSet<VReg>> = vec![HashSet::new(); func.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..func.blocks.len()).rev() {
+            let out: HashSet<VReg> = func.blocks[index].term.successors().iter()
+                .flat_map(|succ| live_in[*succ].iter().copied())
+                .collect();
+            let mut live: HashSet<VReg> = out.difference(&defs[index]).copied().collect();
+            live.extend(uses[index].iter().copied());
+            if live != live_in[index] || out != live_out[index] {
+                live_in[index] = live;
+                live_out[index] = out;
+                changed = true;
+            }
+        }
+    }
+    (live_in, live_out)
+}
+
+// pairs of registers live at the same time, both ways round, found where
+// one of them is defined while the other is live. A copy doesn't make its
+// source interfere with its destination, they hold the same value
+fn interference(func: &Function, live_in: &[HashSet<VReg>], live_out: &[HashSet<VReg>]) -> HashSet<(VReg, VReg)> {
+    let mut edges = HashSet::new();
+    let mut add = |a: VReg, b: VReg| {
+        if a != b {
+            edges.insert((a, b));
+            edges.insert((b, a));
+        }
+    };
+    for param in func.params.iter() {
+        for other in func.params.iter().chain(live_in[0].iter()) {
+            add(*param, *other);
+        }
+    }
+    for (index, block) in func.blocks.iter().enumerate() {
+        let mut live = live_out[index].clone();
+        live.extend(block.term.operands().into_iter().filter_map(|operand| operand.vreg()));
+        for inst in block.insts.iter().rev() {
+            if let Some(dst) = inst.dst() {
+                let copied = match inst {
+                    Inst::Copy { src: Operand::Reg(src), .. } => Some(*src),
+                    _ => None,
+                };
+                for other in live.iter().filter(|other| Some(**other) != copied) {
+                    add(dst, *other);
+                }
+                live.remove(&dst);
+            }
+            live.extend(inst.operands().into_iter().filter_map(|operand| operand.vreg()));
+        }
+    }
+    edges
+}
+
+// the register every register shares its interval with, itself when it
+// wasn't joined with any
+fn coalesce(func: &Function, live_in: &[HashSet<VReg>], live_out: &[HashSet<VReg>]) -> Vec<VReg> {
+    let edges = interference(func, live_in, live_out);
+    let mut class: Vec<VReg> = (0..func.vregs.len()).collect();
+    let mut members: Vec<Vec<VReg>> = (0..func.vregs.len()).map(|vreg| vec![vreg]).collect();
+    for inst in func.blocks.iter().flat_map(|block| block.insts.iter()) {
+        let Inst::Copy { dst, src: Operand::Reg(src), .. } = inst else {
+            continue;
+        };
+        let (a, b) = (class[*dst], class[*src]);
+        if a == b || members[a].iter().any(|x| members[b].iter().any(|y| edges.contains(&(*x, *y)))) {
+            continue;
+        }
+        let moved = std::mem::take(&mut members[b]);
+        for vreg in moved.iter() {
+            class[*vreg] = a;
+        }
+        members[a].extend(moved);
+    }
+    class
+}
+
+// the params are defined at point 0, then every block has a point for its
+// start and one for every instruction, the terminator is its last one. A
+// joined register extends the interval of its class
+fn intervals(func: &Function, live_in: &[HashSet<VReg>], live_out: &[HashSet<VReg>], class: &[VReg]) -> Vec<Interval> {
+    let mut range: Vec<Option<(usize, usize)>> = vec![None; func.vregs.len()];
+    let mut extend = |vreg: VReg, pos: usize| {
+        let vreg = class[vreg];
+        range[vreg] = Some(match range[vreg] {
+            Some((start, end)) => (start.min(pos), end.max(pos)),
+            None => (pos, pos),
+        });
+    };
+    for param in func.params.iter() {
+        extend(*param, 0);
+    }
+    let mut calls = Vec::new();
+    let mut asms = Vec::new();
+    let mut pos = 0;
+    for (index, block) in func.blocks.iter().enumerate() {
+        pos += 1;
+        for vreg in live_in[index].iter() {
+            extend(*vreg, pos);
+        }
+        for inst in block.insts.iter() {
+            pos += 1;
+            for vreg in inst.operands().into_iter().filter_map(|operand| operand.vreg()) {
+                extend(vreg, pos);
+            }
+            if let Some(dst) = inst.dst() {
+                extend(dst, pos);
+            }
+            match inst {
+                Inst::Call { .. } => calls.push(pos),
+                Inst::Asm { .. } => asms.push(pos),
+                _ => {}
+            }
+        }
+        pos += 1;
+        for vreg in block.term.operands().into_iter().filter_map(|operand| operand.vreg()) {
+            extend(vreg, pos);
+        }
+        for vreg in live_out[index].iter() {
+            extend(*vreg, pos);
+        }
+    }
+    range.iter().enumerate()
+        .filter_map(|(vreg, range)| range.map(|(start, end)| Interval {
+            vreg,
+            start,
+            end,
+            // the args of a call and its result are in other registers
+            across_call: calls.iter().any(|call| start < *call && *call < end),
+            across_asm: asms.iter().any(|asm| start < *asm && *asm < end),
+            // an interval ending at a call is one of its args
+            meets_args: start == 0 || (start < end && calls.contains(&end)),
+        }))
+        .collect()
+}
+
+pub(super) fn allocate(func: &Function) -> Allocation {
+    let (live_in, live_out) = liveness(func);
+    let class = coalesce(func, &live_in, &live_out);
+    let mut intervals = intervals(func, &live_in, &live_out, &class);
+    intervals.sort_by_key(|interval| (interval.start, interval.vreg));
+    let mut regs: Vec<Option<&'static str>> = vec![None; func.vregs.len()];
+    let mut spilled = Vec::new();
+    // intervals holding a register, with it
+    let mut active: Vec<(Interval, &'static str)> = Vec::new();
+    for interval in intervals {
+        active.retain(|(other, _)| other.end >= interval.start);
+        if interval.across_asm {
+            spilled.push(interval.vreg);
+            continue;
+        }
+        let pool: Vec<&'static str> = if interval.across_call {
+            CALLEE_SAVED.to_vec()
+        }
+        else {
+            CALLER_SAVED.iter().chain(CALLEE_SAVED.iter()).copied()
+                .filter(|reg| !(interval.meets_args && Gen::ARG_REGS_64.contains(reg)))
+                .collect()
+        };
+        if let Some(reg) = pool.iter().find(|reg| !active.iter().any(|(_, taken)| taken == *reg)) {
+            regs[interval.vreg] = Some(reg);
+            active.push((interval, reg));
+            continue;
+        }
+        // the one reaching furthest gives up its register when that's
+        // further than this one reaches
+        let furthest = active.iter().enumerate()
+            .filter(|(_, (_, reg))| pool.contains(reg))
+            .max_by_key(|(_, (other, _))| other.end)
+            .map(|(pos, _)| pos);
+        match furthest {
+            Some(pos) if active[pos].0.end > interval.end => {
+                let (other, reg) = active.remove(pos);
+                regs[other.vreg] = None;
+                spilled.push(other.vreg);
+                regs[interval.vreg] = Some(reg);
+                active.push((interval, reg));
+            }
+            _ => spilled.push(interval.vreg),
+        }
+    }
+    // the joined registers go where their class went
+    for vreg in 0..func.vregs.len() {
+        regs[vreg] = regs[class[vreg]];
+    }
+    let spilled = (0..func.vregs.len()).filter(|vreg| spilled.contains(&class[*vreg])).collect();
+    Allocation { regs, spilled }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mir::{test_module, BinOp, Block, CastKind, Cond, Module, Terminator, Ty};
+
+    fn add(dst: VReg, lhs: Operand, rhs: Operand) -> Inst {
+        Inst::Bin { dst, op: BinOp::Add, ty: Ty::I32, lhs, rhs }
+    }
+
+    fn build(module: &Module) -> (Vec<Interval>, Allocation) {
+        let func = &module.functions[0];
+        let (live_in, live_out) = liveness(func);
+        let class = coalesce(func, &live_in, &live_out);
+        let mut intervals = intervals(func, &live_in, &live_out, &class);
+        intervals.sort_by_key(|interval| interval.vreg);
+        (intervals, allocate(func))
+    }
+
+    #[test]
+    fn intervals_around_a_call() {
+        // the param is used after the call, its arg ends at it
+        let blocks = vec![Block {
+            insts: vec![
+                add(1, Operand::Reg(0), Operand::Imm(1)),
+                Inst::Cast { dst: 2, kind: CastKind::Sext, from: Ty::I32, to: Ty::I64, src: Operand::Reg(1) },
+                Inst::Call { dst: Some(3), callee: "f".to_string(), args: vec![(Ty::I64, Operand::Reg(2))] },
+                add(4, Operand::Reg(3), Operand::Reg(0)),
+            ],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(4)))),
+        }];
+        let module = test_module(&[Ty::I32, Ty::I32, Ty::I64, Ty::I32, Ty::I32], 1, &[], blocks);
+        let (intervals, allocation) = build(&module);
+        let found: Vec<(VReg, usize, usize, bool, bool)> = intervals.iter()
+            .map(|interval| (interval.vreg, interval.start, interval.end, interval.across_call, interval.meets_args))
+            .collect();
+        assert_eq!(found, vec![(0, 0, 5, true, true), (1, 2, 3, false, false), (2, 3, 4, false, true), (3, 4, 5, false, false), (4, 5, 6, false, false)]);
+        assert!(CALLEE_SAVED.contains(&allocation.regs[0].unwrap()));
+        assert!(!Gen::ARG_REGS_64.contains(&allocation.regs[2].unwrap()));
+        assert!(allocation.spilled.is_empty());
+    }
+
+    #[test]
+    fn spills_what_reaches_furthest() {
+        // twelve values live at once for ten registers, summed up in order
+        let mut insts: Vec<Inst> = (0..12).map(|vreg| Inst::Copy { dst: vreg, ty: Ty::I32, src: Operand::Imm(vreg as i64) }).collect();
+        insts.push(add(12, Operand::Reg(0), Operand::Reg(1)));
+        for vreg in 2..12 {
+            insts.push(add(vreg + 11, Operand::Reg(vreg + 10), Operand::Reg(vreg)));
+        }
+        let blocks = vec![Block { insts, term: Terminator::Ret(Some((Ty::I32, Operand::Reg(22)))) }];
+        let module = test_module(&[Ty::I32; 23], 0, &[], blocks);
+        let (_, allocation) = build(&module);
+        // the first sum is live while its operands are read, %9 gives up
+        // its register for it
+        assert_eq!(allocation.spilled, vec![9, 10, 11]);
+        // the ones live together at the first sum
+        let mut regs: Vec<&str> = (0..9).chain([12]).map(|vreg| allocation.regs[vreg].unwrap()).collect();
+        regs.sort();
+        regs.dedup();
+        assert_eq!(regs.len(), 10);
+    }
+
+    // a counter left out of ssa, the copy back to it at the end of the
+    // loop and, with interfering, one more use of the old value after the
+    // new one is computed
+    fn counter(interfering: bool) -> Module {
+        let mut body = vec![add(2, Operand::Reg(0), Operand::Imm(1))];
+        if interfering {
+            body.push(Inst::Store { ty: Ty::I32, ptr: Operand::Reg(3), src: Operand::Reg(0) });
+        }
+        body.push(Inst::Copy { dst: 0, ty: Ty::I32, src: Operand::Reg(2) });
+        let blocks = vec![
+            Block {
+                insts: vec![Inst::SlotAddr { dst: 3, slot: 0 }, Inst::Copy { dst: 0, ty: Ty::I32, src: Operand::Imm(0) }],
+                term: Terminator::Jump(1),
+            },
+            Block {
+                insts: vec![Inst::Cmp { dst: 1, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Imm(10) }],
+                term: Terminator::Branch { cond: Operand::Reg(1), then: 2, other: 3 },
+            },
+            Block { insts: body, term: Terminator::Jump(1) },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))) },
+        ];
+        test_module(&[Ty::I32, Ty::I8, Ty::I32, Ty::I64], 0, &[4], blocks)
+    }
+
+    #[test]
+    fn coalesces_copies_without_interference() {
+        let (_, allocation) = build(&counter(false));
+        assert_eq!(allocation.regs[0], allocation.regs[2]);
+        let (_, allocation) = build(&counter(true));
+        assert_ne!(allocation.regs[0], allocation.regs[2]);
+    }
+}
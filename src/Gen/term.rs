@@ -0,0 +1,136 @@
+use super::*;
+use super::asm::{Arg, Base, Mem};
+use crate::Mir::{BlockId, Operand, Terminator};
+
+impl Terminator {
+    pub(super) fn eval(&self, gen_helper: &mut Gen, block: BlockId) {
+        match self {
+            Terminator::Jump(target) => gen_helper.emit_jump(block, *target),
+            Terminator::Branch { cond: Operand::Imm(value), then, other } => {
+                let target = if *value != 0 { then } else { other };
+                gen_helper.emit_jump(block, *target);
+            }
+            Terminator::Branch { cond, then, other } => {
+                gen_helper.load("rax", Ty::I8, *cond);
+                gen_helper.op("test", vec![Arg::reg("rax", Ty::I8), Arg::reg("rax", Ty::I8)]);
+                gen_helper.op("jne", vec![Arg::Label(gen_helper.block_label(*then))]);
+                gen_helper.emit_jump(block, *other);
+            }
+            Terminator::Switch { ty, signed, value, cases, default } => {
+                let id = gen_helper.get_id();
+                gen_helper.label(format!("switch_{}",id));
+                gen_helper.load("rax", *ty, *value);
+                // values ordered the way the switch type compares them
+                let mask = u64::MAX >> (64 - ty.size() * 8);
+                let mut cases: Vec<(i128, String)> = cases.iter()
+                    .map(|(value, target)| {
+                        let key = if *signed { *value as i128 } else { (*value as u64 & mask) as i128 };
+                        (key, gen_helper.block_label(*target))
+                    })
+                    .collect();
+                cases.sort_by_key(|(value, _)| *value);
+                let default = gen_helper.block_label(*default);
+                if Terminator::is_dense(&cases) {
+                    Terminator::emit_jump_table(gen_helper, id, *ty, *signed, &cases, &default);
+                }
+                else {
+                    Terminator::emit_case_tree(gen_helper, *ty, *signed, &cases, &default);
+                }
+            }
+            Terminator::Ret(value) => {
+                if let Some((ty, value)) = value {
+                    gen_helper.load("rax", *ty, *value);
+                }
+                // every return shares the epilogue after the last block
+                gen_helper.emit_jump(block, gen_helper.epilogue);
+            }
+            // nothing jumps to it
+            Terminator::Unreachable => {}
+        }
+    }
+
+    // a table pays off when at least half of its slots are cases
+    fn is_dense(cases: &[(i128, String)]) -> bool {
+        let (Some(first), Some(last)) = (cases.first(), cases.last()) else {
+            return false;
+        };
+        cases.len() >= 4 && last.0 - first.0 < 2 * cases.len() as i128
+    }
+
+    // the value minus the lowest case indexes a table of offsets from the
+    // switch label, offsets keep it working in position independent code
+    fn emit_jump_table(gen_helper: &mut Gen, id: usize, ty: Ty, signed: bool, cases: &[(i128, String)], default: &str) {
+        let min = cases.first().unwrap().0;
+        let max = cases.last().unwrap().0;
+        match (ty, signed) {
+            (Ty::I64, _) => {}
+            (Ty::I32, true) => gen_helper.op("movsxd", vec![Arg::reg("rax", Ty::I64), Arg::reg("rax", Ty::I32)]),
+            // writing the low 32 bits clears the upper ones
+            (Ty::I32, false) => gen_helper.op("mov", vec![Arg::reg("rax", Ty::I32), Arg::reg("rax", Ty::I32)]),
+            (_, true) => gen_helper.op("movsx", vec![Arg::reg("rax", Ty::I64), Arg::reg("rax", ty)]),
+            (_, false) => gen_helper.op("movzx", vec![Arg::reg("rax", Ty::I64), Arg::reg("rax", ty)]),
+        }
+        if min != 0 {
+            Terminator::emit_with_imm(gen_helper, "sub", Ty::I64, min);
+        }
+        // below the lowest case wraps around to a big unsigned value
+        gen_helper.op("cmp", vec![Arg::reg("rax", Ty::I64), Arg::Imm((max - min) as i64)]);
+        gen_helper.op("ja", vec![Arg::Label(default.to_string())]);
+
+        let mut entries: Vec<String> = vec![default.to_string(); (max - min + 1) as usize];
+        for (value, label) in cases {
+            entries[(value - min) as usize] = label.clone();
+        }
+        let entries = entries.iter().map(|label| format!("{} - switch_{}",label, id)).collect();
+        let table = format!("switch_table_{}",id);
+        let (rax, rdx) = (Arg::reg("rax", Ty::I64), Arg::reg("rdx", Ty::I64));
+        let entry = Arg::Mem(Mem { ty: Some(Ty::I32), base: Base::Reg("rdx"), index: Some(("rax", 4)), disp: 0 });
+        gen_helper.op("lea", vec![rdx.clone(), Arg::rel(table.clone())]);
+        gen_helper.op("movsxd", vec![rax.clone(), entry]);
+        gen_helper.op("lea", vec![rdx.clone(), Arg::rel(format!("switch_{}",id))]);
+        gen_helper.op("add", vec![rax.clone(), rdx]);
+        gen_helper.op("jmp", vec![rax]);
+        gen_helper.jump_tables.push((table, entries));
+    }
+
+    // binary search over the sorted cases, the last few are compared one by one
+    fn emit_case_tree(gen_helper: &mut Gen, ty: Ty, signed: bool, cases: &[(i128, String)], default: &str) {
+        if cases.len() <= 3 {
+            for (value, label) in cases {
+                Terminator::emit_with_imm(gen_helper, "cmp", ty, ty.wrap(*value) as i128);
+                gen_helper.op("je", vec![Arg::Label(label.clone())]);
+            }
+            gen_helper.op("jmp", vec![Arg::Label(default.to_string())]);
+            return;
+        }
+        let mid = cases.len() / 2;
+        let (value, label) = &cases[mid];
+        let lower = format!("case_tree_{}",gen_helper.get_id());
+        Terminator::emit_with_imm(gen_helper, "cmp", ty, ty.wrap(*value) as i128);
+        gen_helper.op("je", vec![Arg::Label(label.clone())]);
+        gen_helper.op(if signed { "jl" } else { "jb" }, vec![Arg::Label(lower.clone())]);
+        Terminator::emit_case_tree(gen_helper, ty, signed, &cases[mid + 1..], default);
+        gen_helper.label(lower);
+        Terminator::emit_case_tree(gen_helper, ty, signed, &cases[..mid], default);
+    }
+
+    // 64 bit instructions only take a sign extended 32 bit immediate
+    fn emit_with_imm(gen_helper: &mut Gen, op: &'static str, ty: Ty, value: i128) {
+        if ty == Ty::I64 && i32::try_from(value).is_err() {
+            gen_helper.op("mov", vec![Arg::reg("rdx", ty), Arg::Imm(value as i64)]);
+            gen_helper.op(op, vec![Arg::reg("rax", ty), Arg::reg("rdx", ty)]);
+        }
+        else {
+            gen_helper.op(op, vec![Arg::reg("rax", ty), Arg::Imm(value as i64)]);
+        }
+    }
+}
+
+impl Gen {
+    // blocks are emitted in order, a jump to the next one falls through
+    fn emit_jump(&mut self, from: BlockId, to: BlockId) {
+        if to != from + 1 {
+            self.op("jmp", vec![Arg::Label(self.block_label(to))]);
+        }
+    }
+}
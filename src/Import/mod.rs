@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::Tokenizer::{Token, Tokenizer};
+
+// finds and reads the files of `import "path.v";`, the preprocessor
+// splices their tokens in place of the import as it comes across it
+pub struct Importer {
+    m_search_paths: Vec<PathBuf>,
+    // every file is included once per compilation unit
+    m_included: HashSet<PathBuf>,
+    // files that are being imported right now, an import of one of
+    // them is a cycle
+    m_stack: Vec<PathBuf>,
+}
+
+impl Importer {
+    pub fn new(search_paths: Vec<String>) -> Self {
+        Importer {
+            m_search_paths: search_paths.into_iter().map(PathBuf::from).collect(),
+            m_included: HashSet::new(),
+            m_stack: Vec::new(),
+        }
+    }
+
+    // the tokens of the file being compiled
+    pub fn load(&mut self, path: &Path) -> Result<Vec<Token>, Box<dyn std::error::Error>> {
+        let full_path = fs::canonicalize(path)
+            .map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+        self.m_included.insert(full_path.clone());
+        let tokens = Importer::read(path, &full_path)?;
+        self.m_stack.push(full_path);
+        Ok(tokens)
+    }
+
+    // the tokens of the file named by the string token of an import, None
+    // when it was already included, finish has to be called once they are
+    // preprocessed
+    pub fn import(&mut self, file: &Token) -> Result<Option<Vec<Token>>, String> {
+        let name = file.value.clone().unwrap();
+        let import_path = self.resolve(Path::new(file.span.file.as_ref()), &name)
+            .ok_or_else(|| format!("{}: cannot find import \"{}\"", file.span, name))?;
+        let full_import_path = fs::canonicalize(&import_path)
+            .map_err(|e| format!("{}: cannot open {}: {}", file.span, import_path.display(), e))?;
+
+        if let Some(pos) = self.m_stack.iter().position(|v| *v == full_import_path) {
+            let cycle: Vec<String> = self.m_stack[pos..].iter()
+                .chain(std::iter::once(&full_import_path))
+                .map(|v| v.display().to_string())
+                .collect();
+            return Err(format!("{}: import cycle: {}", file.span, cycle.join(" -> ")));
+        }
+        if !self.m_included.insert(full_import_path.clone()) {
+            return Ok(None);
+        }
+        let tokens = Importer::read(&import_path, &full_import_path)
+            .map_err(|e| format!("{}: {}", file.span, e))?;
+        self.m_stack.push(full_import_path);
+        Ok(Some(tokens))
+    }
+
+    // the end of the file of the last import
+    pub fn finish(&mut self) {
+        self.m_stack.pop();
+    }
+
+    fn read(path: &Path, full_path: &Path) -> Result<Vec<Token>, String> {
+        let contents = fs::read_to_string(full_path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let mut tokenizer = Tokenizer::new(contents, Rc::from(path.to_string_lossy().as_ref()));
+        tokenizer.tokenize();
+        Ok(tokenizer.m_res)
+    }
+
+    // relative to the importing file first, then the -I paths in order
+    fn resolve(&self, from: &Path, name: &str) -> Option<PathBuf> {
+        let dir = from.parent().unwrap_or(Path::new(""));
+        std::iter::once(dir.to_path_buf())
+            .chain(self.m_search_paths.iter().cloned())
+            .map(|v| v.join(name))
+            .find(|v| v.is_file())
+    }
+}
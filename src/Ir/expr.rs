@@ -1,9 +1,12 @@
-use crate::Tokenizer::Token;
+use crate::Tokenizer::{Span, Token};
+use crate::Ir::stmt::TypeInfo;
+use crate::Ir::sema::SymbolId;
 
 
 #[derive(Debug, Clone)]
 pub(crate) enum RpnExpr {
     PushNum(PushNum),
+    PushStr(PushStr),
     PushVar(PushVar),
     Operator(Operator),
     Function(Function),
@@ -13,57 +16,127 @@ pub(crate) enum RpnExpr {
     GetAddr(GetAddr),
     GetSizeOf(GetSizeOf),
     GetStructValue(GetStructValue),
+    Convert(Convert),
+    Cast(Cast),
+}
+
+impl RpnExpr {
+    pub(crate) fn set_ty(&mut self, value: TypeInfo) {
+        let ty = match self {
+            RpnExpr::PushNum(v) => &mut v.ty,
+            RpnExpr::PushStr(v) => &mut v.ty,
+            RpnExpr::PushVar(v) => &mut v.ty,
+            RpnExpr::Operator(v) => &mut v.ty,
+            RpnExpr::Function(v) => &mut v.ty,
+            RpnExpr::Negative(v) => &mut v.ty,
+            RpnExpr::GetArrayValue(v) => &mut v.ty,
+            RpnExpr::Deref(v) => &mut v.ty,
+            RpnExpr::GetAddr(v) => &mut v.ty,
+            RpnExpr::GetSizeOf(v) => &mut v.ty,
+            RpnExpr::GetStructValue(v) => &mut v.ty,
+            RpnExpr::Convert(v) => &mut v.ty,
+            RpnExpr::Cast(v) => &mut v.ty,
+        };
+        *ty = Some(value);
+    }
 }
 
 
 #[derive(Debug, Clone)]
 pub(crate) struct GetStructValue {
-    pub(crate) var_name: String,
+    pub(crate) var_name: Token,
     pub(crate) struct_value_name: String,
+    pub(crate) sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 
 
 #[derive(Debug, Clone)]
 pub(crate) struct GetSizeOf {
     pub(crate) var: Token,
+    // set for `sizeof(type)`, var is then just the '(' before it
+    pub(crate) of_type: Option<TypeInfo>,
+    pub(crate) sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 
 
 #[derive(Debug, Clone)]
 pub(crate) struct GetAddr {
     pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Deref {
     pub(crate) var: Token,
     pub(crate) stack_depth: u32,
+    pub(crate) sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct GetArrayValue {
     pub(crate) name: Token,
     pub(crate) index: Token,
+    pub(crate) sym: Option<SymbolId>,
+    // set when the index is a variable
+    pub(crate) index_sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Negative {
     pub(crate) data: Token,
+    // set when negating a variable
+    pub(crate) sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 #[derive(Debug, Clone)]
 pub(crate) struct PushNum {
     pub(crate) data: Token,
+    pub(crate) ty: Option<TypeInfo>,
+}
+#[derive(Debug, Clone)]
+pub(crate) struct PushStr {
+    pub(crate) data: Token,
+    pub(crate) ty: Option<TypeInfo>,
 }
 #[derive(Debug, Clone)]
 pub(crate) struct PushVar {
     pub(crate) data: Token,
+    pub(crate) sym: Option<SymbolId>,
+    pub(crate) ty: Option<TypeInfo>,
 }
 #[derive(Debug, Clone)]
 pub(crate) struct Operator {
     pub(crate) data: Token,
+    pub(crate) ty: Option<TypeInfo>,
 }
 #[derive(Debug, Clone)]
 pub(crate) struct Function {
     pub(crate) name: Token,
-    pub(crate) args: Vec<Token>,
-}
\ No newline at end of file
+    pub(crate) args: Vec<Vec<RpnExpr>>,
+    pub(crate) ty: Option<TypeInfo>,
+}
+
+// added by sema wherever a value changes type, ty is the type after it
+#[derive(Debug, Clone)]
+pub(crate) struct Convert {
+    pub(crate) from: TypeInfo,
+    // the expression being converted
+    pub(crate) span: Span,
+    pub(crate) ty: Option<TypeInfo>,
+}
+
+// `(long)x`, applies to the value before it in the rpn
+#[derive(Debug, Clone)]
+pub(crate) struct Cast {
+    pub(crate) to: TypeInfo,
+    // the opening paren
+    pub(crate) span: Span,
+    // type of the value being cast, set by sema
+    pub(crate) from: Option<TypeInfo>,
+    pub(crate) ty: Option<TypeInfo>,
+}
@@ -1,46 +1,16 @@
 use std::collections::HashMap;
 
-use crate::Tokenizer::{Token, TokenType};
 use crate::Ir::stmt::{Arg, StructArg, TypeInfo};
 
 
-#[derive(Debug)]
-pub struct ExprStackHelper {
-    pub stack: Vec<ExprStack>,
-
-}
-
-#[derive(Debug)]
-pub(crate) struct ExprStack {
-    pub(crate) reg: String,
-    pub(crate) var_type: TokenType,
-    pub(crate) pointer_depth: u32,
-}
-#[derive(Debug)]
-// ????????
-pub(crate) struct ArrData {
-    pub(crate) size: u32,
-}
-#[derive(Debug)]
-pub(crate) struct VarStructData {
-    pub(crate) struct_name: String,
-}
-#[derive(Debug)]
-pub(crate) struct VarData {
-    pub(crate) stack_pos: i32,
-    pub(crate) scope_depth: usize,
-    pub(crate) var_type: TokenType,
-    pub(crate) arr_data: Option<ArrData>,
-    pub(crate) struct_data: Option<VarStructData>,
-    pub(crate) pointer_depth: u32,
-}
-
-
 #[derive(Debug, Clone)]
 pub(crate) struct FuncData {
     pub(crate) args: Vec<Arg>,
     // return type and pointer depth
     pub(crate) return_type: TypeInfo,
+    pub(crate) variadic: bool,
+    // declared but defined in another object, called through the plt
+    pub(crate) is_extern: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -48,3 +18,25 @@ pub(crate) struct StructData {
     pub(crate) elements: HashMap<String, StructArg>,
     pub(crate) element_size: u32,
 }
+
+// where a virtual register lives
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Home {
+    // named by its 64 bit name
+    Reg(&'static str),
+    // 8 bytes below rbp
+    Stack(u32),
+}
+
+// where everything of the function being emitted lives, offsets are below rbp
+#[derive(Debug, Default)]
+pub(crate) struct Frame {
+    // start of every slot of the function
+    pub(crate) slots: Vec<u32>,
+    // None for the virtual registers used nowhere
+    pub(crate) vregs: Vec<Option<Home>>,
+    // callee saved registers the function uses, kept below the homes
+    pub(crate) saved: Vec<(&'static str, u32)>,
+    // what the prologue reserves, a multiple of 16
+    pub(crate) size: u32,
+}
@@ -1,4 +1,5 @@
 pub mod stmt;
 pub use stmt::Stmt;
 pub mod expr;
-pub mod r#gen;
\ No newline at end of file
+pub mod r#gen;
+pub mod sema;
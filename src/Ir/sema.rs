@@ -0,0 +1,26 @@
+use crate::Ir::stmt::TypeInfo;
+use crate::Tokenizer::Span;
+
+// index into the symbol table of a file, every declaration gets its own
+// so two variables with the same name in different scopes never mix up
+pub(crate) type SymbolId = usize;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Symbol {
+    pub(crate) name: String,
+    pub(crate) ty: TypeInfo,
+    // arrays decay to a pointer to their first element but can't be assigned
+    pub(crate) is_array: bool,
+    // where it was declared
+    pub(crate) span: Span,
+    // its value is read somewhere, storing to it doesn't count
+    pub(crate) used: bool,
+}
+
+// a name from the braces of an enum, stands for its value
+#[derive(Debug, Clone)]
+pub(crate) struct EnumConstant {
+    pub(crate) value: i64,
+    pub(crate) enum_name: String,
+    pub(crate) span: Span,
+}
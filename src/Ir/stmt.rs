@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
-use crate::Tokenizer::{Token, TokenType};
+use crate::Tokenizer::{Span, Token, TokenType};
 use crate::Ir::expr::RpnExpr;
+use crate::Ir::sema::SymbolId;
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
@@ -12,9 +13,14 @@ pub enum Stmt {
     IfStmt(IfStmt),
     WhileStmt(WhileStmt),
     ForStmt(ForStmt),
+    DoWhileStmt(DoWhileStmt),
+    SwitchStmt(SwitchStmt),
+    Break(Break),
+    Continue(Continue),
     IncVar(IncVar),
     DecVar(DecVar),
     InitFunc(InitFunc),
+    FuncDecl(FuncDecl),
     Ret(Ret),
     FunctionCall(FunctionCall),
     AsmCode(AsmCode),
@@ -23,6 +29,7 @@ pub enum Stmt {
     CreatePointer(CreatePointer),
     ChangePtrValue(ChangePtrValue),
     InitStruct(InitStruct),
+    InitEnum(InitEnum),
     CreateStruct(CreateStruct),
     ChangeStructValue(ChangeStructValue),
     ChangePtrStructValue(ChangePtrStructValue),
@@ -32,7 +39,8 @@ pub enum Stmt {
 
 #[derive(Debug, Clone)]
 pub(crate) struct ChangePtrStructValue {
-    pub(crate) struct_name: String,
+    pub(crate) struct_name: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) value_name: String,
     pub(crate) expr: Vec<RpnExpr>,
 }
@@ -41,7 +49,8 @@ pub(crate) struct ChangePtrStructValue {
 
 #[derive(Debug, Clone)]
 pub(crate) struct ChangeStructValue {
-    pub(crate) struct_name: String,
+    pub(crate) struct_name: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) value_name: String,
     pub(crate) expr: Vec<RpnExpr>,
 }
@@ -50,7 +59,8 @@ pub(crate) struct ChangeStructValue {
 #[derive(Debug, Clone)]
 pub(crate) struct CreateStruct {
     pub(crate) struct_name: String,
-    pub(crate) var_name: String,
+    pub(crate) var_name: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) pointer_depth: u32,
     pub(crate) expr: Option<Vec<RpnExpr>>,
 }
@@ -70,13 +80,34 @@ pub(crate) struct StructArg {
 #[derive(Debug, Clone)]
 pub(crate) struct InitStruct {
     pub(crate) name: String,
+    // of the name
+    pub(crate) span: Span,
     pub(crate) elements: HashMap<String, StructArg>,
 }
 
 
+#[derive(Debug, Clone)]
+pub(crate) struct Enumerator {
+    pub(crate) name: Token,
+    // the literal after '=', the enumerator before plus one without it
+    pub(crate) init: Option<Token>,
+    pub(crate) negative: bool,
+    // filled in by sema
+    pub(crate) value: Option<i64>,
+}
+
+// enum Color { Red, Green = 5, Blue };
+#[derive(Debug, Clone)]
+pub(crate) struct InitEnum {
+    pub(crate) name: Token,
+    pub(crate) enumerators: Vec<Enumerator>,
+}
+
+
 #[derive(Debug, Clone)]
 pub(crate) struct ChangePtrValue {
-    pub(crate) var: String,
+    pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) stmt: Vec<RpnExpr>,
     pub(crate) pointer_depth: u32
 }
@@ -84,7 +115,8 @@ pub(crate) struct ChangePtrValue {
 #[derive(Debug, Clone)]
 pub(crate) struct CreatePointer {
     pub(crate) type_: TokenType,
-    pub(crate) var: String,
+    pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) stmt: Vec<RpnExpr>,
     pub(crate) pointer_depth: u32
 }
@@ -93,12 +125,16 @@ pub(crate) struct CreatePointer {
 pub(crate) struct ChangeArrElement {
     pub(crate) arr_name: Token,
     pub(crate) element: Token,
+    pub(crate) sym: Option<SymbolId>,
+    // set when the index is a variable
+    pub(crate) element_sym: Option<SymbolId>,
     pub(crate) expr: Vec<RpnExpr>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct InitArray {
     pub(crate) name: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) arr_type: Token,
     pub(crate) size: Token,
     pub(crate) data: Vec<Token>,
@@ -109,6 +145,8 @@ pub(crate) struct InitArray {
 #[derive(Debug, Clone)]
 pub(crate) struct AsmCode {
     pub(crate) code: Vec<String>,
+    // what every (var) in the code refers to
+    pub(crate) vars: HashMap<String, SymbolId>,
 }
 
 #[derive(Debug, Clone)]
@@ -121,12 +159,16 @@ pub(crate) struct FunctionCall {
 pub(crate) struct Ret {
     pub(crate) expr: Vec<RpnExpr>,
     pub(crate) func_name: String,
+    // the return keyword
+    pub(crate) span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct TypeInfo {
     pub(crate) var_type: TokenType,
     pub(crate) pointer_depth: u32,
+    // set when var_type is Struct or Enum
+    pub(crate) struct_name: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -135,25 +177,54 @@ pub(crate) struct InitFunc {
     pub(crate) name: Token,
     // type and pointer depth
     pub(crate) return_type: TypeInfo,
-    pub(crate) data: Vec<Stmt>
+    pub(crate) data: Vec<Stmt>,
+    // `export`/`pub`, visible to other objects and callable from C
+    pub(crate) exported: bool,
+    pub(crate) inline: Inline,
+}
 
+// what the source asks of the inliner
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) enum Inline {
+    // up to the size of the body
+    #[default]
+    Auto,
+    // `inline`, worth inlining a bigger body
+    Hint,
+    // `noinline`, always called
+    Never,
 }
+// function declared without a body, e.g. `extern int printf(char* fmt, ...);`
+// or a prototype `int f(int a, long b);`
+#[derive(Debug, Clone)]
+pub(crate) struct FuncDecl {
+    pub(crate) args: Vec<Arg>,
+    pub(crate) name: Token,
+    pub(crate) return_type: TypeInfo,
+    // takes extra args after the named ones
+    pub(crate) variadic: bool,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Arg {
     pub(crate) arg_type: Token,
+    // tag of a struct or enum arg
     pub(crate) struct_name: Option<String>,
     pub(crate) pointer_depth: u32,
     pub(crate) name: Token,
+    pub(crate) sym: Option<SymbolId>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct IncVar {
     pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct DecVar {
     pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
 }
 
 #[derive(Debug, Clone)]
@@ -162,15 +233,55 @@ pub(crate) struct WhileStmt {
     pub(crate) data: Vec<Stmt>,
 }
 
+// do { ... } while (cond);
+#[derive(Debug, Clone)]
+pub(crate) struct DoWhileStmt {
+    pub(crate) data: Vec<Stmt>,
+    pub(crate) expr: Vec<RpnExpr>,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct ForStmt {
-    pub(crate) expr1: Box<Stmt>,
+    // every clause can be left out, an empty condition is always true
+    pub(crate) expr1: Vec<Stmt>,
     pub(crate) expr2: Vec<RpnExpr>,
-    pub(crate) expr3: Box<Stmt>,
+    pub(crate) expr3: Vec<Stmt>,
     pub(crate) data: Vec<Stmt>,
 }
 
 
+// `case K:` or `default:`
+#[derive(Debug, Clone)]
+pub(crate) struct SwitchCase {
+    // None for default
+    pub(crate) expr: Option<Vec<RpnExpr>>,
+    // filled in by sema, as the type of the switch holds it
+    pub(crate) value: Option<i64>,
+    // index of the stmt of the body the label is in front of
+    pub(crate) pos: usize,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SwitchStmt {
+    pub(crate) expr: Vec<RpnExpr>,
+    pub(crate) cases: Vec<SwitchCase>,
+    pub(crate) data: Vec<Stmt>,
+    // the switch keyword
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Break {
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Continue {
+    pub(crate) span: Span,
+}
+
+
 #[derive(Debug, Clone)]
 pub(crate) struct IfStmt {
     pub(crate) expr: Vec<RpnExpr>,
@@ -180,12 +291,16 @@ pub(crate) struct IfStmt {
 #[derive(Debug, Clone)]
 pub(crate) struct CreateVar {
     pub(crate) Type: TokenType,
-    pub(crate) var: String,
+    // set when Type is Enum
+    pub(crate) enum_name: Option<String>,
+    pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) stmt: Vec<RpnExpr>,
 }
 #[derive(Debug, Clone)]
 pub(crate) struct ChangeVar {
-    pub(crate) var: String,
+    pub(crate) var: Token,
+    pub(crate) sym: Option<SymbolId>,
     pub(crate) stmt: Vec<RpnExpr>,
 }
 
@@ -0,0 +1,383 @@
+//! Constant folding and propagation.
+//!
+//! Sparse conditional constant propagation over SSA form: every register
+//! starts out unknown, a block only counts once an edge into it can be taken
+//! and a phi only merges the values coming over such edges, so a variable
+//! keeps its constant through a loop that never changes it. Afterwards every
+//! constant register is replaced by its value, the instructions computing
+//! them are dropped and branches on a constant become jumps.
+//!
+//! Values are folded at the width of their type the way the machine computes
+//! them. A division by zero, or of the lowest value by -1, is left for the
+//! machine and reported instead.
+
+use std::collections::HashSet;
+
+use super::*;
+
+pub(crate) fn run(module: &mut Module) {
+    for func in module.functions.iter_mut() {
+        let (values, reachable) = Sccp::new(func).solve();
+        for warning in rewrite(func, &values, &reachable) {
+            // the pass can run more than once
+            if !module.warnings.contains(&warning) {
+                module.warnings.push(warning);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    // nothing is known yet, it may still turn out to be anything
+    Unknown,
+    Const(i64),
+    // not the same on every run
+    Varying,
+}
+
+impl Value {
+    fn meet(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Unknown, other) | (other, Value::Unknown) => other,
+            (Value::Const(a), Value::Const(b)) if a == b => Value::Const(a),
+            _ => Value::Varying,
+        }
+    }
+}
+
+// where a register is used, the terminator of a block is at insts.len()
+type Use = (BlockId, usize);
+
+struct Sccp<'a> {
+    func: &'a Function,
+    values: Vec<Value>,
+    reachable: Vec<bool>,
+    edges: HashSet<(BlockId, BlockId)>,
+    uses: Vec<Vec<Use>>,
+    edge_work: Vec<(BlockId, BlockId)>,
+    value_work: Vec<VReg>,
+}
+
+// a value of the type as the machine sees it, zero extended
+fn unsigned(ty: Ty, value: i64) -> u64 {
+    value as u64 & (u64::MAX >> (64 - ty.size() * 8))
+}
+
+// None when the machine would trap
+pub(super) fn fold_bin(op: BinOp, ty: Ty, lhs: i64, rhs: i64) -> Option<i64> {
+    let (a, b) = (lhs as i128, rhs as i128);
+    let min = ty.wrap(1i128 << (ty.size() * 8 - 1));
+    let res = match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::SDiv | BinOp::SRem if rhs == 0 || (lhs == min && rhs == -1) => return None,
+        BinOp::SDiv => a / b,
+        BinOp::SRem => a % b,
+        BinOp::UDiv | BinOp::URem if rhs == 0 => return None,
+        BinOp::UDiv => (unsigned(ty, lhs) / unsigned(ty, rhs)) as i128,
+        BinOp::URem => (unsigned(ty, lhs) % unsigned(ty, rhs)) as i128,
+    };
+    Some(ty.wrap(res))
+}
+
+pub(super) fn fold_cmp(cond: Cond, ty: Ty, lhs: i64, rhs: i64) -> i64 {
+    let (a, b) = (unsigned(ty, lhs), unsigned(ty, rhs));
+    let res = match cond {
+        Cond::Eq => lhs == rhs,
+        Cond::Ne => lhs != rhs,
+        Cond::Slt => lhs < rhs,
+        Cond::Sle => lhs <= rhs,
+        Cond::Sgt => lhs > rhs,
+        Cond::Sge => lhs >= rhs,
+        Cond::Ult => a < b,
+        Cond::Ule => a <= b,
+        Cond::Ugt => a > b,
+        Cond::Uge => a >= b,
+    };
+    res as i64
+}
+
+pub(super) fn fold_cast(kind: CastKind, from: Ty, to: Ty, value: i64) -> i64 {
+    match kind {
+        CastKind::Zext => to.wrap(unsigned(from, value) as i128),
+        CastKind::Sext | CastKind::Trunc => to.wrap(value as i128),
+    }
+}
+
+fn value_of(values: &[Value], operand: Operand) -> Value {
+    match operand {
+        Operand::Reg(v) => values[v],
+        Operand::Imm(value) => Value::Const(value),
+    }
+}
+
+// the block a switch on value jumps to
+fn switch_target(ty: Ty, value: i64, cases: &[(i64, BlockId)], default: BlockId) -> BlockId {
+    let value = ty.wrap(value as i128);
+    cases.iter().find(|(case, _)| *case == value).map_or(default, |(_, target)| *target)
+}
+
+impl Sccp<'_> {
+    fn new(func: &Function) -> Sccp<'_> {
+        let mut uses = vec![Vec::new(); func.vregs.len()];
+        for (index, block) in func.blocks.iter().enumerate() {
+            for (pos, inst) in block.insts.iter().enumerate() {
+                for operand in inst.operands() {
+                    if let Operand::Reg(v) = operand {
+                        uses[v].push((index, pos));
+                    }
+                }
+            }
+            for operand in block.term.operands() {
+                if let Operand::Reg(v) = operand {
+                    uses[v].push((index, block.insts.len()));
+                }
+            }
+        }
+        let mut values = vec![Value::Unknown; func.vregs.len()];
+        for param in func.params.iter() {
+            values[*param] = Value::Varying;
+        }
+        Sccp {
+            func,
+            values,
+            reachable: vec![false; func.blocks.len()],
+            edges: HashSet::new(),
+            uses,
+            edge_work: Vec::new(),
+            value_work: Vec::new(),
+        }
+    }
+
+    fn operand(&self, operand: Operand) -> Value {
+        value_of(&self.values, operand)
+    }
+
+    // the value of every register and which blocks can run
+    fn solve(mut self) -> (Vec<Value>, Vec<bool>) {
+        self.reach(0);
+        while !self.edge_work.is_empty() || !self.value_work.is_empty() {
+            while let Some((from, to)) = self.edge_work.pop() {
+                if !self.edges.insert((from, to)) {
+                    continue;
+                }
+                if self.reachable[to] {
+                    // only the phis see the new edge
+                    for pos in 0..self.func.blocks[to].insts.len() {
+                        if !matches!(self.func.blocks[to].insts[pos], Inst::Phi { .. }) {
+                            break;
+                        }
+                        self.visit(to, pos);
+                    }
+                }
+                else {
+                    self.reach(to);
+                }
+            }
+            while let Some(vreg) = self.value_work.pop() {
+                for (block, pos) in self.uses[vreg].clone() {
+                    if self.reachable[block] {
+                        self.visit(block, pos);
+                    }
+                }
+            }
+        }
+        (self.values, self.reachable)
+    }
+
+    fn reach(&mut self, block: BlockId) {
+        self.reachable[block] = true;
+        for pos in 0..=self.func.blocks[block].insts.len() {
+            self.visit(block, pos);
+        }
+    }
+
+    // a register copied to from more than one place, like out of SSA, is
+    // whatever all of them agree on
+    fn set(&mut self, vreg: VReg, value: Value) {
+        let value = self.values[vreg].meet(value);
+        if self.values[vreg] != value {
+            self.values[vreg] = value;
+            self.value_work.push(vreg);
+        }
+    }
+
+    fn visit(&mut self, block: BlockId, pos: usize) {
+        let insts = &self.func.blocks[block].insts;
+        if pos == insts.len() {
+            self.visit_term(block);
+            return;
+        }
+        let (dst, value) = match &insts[pos] {
+            Inst::Phi { dst, args, .. } => {
+                let value = args.iter()
+                    .filter(|(from, _)| self.edges.contains(&(*from, block)))
+                    .fold(Value::Unknown, |acc, (_, arg)| acc.meet(self.operand(*arg)));
+                (*dst, value)
+            }
+            Inst::Copy { dst, src, .. } => (*dst, self.operand(*src)),
+            Inst::Bin { dst, op, ty, lhs, rhs } => {
+                let value = match (self.operand(*lhs), self.operand(*rhs)) {
+                    (Value::Const(a), Value::Const(b)) => fold_bin(*op, *ty, a, b).map_or(Value::Varying, Value::Const),
+                    (Value::Varying, _) | (_, Value::Varying) => Value::Varying,
+                    _ => Value::Unknown,
+                };
+                (*dst, value)
+            }
+            Inst::Neg { dst, ty, src } => {
+                let value = match self.operand(*src) {
+                    Value::Const(a) => Value::Const(ty.wrap(-(a as i128))),
+                    other => other,
+                };
+                (*dst, value)
+            }
+            Inst::Cmp { dst, cond, ty, lhs, rhs } => {
+                let value = match (self.operand(*lhs), self.operand(*rhs)) {
+                    (Value::Const(a), Value::Const(b)) => Value::Const(fold_cmp(*cond, *ty, a, b)),
+                    (Value::Varying, _) | (_, Value::Varying) => Value::Varying,
+                    _ => Value::Unknown,
+                };
+                (*dst, value)
+            }
+            Inst::Cast { dst, kind, from, to, src } => {
+                let value = match self.operand(*src) {
+                    Value::Const(a) => Value::Const(fold_cast(*kind, *from, *to, a)),
+                    other => other,
+                };
+                (*dst, value)
+            }
+            // memory and calls are never known
+            inst => match inst.dst() {
+                Some(dst) => (dst, Value::Varying),
+                None => return,
+            },
+        };
+        self.set(dst, value);
+    }
+
+    fn visit_term(&mut self, block: BlockId) {
+        let targets = match &self.func.blocks[block].term {
+            Terminator::Branch { cond, then, other } => match self.operand(*cond) {
+                Value::Unknown => Vec::new(),
+                Value::Const(0) => vec![*other],
+                Value::Const(_) => vec![*then],
+                Value::Varying => vec![*then, *other],
+            },
+            Terminator::Switch { ty, value, cases, default, .. } => match self.operand(*value) {
+                Value::Unknown => Vec::new(),
+                Value::Const(value) => vec![switch_target(*ty, value, cases, *default)],
+                Value::Varying => self.func.blocks[block].term.successors(),
+            },
+            term => term.successors(),
+        };
+        for target in targets {
+            self.edge_work.push((block, target));
+        }
+    }
+}
+
+// puts the constants in, returns the warnings about what the machine
+// will trap on
+fn rewrite(func: &mut Function, values: &[Value], reachable: &[bool]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (index, block) in func.blocks.iter().enumerate() {
+        if !reachable[index] {
+            continue;
+        }
+        for inst in block.insts.iter() {
+            let Inst::Bin { dst, op, ty, lhs, rhs } = inst else {
+                continue;
+            };
+            let msg = match (op, value_of(values, *lhs), value_of(values, *rhs)) {
+                (BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem, _, Value::Const(0)) => "division by zero",
+                (BinOp::SDiv | BinOp::SRem, Value::Const(lhs), Value::Const(-1)) if fold_bin(*op, *ty, lhs, -1).is_none() => "division overflows",
+                _ => continue,
+            };
+            let at = func.spans.get(dst).map_or_else(|| format!("in {}", func.name), |span| span.to_string());
+            warnings.push(format!("{}: {}", at, msg));
+        }
+    }
+
+    let constant = |operand: &mut Operand| {
+        if let Operand::Reg(v) = operand
+            && let Value::Const(value) = values[*v] {
+            *operand = Operand::Imm(value);
+        }
+    };
+    for (index, block) in func.blocks.iter_mut().enumerate() {
+        block.insts.retain(|inst| {
+            let pure = matches!(inst, Inst::Phi { .. } | Inst::Copy { .. } | Inst::Bin { .. } | Inst::Neg { .. } | Inst::Cmp { .. } | Inst::Cast { .. });
+            !(pure && matches!(inst.dst().map(|dst| values[dst]), Some(Value::Const(_))))
+        });
+        for inst in block.insts.iter_mut() {
+            inst.operands_mut().into_iter().for_each(constant);
+        }
+        block.term.operands_mut().into_iter().for_each(constant);
+        if !reachable[index] {
+            continue;
+        }
+        let target = match &block.term {
+            Terminator::Branch { cond: Operand::Imm(cond), then, other } => if *cond != 0 { *then } else { *other },
+            Terminator::Switch { ty, value: Operand::Imm(value), cases, default, .. } => switch_target(*ty, *value, cases, *default),
+            _ => continue,
+        };
+        block.term = Terminator::Jump(target);
+    }
+
+    // the edges that are gone take their phi args with them
+    let preds = func.predecessors();
+    for (index, block) in func.blocks.iter_mut().enumerate() {
+        for inst in block.insts.iter_mut() {
+            if let Inst::Phi { args, .. } = inst {
+                args.retain(|(from, _)| preds[index].contains(from));
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_through_loop_phi() {
+        // %1 keeps the 5 it enters the loop with, %2 counts to 10
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Jump(1) },
+            Block {
+                insts: vec![
+                    Inst::Phi { dst: 1, ty: Ty::I32, args: vec![(0, Operand::Imm(5)), (2, Operand::Reg(3))] },
+                    Inst::Phi { dst: 2, ty: Ty::I32, args: vec![(0, Operand::Imm(0)), (2, Operand::Reg(4))] },
+                    Inst::Cmp { dst: 5, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(2), rhs: Operand::Imm(10) },
+                ],
+                term: Terminator::Branch { cond: Operand::Reg(5), then: 2, other: 3 },
+            },
+            Block {
+                insts: vec![
+                    Inst::Copy { dst: 3, ty: Ty::I32, src: Operand::Reg(1) },
+                    Inst::Bin { dst: 4, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(2), rhs: Operand::Imm(1) },
+                ],
+                term: Terminator::Jump(1),
+            },
+            Block {
+                insts: vec![Inst::Bin { dst: 6, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(1) }],
+                term: Terminator::Ret(Some((Ty::I32, Operand::Reg(6)))),
+            },
+        ];
+        let mut module = test_module(&[Ty::I32, Ty::I32, Ty::I32, Ty::I32, Ty::I32, Ty::I8, Ty::I32], 0, &[], blocks);
+        run(&mut module);
+        verify(&module).unwrap();
+        let func = &module.functions[0];
+        assert!(matches!(func.blocks[3].term, Terminator::Ret(Some((Ty::I32, Operand::Imm(6))))));
+        // the counter still varies
+        let phis: Vec<VReg> = func.blocks[1].insts.iter().filter_map(|inst| match inst {
+            Inst::Phi { dst, .. } => Some(*dst),
+            _ => None,
+        }).collect();
+        assert_eq!(phis, vec![2]);
+        assert!(func.blocks[2].insts.iter().all(|inst| !matches!(inst, Inst::Copy { .. })));
+    }
+}
@@ -0,0 +1,292 @@
+//! Dead code elimination.
+//!
+//! Cleans up what lowering and the other passes leave behind: blocks nothing
+//! jumps to any more, jumps to a block that only jumps on, instructions whose
+//! value nobody uses, stores to a slot nobody reads before it is stored to
+//! again and slots nothing refers to, so they take no room in the frame.
+//! Functions that aren't exported and that nothing in the file calls are
+//! dropped too, nothing outside the file can call them.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use super::dom::reverse_postorder;
+
+pub(crate) fn run(module: &mut Module) {
+    remove_functions(module);
+    for func in module.functions.iter_mut() {
+        thread_jumps(func);
+        // the blocks jumped past don't count as predecessors any more
+        remove_blocks(func);
+        merge_blocks(func);
+        remove_blocks(func);
+        // what a dead store stored is dead too
+        remove_stores(func);
+        remove_insts(func);
+        remove_slots(func);
+    }
+}
+
+// only functions of this file are dropped: main is called by the _start
+// stub or by the crt, an exported function is a global of the object, and
+// one without export is a local label no other object can call, the calls
+// other files of the compilation try are rejected by sema
+fn remove_functions(module: &mut Module) {
+    loop {
+        let called: HashSet<String> = module.functions.iter()
+            .flat_map(|func| func.blocks.iter())
+            .flat_map(|block| block.insts.iter())
+            .filter_map(|inst| match inst {
+                Inst::Call { callee, .. } => Some(callee.clone()),
+                _ => None,
+            })
+            .collect();
+        let before = module.functions.len();
+        module.functions.retain(|func| func.exported || func.name == "main" || called.contains(&func.name));
+        if module.functions.len() == before {
+            return;
+        }
+    }
+}
+
+// a jump to an empty block that only jumps on goes straight to where that
+// one goes, unless a phi there tells the paths apart
+fn thread_jumps(func: &mut Function) {
+    for index in 0..func.blocks.len() {
+        for succ in 0..func.blocks[index].term.successors().len() {
+            let mut target = func.blocks[index].term.successors()[succ];
+            // a loop of empty blocks never ends
+            let mut seen = HashSet::new();
+            while target != 0 && seen.insert(target) && func.blocks[target].insts.is_empty()
+                && let Terminator::Jump(next) = func.blocks[target].term
+                && !matches!(func.blocks[next].insts.first(), Some(Inst::Phi { .. })) {
+                target = next;
+            }
+            *func.blocks[index].term.successors_mut()[succ] = target;
+        }
+    }
+}
+
+// a block jumping to one nothing else jumps to takes over its code
+fn merge_blocks(func: &mut Function) {
+    let mut preds = func.predecessors();
+    for block in reverse_postorder(func) {
+        while let Terminator::Jump(next) = func.blocks[block].term
+            && next != 0 && next != block && preds[next].len() == 1 {
+            let merged = std::mem::replace(&mut func.blocks[next], Block { insts: Vec::new(), term: Terminator::Unreachable });
+            // with one predecessor a phi has one value
+            for inst in merged.insts {
+                let inst = match inst {
+                    Inst::Phi { dst, ty, args } => Inst::Copy { dst, ty, src: args[0].1 },
+                    inst => inst,
+                };
+                func.blocks[block].insts.push(inst);
+            }
+            for succ in merged.term.successors() {
+                preds[succ].retain(|pred| *pred != next);
+                if !preds[succ].contains(&block) {
+                    preds[succ].push(block);
+                }
+                for inst in func.blocks[succ].insts.iter_mut() {
+                    if let Inst::Phi { args, .. } = inst {
+                        for (from, _) in args.iter_mut() {
+                            if *from == next {
+                                *from = block;
+                            }
+                        }
+                    }
+                }
+            }
+            preds[next].clear();
+            func.blocks[block].term = merged.term;
+        }
+    }
+}
+
+// drops the blocks nothing reaches and numbers the rest in order
+fn remove_blocks(func: &mut Function) {
+    let mut reachable = reverse_postorder(func);
+    reachable.sort();
+    if reachable.len() == func.blocks.len() {
+        return;
+    }
+    let mut renumbered: HashMap<BlockId, BlockId> = HashMap::new();
+    for (index, block) in reachable.iter().enumerate() {
+        renumbered.insert(*block, index);
+    }
+    let blocks = std::mem::take(&mut func.blocks);
+    for (index, mut block) in blocks.into_iter().enumerate() {
+        if !renumbered.contains_key(&index) {
+            continue;
+        }
+        for target in block.term.successors_mut() {
+            *target = renumbered[target];
+        }
+        for inst in block.insts.iter_mut() {
+            if let Inst::Phi { args, .. } = inst {
+                args.retain(|(from, _)| renumbered.contains_key(from));
+                for (from, _) in args.iter_mut() {
+                    *from = renumbered[from];
+                }
+            }
+        }
+        func.blocks.push(block);
+    }
+}
+
+// stores, calls and asm are kept, anything else only if what it computes
+// ends up in one of them or in a terminator
+fn remove_insts(func: &mut Function) {
+    let mut live = vec![false; func.vregs.len()];
+    let mut work: Vec<VReg> = Vec::new();
+    let mut mark = |operands: Vec<Operand>, work: &mut Vec<VReg>| {
+        for operand in operands {
+            if let Operand::Reg(v) = operand
+                && !live[v] {
+                live[v] = true;
+                work.push(v);
+            }
+        }
+    };
+    // every instruction defining a register, there can be more than one
+    // out of ssa
+    let mut defs: Vec<Vec<(BlockId, usize)>> = vec![Vec::new(); func.vregs.len()];
+    for (index, block) in func.blocks.iter().enumerate() {
+        for (pos, inst) in block.insts.iter().enumerate() {
+            match inst {
+                Inst::Store { .. } | Inst::StoreSlot { .. } | Inst::Call { .. } | Inst::Asm { .. } => mark(inst.operands(), &mut work),
+                _ => {}
+            }
+            if let Some(dst) = inst.dst() {
+                defs[dst].push((index, pos));
+            }
+        }
+        mark(block.term.operands(), &mut work);
+    }
+    while let Some(vreg) = work.pop() {
+        for (block, pos) in defs[vreg].iter() {
+            mark(func.blocks[*block].insts[*pos].operands(), &mut work);
+        }
+    }
+    for block in func.blocks.iter_mut() {
+        block.insts.retain(|inst| match inst {
+            Inst::Store { .. } | Inst::StoreSlot { .. } | Inst::Call { .. } | Inst::Asm { .. } => true,
+            inst => inst.dst().is_some_and(|dst| live[dst]),
+        });
+    }
+    for (vreg, live) in live.iter().enumerate() {
+        if !live {
+            func.spans.remove(&vreg);
+        }
+    }
+}
+
+// slots that are read, anything that takes the address can read it later
+fn read_slots(func: &Function) -> HashSet<SlotId> {
+    let mut read = HashSet::new();
+    for inst in func.blocks.iter().flat_map(|block| block.insts.iter()) {
+        match inst {
+            Inst::LoadSlot { slot, .. } | Inst::SlotAddr { slot, .. } => {
+                read.insert(*slot);
+            }
+            Inst::Asm { vars, .. } => read.extend(vars.iter().map(|(_, slot)| *slot)),
+            _ => {}
+        }
+    }
+    read
+}
+
+// a store is dead when its slot is never read, or when the same bytes are
+// stored to again in the block before anything could read them
+fn remove_stores(func: &mut Function) {
+    let read = read_slots(func);
+    for block in func.blocks.iter_mut() {
+        let mut dead = vec![false; block.insts.len()];
+        // the last store to every place that nothing read yet
+        let mut pending: HashMap<(SlotId, u32), (Ty, usize)> = HashMap::new();
+        for (pos, inst) in block.insts.iter().enumerate() {
+            match inst {
+                Inst::StoreSlot { slot, .. } if !read.contains(slot) => dead[pos] = true,
+                Inst::StoreSlot { ty, slot, offset, .. } => {
+                    if let Some((prev, prev_pos)) = pending.insert((*slot, *offset), (*ty, pos))
+                        && prev == *ty {
+                        dead[prev_pos] = true;
+                    }
+                }
+                Inst::LoadSlot { slot, .. } => pending.retain(|(pending, _), _| pending != slot),
+                // they can go through the address of any slot
+                Inst::Load { .. } | Inst::Store { .. } | Inst::Call { .. } | Inst::Asm { .. } => pending.clear(),
+                _ => {}
+            }
+        }
+        let mut pos = 0;
+        block.insts.retain(|_| {
+            pos += 1;
+            !dead[pos - 1]
+        });
+    }
+}
+
+// slots nothing refers to any more take no room in the frame
+fn remove_slots(func: &mut Function) {
+    let mut used = vec![false; func.slots.len()];
+    for inst in func.blocks.iter().flat_map(|block| block.insts.iter()) {
+        match inst {
+            Inst::LoadSlot { slot, .. } | Inst::StoreSlot { slot, .. } | Inst::SlotAddr { slot, .. } => used[*slot] = true,
+            Inst::Asm { vars, .. } => vars.iter().for_each(|(_, slot)| used[*slot] = true),
+            _ => {}
+        }
+    }
+    let mut renumbered = vec![0; func.slots.len()];
+    let mut count = 0;
+    for (slot, used) in used.iter().enumerate() {
+        renumbered[slot] = count;
+        if *used {
+            count += 1;
+        }
+    }
+    if count == func.slots.len() {
+        return;
+    }
+    let mut slot = 0;
+    func.slots.retain(|_| {
+        slot += 1;
+        used[slot - 1]
+    });
+    for inst in func.blocks.iter_mut().flat_map(|block| block.insts.iter_mut()) {
+        match inst {
+            Inst::LoadSlot { slot, .. } | Inst::StoreSlot { slot, .. } | Inst::SlotAddr { slot, .. } => *slot = renumbered[*slot],
+            Inst::Asm { vars, .. } => vars.iter_mut().for_each(|(_, slot)| *slot = renumbered[*slot]),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_calls_and_stores() {
+        let blocks = vec![Block {
+            insts: vec![
+                Inst::SlotAddr { dst: 0, slot: 0 },
+                Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Imm(7) },
+                Inst::Call { dst: Some(1), callee: "f".to_string(), args: vec![(Ty::I64, Operand::Reg(0))] },
+                Inst::Store { ty: Ty::I32, ptr: Operand::Reg(0), src: Operand::Imm(3) },
+                Inst::Bin { dst: 2, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(1) },
+                Inst::LoadSlot { dst: 3, ty: Ty::I32, slot: 0, offset: 0 },
+            ],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Imm(0)))),
+        }];
+        let mut module = test_module(&[Ty::I64, Ty::I32, Ty::I32, Ty::I32], 0, &[4], blocks);
+        run(&mut module);
+        verify(&module).unwrap();
+        let insts = &module.functions[0].blocks[0].insts;
+        assert_eq!(insts.len(), 4);
+        assert!(matches!(insts[0], Inst::SlotAddr { .. }));
+        assert!(matches!(insts[1], Inst::StoreSlot { .. }));
+        assert!(matches!(insts[2], Inst::Call { .. }));
+        assert!(matches!(insts[3], Inst::Store { .. }));
+    }
+}
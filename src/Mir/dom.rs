@@ -0,0 +1,129 @@
+//! Dominator tree and dominance frontiers.
+//!
+//! Uses the iterative algorithm of Cooper, Harvey and Kennedy over the
+//! reverse postorder of the blocks reachable from the entry. Blocks nothing
+//! reaches have no immediate dominator and are left out of the tree.
+
+use super::*;
+
+pub(crate) struct DomTree {
+    // immediate dominator of every block, the entry is its own
+    pub(crate) idom: Vec<Option<BlockId>>,
+    pub(crate) children: Vec<Vec<BlockId>>,
+    // reachable blocks, every block comes after the ones dominating it
+    pub(crate) order: Vec<BlockId>,
+}
+
+// reachable blocks in reverse postorder
+pub(crate) fn reverse_postorder(func: &Function) -> Vec<BlockId> {
+    let mut seen = vec![false; func.blocks.len()];
+    let mut order = Vec::new();
+    // block and how many of its successors were visited
+    let mut stack: Vec<(BlockId, usize)> = vec![(0, 0)];
+    seen[0] = true;
+    while let Some((block, next)) = stack.pop() {
+        let succs = func.blocks[block].term.successors();
+        if next < succs.len() {
+            stack.push((block, next + 1));
+            let succ = succs[next];
+            if !seen[succ] {
+                seen[succ] = true;
+                stack.push((succ, 0));
+            }
+        }
+        else {
+            order.push(block);
+        }
+    }
+    order.reverse();
+    order
+}
+
+impl DomTree {
+    pub(crate) fn new(func: &Function) -> DomTree {
+        let order = reverse_postorder(func);
+        let mut pos = vec![usize::MAX; func.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            pos[*block] = index;
+        }
+        let preds = func.predecessors();
+        let mut idom: Vec<Option<BlockId>> = vec![None; func.blocks.len()];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().skip(1) {
+                let mut new_idom: Option<BlockId> = None;
+                for pred in preds[*block].iter() {
+                    if idom[*pred].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *pred,
+                        Some(other) => DomTree::intersect(&idom, &pos, *pred, other),
+                    });
+                }
+                if new_idom != idom[*block] {
+                    idom[*block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        let mut children = vec![Vec::new(); func.blocks.len()];
+        for block in order.iter().skip(1) {
+            children[idom[*block].unwrap()].push(*block);
+        }
+        DomTree { idom, children, order }
+    }
+
+    // every path from the entry to b goes through a
+    pub(crate) fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom[b] {
+                Some(idom) if idom != b => b = idom,
+                _ => return false,
+            }
+        }
+    }
+
+    // walks both up the tree until they meet, blocks higher up come
+    // earlier in the order
+    fn intersect(idom: &[Option<BlockId>], pos: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+        while a != b {
+            while pos[a] > pos[b] {
+                a = idom[a].unwrap();
+            }
+            while pos[b] > pos[a] {
+                b = idom[b].unwrap();
+            }
+        }
+        a
+    }
+
+    // where the dominance of every block ends, the blocks joining its paths
+    // with paths it doesn't dominate
+    pub(crate) fn frontiers(&self, func: &Function) -> Vec<Vec<BlockId>> {
+        let preds = func.predecessors();
+        let mut frontiers = vec![Vec::new(); func.blocks.len()];
+        for block in self.order.iter() {
+            let reachable: Vec<BlockId> = preds[*block].iter().copied().filter(|pred| self.idom[*pred].is_some()).collect();
+            if reachable.len() < 2 {
+                continue;
+            }
+            let idom = self.idom[*block].unwrap();
+            for pred in reachable {
+                let mut runner = pred;
+                while runner != idom {
+                    if !frontiers[runner].contains(block) {
+                        frontiers[runner].push(*block);
+                    }
+                    runner = self.idom[runner].unwrap();
+                }
+            }
+        }
+        frontiers
+    }
+}
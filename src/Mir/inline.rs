@@ -0,0 +1,291 @@
+//! Inlining.
+//!
+//! A call to a small function of the module is replaced by a copy of its
+//! body: the args are copied to the params, every return becomes a copy to
+//! the result and a jump to the rest of the calling block. The copy gets its
+//! own registers, slots and blocks, so the callee can be inlined anywhere
+//! any number of times, and keeps the spans of its registers for the
+//! diagnostics of later passes.
+//!
+//! Only leaves are inlined, a function that calls something could end up
+//! calling itself. Once every call of a function was inlined it is a leaf
+//! itself and can go into its own callers. How big a body may be depends
+//! on the `inline` hint, `noinline` keeps every call.
+
+use std::collections::HashMap;
+
+use super::*;
+
+// instructions and terminators of a body inlined without a hint
+const SIZE: usize = 24;
+// and with `inline`
+const HINTED_SIZE: usize = 96;
+
+pub(crate) fn run(module: &mut Module) {
+    loop {
+        let leaves: HashMap<String, Function> = module.functions.iter()
+            .filter(|func| inlinable(func))
+            .map(|func| (func.name.clone(), func.clone()))
+            .collect();
+        let mut changed = false;
+        for func in module.functions.iter_mut() {
+            changed |= inline_calls(func, &leaves);
+        }
+        // every round inlines calls without adding any
+        if !changed {
+            return;
+        }
+    }
+}
+
+fn size(func: &Function) -> usize {
+    func.blocks.iter().map(|block| block.insts.len() + 1).sum()
+}
+
+// asm can define labels, which can't be there twice
+fn inlinable(func: &Function) -> bool {
+    let limit = match func.inline {
+        Inline::Auto => SIZE,
+        Inline::Hint => HINTED_SIZE,
+        Inline::Never => return false,
+    };
+    let leaf = func.blocks.iter()
+        .flat_map(|block| block.insts.iter())
+        .all(|inst| !matches!(inst, Inst::Call { .. } | Inst::Asm { .. }));
+    leaf && size(func) <= limit
+}
+
+fn inline_calls(func: &mut Function, leaves: &HashMap<String, Function>) -> bool {
+    let mut changed = false;
+    // the rest of a block with an inlined call moves to a new block at the
+    // end, which is looked at when the loop gets there
+    let mut block = 0;
+    while block < func.blocks.len() {
+        let call = func.blocks[block].insts.iter().position(|inst| {
+            matches!(inst, Inst::Call { callee, .. } if leaves.contains_key(callee))
+        });
+        let Some(pos) = call else {
+            block += 1;
+            continue;
+        };
+        let Inst::Call { callee, .. } = &func.blocks[block].insts[pos] else {
+            unreachable!();
+        };
+        let callee = &leaves[&callee.clone()];
+        inline_call(func, block, pos, callee);
+        changed = true;
+    }
+    changed
+}
+
+fn inline_call(func: &mut Function, block: BlockId, pos: usize, callee: &Function) {
+    let mut rest = func.blocks[block].insts.split_off(pos);
+    let Inst::Call { dst, args, .. } = rest.remove(0) else {
+        panic!("no call to inline at bb{} of {}", block, func.name);
+    };
+    let vregs = func.vregs.len();
+    func.vregs.extend(callee.vregs.iter().copied());
+    for (vreg, span) in callee.spans.iter() {
+        func.spans.insert(vreg + vregs, span.clone());
+    }
+    // the call can be in any scope of the caller, the body scope shares
+    // memory with none of them
+    let slots = func.slots.len();
+    func.slots.extend(callee.slots.iter().map(|slot| Slot { scope: 0, ..slot.clone() }));
+    let entry = func.blocks.len();
+    let after = entry + callee.blocks.len();
+
+    for (param, (ty, arg)) in callee.params.iter().zip(args) {
+        func.blocks[block].insts.push(Inst::Copy { dst: param + vregs, ty, src: arg });
+    }
+    let term = std::mem::replace(&mut func.blocks[block].term, Terminator::Jump(entry));
+    // the end of the block comes from the new one now
+    for succ in term.successors() {
+        for inst in func.blocks[succ].insts.iter_mut() {
+            if let Inst::Phi { args, .. } = inst {
+                args.iter_mut().filter(|(from, _)| *from == block).for_each(|(from, _)| *from = after);
+            }
+        }
+    }
+
+    let rename = |operand: &mut Operand| {
+        if let Operand::Reg(v) = operand {
+            *v += vregs;
+        }
+    };
+    for body in callee.blocks.iter() {
+        let mut body = body.clone();
+        for inst in body.insts.iter_mut() {
+            if let Some(dst) = inst.dst_mut() {
+                *dst += vregs;
+            }
+            inst.operands_mut().into_iter().for_each(rename);
+            match inst {
+                Inst::Phi { args, .. } => args.iter_mut().for_each(|(from, _)| *from += entry),
+                Inst::SlotAddr { slot, .. } | Inst::LoadSlot { slot, .. } | Inst::StoreSlot { slot, .. } => *slot += slots,
+                _ => {}
+            }
+        }
+        body.term.operands_mut().into_iter().for_each(rename);
+        for target in body.term.successors_mut() {
+            *target += entry;
+        }
+        // the result is copied to on every return, like a register out of SSA
+        if let Terminator::Ret(value) = body.term {
+            if let (Some(dst), Some((ty, value))) = (dst, value) {
+                body.insts.push(Inst::Copy { dst, ty, src: value });
+            }
+            body.term = Terminator::Jump(after);
+        }
+        func.blocks.push(body);
+    }
+    func.blocks.push(Block { insts: rest, term });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // t with the blocks, the callees are functions of the module next to it
+    fn module(vregs: &[Ty], params: usize, blocks: Vec<Block>, callees: Vec<Function>) -> Module {
+        let mut module = test_module(vregs, params, &[], blocks);
+        for callee in callees {
+            let params = callee.params.iter().map(|param| callee.vregs[*param]).collect();
+            module.signatures.insert(callee.name.clone(), Signature { params, ret: callee.ret, variadic: false, external: false });
+            module.functions.push(callee);
+        }
+        module
+    }
+
+    fn callee(name: &str, inline: Inline, vregs: &[Ty], params: usize, blocks: Vec<Block>) -> Function {
+        let mut func = test_module(vregs, params, &[], blocks).functions.remove(0);
+        func.name = name.to_string();
+        func.exported = false;
+        func.inline = inline;
+        func
+    }
+
+    // t returning g(1)
+    fn calls_left(callee: Function) -> usize {
+        let blocks = vec![Block {
+            insts: vec![Inst::Call { dst: Some(0), callee: callee.name.clone(), args: vec![(Ty::I32, Operand::Imm(1))] }],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))),
+        }];
+        let mut module = module(&[Ty::I32], 0, blocks, vec![callee]);
+        run(&mut module);
+        verify(&module).unwrap();
+        module.functions[0].blocks.iter().flat_map(|block| block.insts.iter())
+            .filter(|inst| matches!(inst, Inst::Call { .. }))
+            .count()
+    }
+
+    // a body of `count` adds and the return
+    fn sized(name: &str, inline: Inline, count: usize) -> Function {
+        let insts = (0..count)
+            .map(|_| Inst::Bin { dst: 1, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Imm(1) })
+            .collect();
+        let blocks = vec![Block { insts, term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))) }];
+        callee(name, inline, &[Ty::I32, Ty::I32], 1, blocks)
+    }
+
+    #[test]
+    fn every_return_jumps_to_the_rest() {
+        // g(x) returns 0 below 0 and x otherwise
+        let body = vec![
+            Block {
+                insts: vec![Inst::Cmp { dst: 1, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Imm(0) }],
+                term: Terminator::Branch { cond: Operand::Reg(1), then: 1, other: 2 },
+            },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Imm(0)))) },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))) },
+        ];
+        let g = callee("g", Inline::Auto, &[Ty::I32, Ty::I8], 1, body);
+        let blocks = vec![Block {
+            insts: vec![
+                Inst::Call { dst: Some(1), callee: "g".to_string(), args: vec![(Ty::I32, Operand::Reg(0))] },
+                Inst::Bin { dst: 2, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(1) },
+            ],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(2)))),
+        }];
+        let mut module = module(&[Ty::I32, Ty::I32, Ty::I32], 1, blocks, vec![g]);
+        run(&mut module);
+        verify(&module).unwrap();
+
+        // the registers of g start at %3, its blocks at bb1 and the rest
+        // of bb0 is bb4
+        let func = &module.functions[0];
+        assert_eq!(func.blocks.len(), 5);
+        assert!(matches!(func.blocks[0].insts[..], [Inst::Copy { dst: 3, src: Operand::Reg(0), .. }]));
+        assert!(matches!(func.blocks[0].term, Terminator::Jump(1)));
+        assert!(matches!(func.blocks[1].term, Terminator::Branch { cond: Operand::Reg(4), then: 2, other: 3 }));
+        assert!(matches!(func.blocks[2].insts[..], [Inst::Copy { dst: 1, src: Operand::Imm(0), .. }]));
+        assert!(matches!(func.blocks[2].term, Terminator::Jump(4)));
+        assert!(matches!(func.blocks[3].insts[..], [Inst::Copy { dst: 1, src: Operand::Reg(3), .. }]));
+        assert!(matches!(func.blocks[3].term, Terminator::Jump(4)));
+        assert!(matches!(func.blocks[4].insts[..], [Inst::Bin { dst: 2, lhs: Operand::Reg(1), .. }]));
+        assert!(matches!(func.blocks[4].term, Terminator::Ret(Some((Ty::I32, Operand::Reg(2))))));
+    }
+
+    #[test]
+    fn phis_after_the_call_come_from_the_rest() {
+        let g = callee("g", Inline::Auto, &[], 0, vec![Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Imm(5)))) }]);
+        let blocks = vec![
+            Block {
+                insts: vec![Inst::Call { dst: Some(1), callee: "g".to_string(), args: Vec::new() }],
+                term: Terminator::Branch { cond: Operand::Reg(0), then: 1, other: 2 },
+            },
+            Block { insts: Vec::new(), term: Terminator::Jump(2) },
+            Block {
+                insts: vec![Inst::Phi { dst: 2, ty: Ty::I32, args: vec![(0, Operand::Reg(1)), (1, Operand::Imm(2))] }],
+                term: Terminator::Ret(Some((Ty::I32, Operand::Reg(2)))),
+            },
+        ];
+        let mut module = module(&[Ty::I8, Ty::I32, Ty::I32], 1, blocks, vec![g]);
+        run(&mut module);
+        verify(&module).unwrap();
+
+        // g is bb3 and the branch moved on to bb4
+        let func = &module.functions[0];
+        assert!(matches!(func.blocks[4].term, Terminator::Branch { then: 1, other: 2, .. }));
+        let Inst::Phi { args, .. } = &func.blocks[2].insts[0] else { panic!("bb2 starts with a phi") };
+        assert_eq!(args, &vec![(4, Operand::Reg(1)), (1, Operand::Imm(2))]);
+    }
+
+    #[test]
+    fn hints_move_the_limit() {
+        assert_eq!(calls_left(sized("g", Inline::Auto, SIZE - 1)), 0);
+        assert_eq!(calls_left(sized("g", Inline::Auto, SIZE)), 1);
+        assert_eq!(calls_left(sized("g", Inline::Hint, SIZE)), 0);
+        assert_eq!(calls_left(sized("g", Inline::Hint, HINTED_SIZE - 1)), 0);
+        assert_eq!(calls_left(sized("g", Inline::Hint, HINTED_SIZE)), 1);
+        assert_eq!(calls_left(sized("g", Inline::Never, 0)), 1);
+    }
+
+    #[test]
+    fn only_leaves_are_inlined() {
+        // g calls the extern f
+        let body = vec![Block {
+            insts: vec![Inst::Call { dst: Some(1), callee: "f".to_string(), args: vec![(Ty::I64, Operand::Imm(1))] }],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(1)))),
+        }];
+        assert_eq!(calls_left(callee("g", Inline::Hint, &[Ty::I32, Ty::I32], 1, body)), 1);
+
+        // g calls the leaf h, once h is in g, g goes into t
+        let body = vec![Block {
+            insts: vec![Inst::Call { dst: Some(0), callee: "h".to_string(), args: Vec::new() }],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))),
+        }];
+        let g = callee("g", Inline::Auto, &[Ty::I32], 0, body);
+        let h = callee("h", Inline::Auto, &[], 0, vec![Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Imm(3)))) }]);
+        let blocks = vec![Block {
+            insts: vec![Inst::Call { dst: Some(0), callee: "g".to_string(), args: Vec::new() }],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))),
+        }];
+        let mut module = module(&[Ty::I32], 0, blocks, vec![g, h]);
+        run(&mut module);
+        verify(&module).unwrap();
+        for func in module.functions.iter() {
+            assert!(func.blocks.iter().flat_map(|block| block.insts.iter()).all(|inst| !matches!(inst, Inst::Call { .. })));
+        }
+    }
+}
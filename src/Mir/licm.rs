@@ -0,0 +1,127 @@
+//! Loop-invariant code motion.
+//!
+//! An instruction in a loop computing the same value on every iteration,
+//! from constants and registers defined outside the loop, moves to the
+//! preheader and runs once. Inner loops go first, so what they hoisted can
+//! leave the loops around them too.
+//!
+//! The preheader runs even when the loop body doesn't, so only what can't
+//! trap moves: arithmetic without a division by anything but a constant
+//! that is fine, addresses, and reads of slots when nothing in the loop
+//! writes memory.
+
+use super::*;
+use super::dom::reverse_postorder;
+use super::loops::{self, Loop};
+
+pub(crate) fn run(module: &mut Module) {
+    for func in module.functions.iter_mut() {
+        if !loops::is_ssa(func) {
+            continue;
+        }
+        let loops = loops::prepare(func);
+        let mut def_block = def_blocks(func);
+        for (lp, pre) in loops.iter() {
+            hoist(func, lp, *pre, &mut def_block);
+        }
+    }
+}
+
+// the block defining every register, None for params and unused ones
+pub(super) fn def_blocks(func: &Function) -> Vec<Option<BlockId>> {
+    let mut def_block = vec![None; func.vregs.len()];
+    for (index, block) in func.blocks.iter().enumerate() {
+        for inst in block.insts.iter() {
+            if let Some(dst) = inst.dst() {
+                def_block[dst] = Some(index);
+            }
+        }
+    }
+    def_block
+}
+
+// the same on every iteration of the loop
+pub(super) fn invariant(lp: &Loop, def_block: &[Option<BlockId>], operand: Operand) -> bool {
+    match operand {
+        Operand::Reg(v) => def_block[v].is_none_or(|block| !lp.contains(block)),
+        Operand::Imm(_) => true,
+    }
+}
+
+fn writes_memory(inst: &Inst) -> bool {
+    matches!(inst, Inst::StoreSlot { .. } | Inst::Store { .. } | Inst::Call { .. } | Inst::Asm { .. })
+}
+
+// can run where it wasn't going to without trapping
+fn movable(inst: &Inst, memory_changes: bool) -> bool {
+    match inst {
+        Inst::Copy { .. } | Inst::Neg { .. } | Inst::Cmp { .. } | Inst::Cast { .. } | Inst::SlotAddr { .. } | Inst::StrAddr { .. } => true,
+        Inst::Bin { op: BinOp::Add | BinOp::Sub | BinOp::Mul, .. } => true,
+        Inst::Bin { op: BinOp::SDiv | BinOp::SRem, rhs: Operand::Imm(rhs), .. } => *rhs != 0 && *rhs != -1,
+        Inst::Bin { op: BinOp::UDiv | BinOp::URem, rhs: Operand::Imm(rhs), .. } => *rhs != 0,
+        // slots of scopes never open together share memory, a store to any
+        // of them can change it
+        Inst::LoadSlot { .. } => !memory_changes,
+        _ => false,
+    }
+}
+
+fn hoist(func: &mut Function, lp: &Loop, pre: BlockId, def_block: &mut [Option<BlockId>]) {
+    let memory_changes = lp.blocks.iter()
+        .flat_map(|block| func.blocks[*block].insts.iter())
+        .any(writes_memory);
+    // a register is defined before it is used in this order
+    let order: Vec<BlockId> = reverse_postorder(func).into_iter().filter(|block| lp.contains(*block)).collect();
+    for block in order {
+        let mut pos = 0;
+        while pos < func.blocks[block].insts.len() {
+            let inst = &func.blocks[block].insts[pos];
+            if !movable(inst, memory_changes) || !inst.operands().into_iter().all(|operand| invariant(lp, def_block, operand)) {
+                pos += 1;
+                continue;
+            }
+            let inst = func.blocks[block].insts.remove(pos);
+            def_block[inst.dst().unwrap()] = Some(pre);
+            func.blocks[pre].insts.push(inst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_storing_keeps_its_loads() {
+        // %4 only depends on the param and moves, the load of $0 sees the
+        // store of the iteration before
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Jump(1) },
+            Block {
+                insts: vec![
+                    Inst::Phi { dst: 1, ty: Ty::I32, args: vec![(0, Operand::Imm(0)), (2, Operand::Reg(6))] },
+                    Inst::Cmp { dst: 2, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Reg(0) },
+                ],
+                term: Terminator::Branch { cond: Operand::Reg(2), then: 2, other: 3 },
+            },
+            Block {
+                insts: vec![
+                    Inst::LoadSlot { dst: 3, ty: Ty::I32, slot: 0, offset: 0 },
+                    Inst::Bin { dst: 4, op: BinOp::Mul, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Imm(3) },
+                    Inst::Bin { dst: 5, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(3), rhs: Operand::Reg(4) },
+                    Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Reg(5) },
+                    Inst::Bin { dst: 6, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(1) },
+                ],
+                term: Terminator::Jump(1),
+            },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Reg(1)))) },
+        ];
+        let mut module = test_module(&[Ty::I32, Ty::I32, Ty::I8, Ty::I32, Ty::I32, Ty::I32, Ty::I32], 1, &[4], blocks);
+        run(&mut module);
+        verify(&module).unwrap();
+        let func = &module.functions[0];
+        assert!(matches!(func.blocks[0].insts[..], [Inst::Bin { dst: 4, .. }]));
+        assert!(matches!(func.blocks[2].insts[0], Inst::LoadSlot { dst: 3, .. }));
+        assert_eq!(func.blocks[2].insts.len(), 4);
+    }
+}
@@ -0,0 +1,137 @@
+//! Loops of the control flow graph.
+//!
+//! A loop is found by its back edges, the jumps to a block dominating the
+//! block they come from. That block is the header, the body is every block
+//! reaching one of those jumps without going through the header. Loops
+//! entered somewhere else than at one header aren't found, they can't be
+//! written without goto.
+//!
+//! The loop passes put the code they take out of a loop in its preheader,
+//! a block only jumping to the header that every way into the loop goes
+//! through. [`prepare`] adds one where there is none yet.
+
+use super::*;
+use super::dom::DomTree;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Loop {
+    pub(crate) header: BlockId,
+    // every block of the loop, the header first
+    pub(crate) blocks: Vec<BlockId>,
+    // the blocks jumping back to the header
+    pub(crate) latches: Vec<BlockId>,
+}
+
+impl Loop {
+    pub(crate) fn contains(&self, block: BlockId) -> bool {
+        self.blocks.contains(&block)
+    }
+}
+
+// every loop of the function, inner loops before the ones around them
+pub(crate) fn find(func: &Function) -> Vec<Loop> {
+    let dom = DomTree::new(func);
+    let preds = func.predecessors();
+    let mut loops: Vec<Loop> = Vec::new();
+    for block in dom.order.iter() {
+        for header in func.blocks[*block].term.successors() {
+            if !dom.dominates(header, *block) {
+                continue;
+            }
+            match loops.iter_mut().find(|other| other.header == header) {
+                Some(other) => other.latches.push(*block),
+                None => loops.push(Loop { header, blocks: vec![header], latches: vec![*block] }),
+            }
+        }
+    }
+    for lp in loops.iter_mut() {
+        // walks back from the latches, the header stops the walk
+        let mut work = lp.latches.clone();
+        while let Some(block) = work.pop() {
+            if lp.contains(block) || dom.idom[block].is_none() {
+                continue;
+            }
+            lp.blocks.push(block);
+            work.extend(preds[block].iter().copied());
+        }
+    }
+    loops.sort_by_key(|lp| lp.blocks.len());
+    loops
+}
+
+// the passes moving code around need every register defined once, which
+// holds as lowered and in SSA form but not after it
+pub(crate) fn is_ssa(func: &Function) -> bool {
+    let mut defined = vec![false; func.vregs.len()];
+    for param in func.params.iter() {
+        defined[*param] = true;
+    }
+    for inst in func.blocks.iter().flat_map(|block| block.insts.iter()) {
+        if let Some(dst) = inst.dst() {
+            if defined[dst] {
+                return false;
+            }
+            defined[dst] = true;
+        }
+    }
+    true
+}
+
+// the block only jumping to the header of the loop that everything
+// coming from outside the loop jumps to
+pub(crate) fn preheader(func: &Function, lp: &Loop) -> Option<BlockId> {
+    let preds = func.predecessors();
+    match preds[lp.header].iter().filter(|pred| !lp.contains(**pred)).collect::<Vec<_>>()[..] {
+        [pred] if matches!(func.blocks[*pred].term, Terminator::Jump(_)) => Some(*pred),
+        _ => None,
+    }
+}
+
+// the loops of the function with their preheaders, adding the ones
+// missing, a loop back to the entry can't have one and is left out
+pub(crate) fn prepare(func: &mut Function) -> Vec<(Loop, BlockId)> {
+    for lp in find(func) {
+        if lp.header != 0 && preheader(func, &lp).is_none() {
+            add_preheader(func, &lp);
+        }
+    }
+    find(func).into_iter()
+        .filter_map(|lp| preheader(func, &lp).map(|pre| (lp, pre)))
+        .collect()
+}
+
+fn add_preheader(func: &mut Function, lp: &Loop) {
+    let pre = func.blocks.len();
+    let outside: Vec<BlockId> = func.predecessors()[lp.header].iter().copied().filter(|pred| !lp.contains(*pred)).collect();
+    for pred in outside.iter() {
+        for target in func.blocks[*pred].term.successors_mut() {
+            if *target == lp.header {
+                *target = pre;
+            }
+        }
+    }
+    // the values coming from outside come through the preheader now, with
+    // a phi of its own when they differ
+    let mut phis = Vec::new();
+    for pos in 0..func.blocks[lp.header].insts.len() {
+        let Inst::Phi { ty, args, .. } = &func.blocks[lp.header].insts[pos] else {
+            break;
+        };
+        let ty = *ty;
+        let (from_outside, from_loop): (Vec<_>, Vec<_>) = args.iter().copied().partition(|(from, _)| outside.contains(from));
+        let value = match from_outside.first() {
+            Some((_, first)) if from_outside.iter().all(|(_, arg)| arg == first) => *first,
+            _ => {
+                let dst = func.new_vreg(ty);
+                phis.push(Inst::Phi { dst, ty, args: from_outside });
+                Operand::Reg(dst)
+            }
+        };
+        let Inst::Phi { args, .. } = &mut func.blocks[lp.header].insts[pos] else {
+            unreachable!();
+        };
+        *args = from_loop;
+        args.push((pre, value));
+    }
+    func.blocks.push(Block { insts: phis, term: Terminator::Jump(lp.header) });
+}
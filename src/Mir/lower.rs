@@ -0,0 +1,258 @@
+//! Lowering of the checked AST into the IR.
+//!
+//! Sema already typed every expression and put a conversion wherever a
+//! value changes type, so lowering is a straight walk: every local gets a
+//! stack slot, every rpn node becomes a few instructions on virtual
+//! registers and control flow becomes blocks and terminators.
+
+use std::collections::HashMap;
+
+use super::*;
+use crate::Ir::Stmt;
+use crate::Ir::r#gen::{FuncData, StructData};
+use crate::Ir::sema::SymbolId;
+use crate::Ir::stmt::{Arg, InitFunc, TypeInfo};
+use crate::Tokenizer::TokenType;
+
+// a local of the AST and the slot holding it, an array has the type of
+// the pointer it decays to
+pub(super) struct Local {
+    pub(super) slot: SlotId,
+    pub(super) ty: TypeInfo,
+    pub(super) is_array: bool,
+}
+
+pub(super) struct Lower<'a> {
+    pub(super) structs: &'a HashMap<String, StructData>,
+    pub(super) module: &'a mut Module,
+    pub(super) func: Function,
+    pub(super) block: BlockId,
+    // the current block got its terminator, whatever comes next is
+    // unreachable and goes into a new block
+    sealed: bool,
+    pub(super) locals: HashMap<SymbolId, Local>,
+    scope: usize,
+    // where a break and a continue jump to, innermost last
+    pub(super) break_targets: Vec<BlockId>,
+    pub(super) continue_targets: Vec<BlockId>,
+}
+
+pub(crate) fn lower(ast: &[Stmt], structs: &HashMap<String, StructData>, functions: &HashMap<String, FuncData>) -> Module {
+    let mut module = Module::default();
+    for (name, func) in functions.iter() {
+        let sig = Signature {
+            params: func.args.iter().map(Lower::arg_ty).collect(),
+            ret: Lower::ret_ty(&func.return_type),
+            variadic: func.variadic,
+            external: func.is_extern,
+        };
+        module.signatures.insert(name.clone(), sig);
+    }
+    for stmt in ast.iter() {
+        match stmt {
+            Stmt::InitFunc(v) => {
+                let func = Lower::function(v, structs, &mut module);
+                module.functions.push(func);
+            }
+            // declarations, collected before
+            Stmt::FuncDecl(_) | Stmt::InitStruct(_) | Stmt::InitEnum(_) => {}
+            _ => panic!("statement outside of a function: {:?}", stmt),
+        }
+    }
+    module
+}
+
+impl Lower<'_> {
+    fn function(v: &InitFunc, structs: &HashMap<String, StructData>, module: &mut Module) -> Function {
+        let func = Function {
+            name: v.name.value.clone().unwrap(),
+            params: Vec::new(),
+            ret: Lower::ret_ty(&v.return_type),
+            exported: v.exported,
+            inline: v.inline,
+            vregs: Vec::new(),
+            slots: Vec::new(),
+            scopes: vec![None],
+            blocks: Vec::new(),
+            spans: HashMap::new(),
+        };
+        let mut lower = Lower {
+            structs,
+            module,
+            func,
+            block: 0,
+            sealed: false,
+            locals: HashMap::new(),
+            scope: 0,
+            break_targets: Vec::new(),
+            continue_targets: Vec::new(),
+        };
+        lower.block = lower.new_block();
+
+        // args arrive in registers, they are stored to their slots like
+        // any other local
+        for arg in v.args.iter() {
+            if arg.pointer_depth == 0 && arg.arg_type.token == TokenType::Struct {
+                // sema rejects struct args passed by value
+                unreachable!("struct arg {} is passed by value", arg.name.value.as_ref().unwrap());
+            }
+            let ty = Lower::arg_ty(arg);
+            let param = lower.func.new_vreg(ty);
+            lower.func.params.push(param);
+            let arg_type = TypeInfo { var_type: arg.arg_type.token, pointer_depth: arg.pointer_depth, struct_name: arg.struct_name.clone() };
+            let slot = lower.declare(arg.sym, &arg.name.value.clone().unwrap(), arg_type, ty.size(), ty.size(), false);
+            lower.push(Inst::StoreSlot { ty, slot, offset: 0, src: Operand::Reg(param) });
+        }
+        for stmt in v.data.iter() {
+            lower.stmt(stmt);
+        }
+        // falling off the end returns 0, what C does for main
+        if !lower.sealed {
+            let value = lower.func.ret.map(|ty| (ty, Operand::Imm(0)));
+            lower.terminate(Terminator::Ret(value));
+        }
+        lower.func
+    }
+
+    // what a value of the AST type is held as
+    pub(super) fn ty(ty: &TypeInfo) -> Ty {
+        if ty.pointer_depth > 0 {
+            return Ty::I64;
+        }
+        Ty::from_size(Lower::type_size(ty.var_type))
+    }
+
+    fn arg_ty(arg: &Arg) -> Ty {
+        if arg.pointer_depth > 0 || arg.arg_type.token == TokenType::Struct {
+            return Ty::I64;
+        }
+        Ty::from_size(Lower::type_size(arg.arg_type.token))
+    }
+
+    fn ret_ty(ty: &TypeInfo) -> Option<Ty> {
+        if ty.var_type == TokenType::Void && ty.pointer_depth == 0 {
+            return None;
+        }
+        Some(Lower::ty(ty))
+    }
+
+    pub(super) fn type_size(token: TokenType) -> u32 {
+        match token {
+            TokenType::IntType | TokenType::UIntType | TokenType::Enum => 4,
+            TokenType::CharType | TokenType::UCharType | TokenType::Bool => 1,
+            TokenType::ShortType | TokenType::UShortType => 2,
+            TokenType::LongType | TokenType::ULongType => 8,
+            _ => panic!("trying to get size of unexpected type: {:?}",token),
+        }
+    }
+
+    pub(super) fn is_unsigned(ty: &TypeInfo) -> bool {
+        ty.pointer_depth > 0 || matches!(ty.var_type, TokenType::Bool |
+            TokenType::UCharType | TokenType::UShortType | TokenType::UIntType | TokenType::ULongType)
+    }
+
+    pub(super) fn struct_data(&self, name: &str) -> &StructData {
+        self.structs.get(name).unwrap_or_else(|| panic!("no struct with name: {}", name))
+    }
+
+    pub(super) fn struct_size(&self, name: &str) -> u32 {
+        let struct_data = self.struct_data(name);
+        struct_data.elements.len() as u32 * struct_data.element_size
+    }
+
+    // offset and type of a field, every field takes element_size bytes
+    pub(super) fn field(&self, struct_name: &str, field: &str) -> (u32, TypeInfo) {
+        let struct_data = self.struct_data(struct_name);
+        let arg = struct_data.elements.get(field)
+            .unwrap_or_else(|| panic!("struct {} has no field: {}", struct_name, field));
+        let ty = TypeInfo { var_type: arg.arg_type.token, pointer_depth: arg.pointer_depth, struct_name: None };
+        (arg.pos * struct_data.element_size, ty)
+    }
+
+    // size of what a pointer points to, the step of pointer arithmetic
+    pub(super) fn pointee_size(&self, ty: &TypeInfo) -> u32 {
+        if ty.pointer_depth > 1 {
+            return 8;
+        }
+        match ty.var_type {
+            TokenType::Void => 1,
+            TokenType::Struct => self.struct_size(ty.struct_name.as_ref().expect("pointer to a struct without its name")),
+            other => Lower::type_size(other),
+        }
+    }
+
+    pub(super) fn local(&self, sym: Option<SymbolId>) -> &Local {
+        self.locals.get(&sym.expect("var was not resolved by sema")).expect("unkown var")
+    }
+
+    pub(super) fn new_vreg(&mut self, ty: Ty) -> VReg {
+        self.func.new_vreg(ty)
+    }
+
+    pub(super) fn new_block(&mut self) -> BlockId {
+        self.func.blocks.push(Block { insts: Vec::new(), term: Terminator::Unreachable });
+        self.func.blocks.len() - 1
+    }
+
+    pub(super) fn push(&mut self, inst: Inst) {
+        if self.sealed {
+            let block = self.new_block();
+            self.switch_to(block);
+        }
+        self.func.blocks[self.block].insts.push(inst);
+    }
+
+    // instruction producing a value of type ty into a new register
+    pub(super) fn value(&mut self, ty: Ty, inst: impl FnOnce(VReg) -> Inst) -> Operand {
+        let dst = self.new_vreg(ty);
+        self.push(inst(dst));
+        Operand::Reg(dst)
+    }
+
+    pub(super) fn terminate(&mut self, term: Terminator) {
+        if self.sealed {
+            let block = self.new_block();
+            self.switch_to(block);
+        }
+        self.func.blocks[self.block].term = term;
+        self.sealed = true;
+    }
+
+    // the current block falls through into block, which becomes current
+    pub(super) fn enter(&mut self, block: BlockId) {
+        if !self.sealed {
+            self.terminate(Terminator::Jump(block));
+        }
+        self.switch_to(block);
+    }
+
+    pub(super) fn switch_to(&mut self, block: BlockId) {
+        self.block = block;
+        self.sealed = false;
+    }
+
+    pub(super) fn open_scope(&mut self) {
+        self.func.scopes.push(Some(self.scope));
+        self.scope = self.func.scopes.len() - 1;
+    }
+
+    pub(super) fn close_scope(&mut self) {
+        self.scope = self.func.scopes[self.scope].expect("unexcpected }");
+    }
+
+    pub(super) fn stmts_in_scope(&mut self, stmts: &[Stmt]) {
+        self.open_scope();
+        for stmt in stmts.iter() {
+            self.stmt(stmt);
+        }
+        self.close_scope();
+    }
+
+    // gives a local its slot in the current scope
+    pub(super) fn declare(&mut self, sym: Option<SymbolId>, name: &str, ty: TypeInfo, size: u32, align: u32, is_array: bool) -> SlotId {
+        self.func.slots.push(Slot { name: name.to_string(), size, align, scope: self.scope });
+        let slot = self.func.slots.len() - 1;
+        self.locals.insert(sym.expect("local was not resolved by sema"), Local { slot, ty, is_array });
+        slot
+    }
+}
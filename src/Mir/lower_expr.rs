@@ -0,0 +1,331 @@
+use super::*;
+use super::lower::Lower;
+use crate::Ir::expr::{Cast, Convert, Deref, Function as FunctionExpr, GetAddr, GetArrayValue, GetSizeOf, GetStructValue, Negative, Operator, PushNum, PushStr, PushVar, RpnExpr};
+use crate::Ir::sema::SymbolId;
+use crate::Ir::stmt::TypeInfo;
+use crate::Tokenizer::{Token, TokenType};
+
+// a value on the rpn stack and the type sema gave it
+pub(super) struct Value {
+    pub(super) operand: Operand,
+    pub(super) ty: TypeInfo,
+}
+
+impl Lower<'_> {
+    // the value of the whole expression, None for an empty one
+    pub(super) fn expr(&mut self, rpn: &[RpnExpr]) -> Option<Value> {
+        let mut stack: Vec<Value> = Vec::new();
+        for expr in rpn.iter() {
+            match expr {
+                RpnExpr::PushNum(v) => v.lower(&mut stack),
+                RpnExpr::PushStr(v) => v.lower(self, &mut stack),
+                RpnExpr::PushVar(v) => v.lower(self, &mut stack),
+                RpnExpr::Negative(v) => v.lower(self, &mut stack),
+                RpnExpr::GetArrayValue(v) => v.lower(self, &mut stack),
+                RpnExpr::Deref(v) => v.lower(self, &mut stack),
+                RpnExpr::GetAddr(v) => v.lower(self, &mut stack),
+                RpnExpr::GetSizeOf(v) => v.lower(self, &mut stack),
+                RpnExpr::GetStructValue(v) => v.lower(self, &mut stack),
+                RpnExpr::Function(v) => v.lower(self, &mut stack),
+                RpnExpr::Operator(v) => v.lower(self, &mut stack),
+                RpnExpr::Convert(v) => v.lower(self, &mut stack),
+                RpnExpr::Cast(v) => v.lower(self, &mut stack),
+            }
+        }
+        stack.pop()
+    }
+
+    // the value of an expression that can't be empty
+    pub(super) fn operand(&mut self, rpn: &[RpnExpr]) -> Operand {
+        self.expr(rpn).expect("expression without a value").operand
+    }
+
+    pub(super) fn literal(text: &str) -> i128 {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        // the parser writes the value of a pointer without an initializer in hex
+        let magnitude = match digits.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => digits.parse::<u64>(),
+        };
+        let magnitude = magnitude.unwrap_or_else(|_| panic!("not a number: {}", text)) as i128;
+        if negative { -magnitude } else { magnitude }
+    }
+
+    pub(super) fn load_local(&mut self, sym: Option<SymbolId>) -> Operand {
+        let local = self.local(sym);
+        let (slot, ty) = (local.slot, Lower::ty(&local.ty));
+        self.value(ty, |dst| Inst::LoadSlot { dst, ty, slot, offset: 0 })
+    }
+
+    // sign or zero extends the value, or keeps just the low part of it when
+    // it gets narrower, a bool is whether it isn't 0
+    pub(super) fn convert(&mut self, value: Operand, from: &TypeInfo, to: &TypeInfo) -> Operand {
+        let from_ty = Lower::ty(from);
+        let to_ty = Lower::ty(to);
+        let is_bool = |ty: &TypeInfo| ty.var_type == TokenType::Bool && ty.pointer_depth == 0;
+        if is_bool(to) && !is_bool(from) {
+            return self.value(Ty::I8, |dst| Inst::Cmp { dst, cond: Cond::Ne, ty: from_ty, lhs: value, rhs: Operand::Imm(0) });
+        }
+        let kind = if to_ty.size() < from_ty.size() {
+            CastKind::Trunc
+        }
+        else if to_ty.size() > from_ty.size() {
+            if Lower::is_unsigned(from) { CastKind::Zext } else { CastKind::Sext }
+        }
+        else {
+            return value;
+        };
+        self.value(to_ty, |dst| Inst::Cast { dst, kind, from: from_ty, to: to_ty, src: value })
+    }
+
+    // address of name[index], the array is in a slot, a pointer points to it
+    pub(super) fn element_addr(&mut self, sym: Option<SymbolId>, index: &Token, index_sym: Option<SymbolId>, size: u32) -> Operand {
+        let local = self.local(sym);
+        let (slot, is_array) = (local.slot, local.is_array);
+        let base = if is_array {
+            self.value(Ty::I64, |dst| Inst::SlotAddr { dst, slot })
+        }
+        else {
+            self.value(Ty::I64, |dst| Inst::LoadSlot { dst, ty: Ty::I64, slot, offset: 0 })
+        };
+        let offset = if index.token == TokenType::Var {
+            let index_ty = self.local(index_sym).ty.clone();
+            let value = self.load_local(index_sym);
+            let long = TypeInfo { var_type: TokenType::LongType, pointer_depth: 0, struct_name: None };
+            let value = self.convert(value, &index_ty, &long);
+            self.value(Ty::I64, |dst| Inst::Bin { dst, op: BinOp::Mul, ty: Ty::I64, lhs: value, rhs: Operand::Imm(size as i64) })
+        }
+        else {
+            Operand::Imm(Lower::literal(index.value.as_ref().unwrap()) as i64 * size as i64)
+        };
+        self.value(Ty::I64, |dst| Inst::Bin { dst, op: BinOp::Add, ty: Ty::I64, lhs: base, rhs: offset })
+    }
+
+    pub(super) fn args(&mut self, args: &[Vec<RpnExpr>]) -> Vec<(Ty, Operand)> {
+        args.iter()
+            .map(|arg| {
+                let value = self.expr(arg).expect("arg without a value");
+                (Lower::ty(&value.ty), value.operand)
+            })
+            .collect()
+    }
+}
+
+fn checked(ty: &Option<TypeInfo>) -> TypeInfo {
+    ty.clone().expect("expression was not checked by sema")
+}
+
+impl PushNum {
+    fn lower(&self, stack: &mut Vec<Value>) {
+        // sema made it an int, or a long when it doesn't fit
+        let ty = checked(&self.ty);
+        let value = Lower::ty(&ty).wrap(Lower::literal(self.data.value.as_ref().unwrap()));
+        stack.push(Value { operand: Operand::Imm(value), ty });
+    }
+}
+
+impl PushStr {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        lower.module.strings.push(self.data.value.clone().unwrap());
+        let index = lower.module.strings.len() - 1;
+        let operand = lower.value(Ty::I64, |dst| Inst::StrAddr { dst, index });
+        stack.push(Value { operand, ty: checked(&self.ty) });
+    }
+}
+
+impl PushVar {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let local = lower.local(self.sym);
+        let slot = local.slot;
+        // an array is the address of its first element
+        let operand = if local.is_array {
+            lower.value(Ty::I64, |dst| Inst::SlotAddr { dst, slot })
+        }
+        else {
+            lower.load_local(self.sym)
+        };
+        stack.push(Value { operand, ty: checked(&self.ty) });
+    }
+}
+
+impl Negative {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let ty = checked(&self.ty);
+        let operand = if self.data.token == TokenType::Var {
+            let value = lower.load_local(self.sym);
+            let neg_ty = Lower::ty(&ty);
+            lower.value(neg_ty, |dst| Inst::Neg { dst, ty: neg_ty, src: value })
+        }
+        else {
+            Operand::Imm(Lower::ty(&ty).wrap(-Lower::literal(self.data.value.as_ref().unwrap())))
+        };
+        stack.push(Value { operand, ty });
+    }
+}
+
+impl GetArrayValue {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let ty = checked(&self.ty);
+        let elem = Lower::ty(&ty);
+        let local = lower.local(self.sym);
+        let slot = local.slot;
+        let operand = if local.is_array && self.index.token != TokenType::Var {
+            let offset = Lower::literal(self.index.value.as_ref().unwrap()) as u32 * elem.size();
+            lower.value(elem, |dst| Inst::LoadSlot { dst, ty: elem, slot, offset })
+        }
+        else {
+            let addr = lower.element_addr(self.sym, &self.index, self.index_sym, elem.size());
+            lower.value(elem, |dst| Inst::Load { dst, ty: elem, ptr: addr })
+        };
+        stack.push(Value { operand, ty });
+    }
+}
+
+impl Deref {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let ty = checked(&self.ty);
+        let mut value = lower.load_local(self.sym);
+        // every level but the last loads another pointer
+        for level in 0..self.stack_depth {
+            let load_ty = if level + 1 == self.stack_depth { Lower::ty(&ty) } else { Ty::I64 };
+            let ptr = value;
+            value = lower.value(load_ty, |dst| Inst::Load { dst, ty: load_ty, ptr });
+        }
+        stack.push(Value { operand: value, ty });
+    }
+}
+
+impl GetAddr {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let slot = lower.local(self.sym).slot;
+        let operand = lower.value(Ty::I64, |dst| Inst::SlotAddr { dst, slot });
+        stack.push(Value { operand, ty: checked(&self.ty) });
+    }
+}
+
+impl GetSizeOf {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let size = match &self.of_type {
+            Some(ty) if ty.pointer_depth > 0 => 8,
+            Some(ty) if ty.var_type == TokenType::Struct => lower.struct_size(ty.struct_name.as_ref().unwrap()),
+            Some(ty) => Lower::type_size(ty.var_type),
+            None => {
+                let ty = lower.local(self.sym).ty.clone();
+                if ty.pointer_depth > 0 {
+                    8
+                }
+                else if ty.var_type == TokenType::Struct {
+                    lower.struct_size(ty.struct_name.as_ref().unwrap())
+                }
+                else {
+                    Lower::type_size(ty.var_type)
+                }
+            }
+        };
+        // sema typed it as an int
+        stack.push(Value { operand: Operand::Imm(size as i64), ty: checked(&self.ty) });
+    }
+}
+
+impl GetStructValue {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let local = lower.local(self.sym);
+        let slot = local.slot;
+        let struct_name = local.ty.struct_name.clone().expect("struct var without its struct");
+        let (offset, field) = lower.field(&struct_name, &self.struct_value_name);
+        let ty = Lower::ty(&field);
+        let operand = lower.value(ty, |dst| Inst::LoadSlot { dst, ty, slot, offset });
+        stack.push(Value { operand, ty: checked(&self.ty) });
+    }
+}
+
+impl FunctionExpr {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let ty = checked(&self.ty);
+        let args = lower.args(&self.args);
+        let callee = self.name.value.clone().unwrap();
+        let operand = lower.value(Lower::ty(&ty), |dst| Inst::Call { dst: Some(dst), callee, args });
+        stack.push(Value { operand, ty });
+    }
+}
+
+impl Operator {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let ty = checked(&self.ty);
+        let rhs = stack.pop().expect("rhs missing");
+        let lhs = stack.pop().expect("lhs missing");
+        let t = &self.data.token;
+        let unsigned = Lower::is_unsigned(&lhs.ty);
+
+        let op = match t {
+            TokenType::Add => BinOp::Add,
+            TokenType::Sub => BinOp::Sub,
+            TokenType::Mul => BinOp::Mul,
+            TokenType::Div => if unsigned { BinOp::UDiv } else { BinOp::SDiv },
+            TokenType::Remainder => if unsigned { BinOp::URem } else { BinOp::SRem },
+            _ => {
+                // comparisons, sema converted both sides to the same type
+                let cond = match t {
+                    TokenType::AsertEq  => Cond::Eq,
+                    TokenType::NotEq    => Cond::Ne,
+                    TokenType::Less     => if unsigned { Cond::Ult } else { Cond::Slt },
+                    TokenType::LessThan => if unsigned { Cond::Ule } else { Cond::Sle },
+                    TokenType::More     => if unsigned { Cond::Ugt } else { Cond::Sgt },
+                    TokenType::MoreThan => if unsigned { Cond::Uge } else { Cond::Sge },
+                    _ => panic!("unsupported operator {:?}", t),
+                };
+                let cmp_ty = Lower::ty(&lhs.ty);
+                let operand = lower.value(Ty::I8, |dst| Inst::Cmp { dst, cond, ty: cmp_ty, lhs: lhs.operand, rhs: rhs.operand });
+                stack.push(Value { operand, ty });
+                return;
+            }
+        };
+
+        // the integer side of pointer arithmetic counts elements
+        let (mut lhs_value, mut rhs_value) = (lhs.operand, rhs.operand);
+        if op == BinOp::Add || op == BinOp::Sub {
+            if lhs.ty.pointer_depth > 0 && rhs.ty.pointer_depth == 0 {
+                let size = lower.pointee_size(&ty) as i64;
+                rhs_value = lower.value(Ty::I64, |dst| Inst::Bin { dst, op: BinOp::Mul, ty: Ty::I64, lhs: rhs_value, rhs: Operand::Imm(size) });
+            }
+            else if rhs.ty.pointer_depth > 0 && lhs.ty.pointer_depth == 0 {
+                let size = lower.pointee_size(&ty) as i64;
+                lhs_value = lower.value(Ty::I64, |dst| Inst::Bin { dst, op: BinOp::Mul, ty: Ty::I64, lhs: lhs_value, rhs: Operand::Imm(size) });
+            }
+        }
+        let bin_ty = Lower::ty(&ty);
+        let mut operand = lower.value(bin_ty, |dst| Inst::Bin { dst, op, ty: bin_ty, lhs: lhs_value, rhs: rhs_value });
+        if let (Operand::Reg(dst), TokenType::Div | TokenType::Remainder) = (operand, t) {
+            // a division by zero is only found once the divisor is known
+            lower.func.spans.insert(dst, self.data.span.clone());
+        }
+        if op == BinOp::Sub && lhs.ty.pointer_depth > 0 && rhs.ty.pointer_depth > 0 {
+            // the distance is in elements, not bytes
+            let size = lower.pointee_size(&lhs.ty) as i64;
+            let bytes = operand;
+            operand = lower.value(Ty::I64, |dst| Inst::Bin { dst, op: BinOp::SDiv, ty: Ty::I64, lhs: bytes, rhs: Operand::Imm(size) });
+        }
+        stack.push(Value { operand, ty });
+    }
+}
+
+impl Convert {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let to = self.ty.as_ref().expect("conversion was not made by sema");
+        let value = stack.pop().expect("nothing to convert");
+        let operand = lower.convert(value.operand, &self.from, to);
+        stack.push(Value { operand, ty: to.clone() });
+    }
+}
+
+impl Cast {
+    fn lower(&self, lower: &mut Lower, stack: &mut Vec<Value>) {
+        let from = self.from.as_ref().expect("cast was not checked by sema");
+        let value = stack.pop().expect("nothing to cast");
+        let operand = lower.convert(value.operand, from, &self.to);
+        stack.push(Value { operand, ty: self.to.clone() });
+    }
+}
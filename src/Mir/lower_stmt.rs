@@ -0,0 +1,330 @@
+use super::*;
+use super::lower::Lower;
+use crate::Ir::Stmt;
+use crate::Ir::stmt::*;
+use crate::Tokenizer::TokenType;
+
+impl Lower<'_> {
+    pub(super) fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::CreateVar(v) => v.lower(self),
+            Stmt::CreatePointer(v) => v.lower(self),
+            Stmt::CreateStruct(v) => v.lower(self),
+            Stmt::InitArray(v) => v.lower(self),
+            Stmt::OpenScope(_) => self.open_scope(),
+            Stmt::CloseScope(_) => self.close_scope(),
+            Stmt::ChangeVar(v) => v.lower(self),
+            Stmt::ChangePtrValue(v) => v.lower(self),
+            Stmt::ChangeArrElement(v) => v.lower(self),
+            Stmt::ChangeStructValue(v) => v.lower(self),
+            Stmt::ChangePtrStructValue(v) => v.lower(self),
+            Stmt::IncVar(v) => self.step(v.sym, BinOp::Add),
+            Stmt::DecVar(v) => self.step(v.sym, BinOp::Sub),
+            Stmt::IfStmt(v) => v.lower(self),
+            Stmt::WhileStmt(v) => v.lower(self),
+            Stmt::DoWhileStmt(v) => v.lower(self),
+            Stmt::ForStmt(v) => v.lower(self),
+            Stmt::SwitchStmt(v) => v.lower(self),
+            Stmt::Break(_) => {
+                let target = *self.break_targets.last().expect("break outside of a loop or a switch");
+                self.terminate(Terminator::Jump(target));
+            }
+            Stmt::Continue(_) => {
+                let target = *self.continue_targets.last().expect("continue outside of a loop");
+                self.terminate(Terminator::Jump(target));
+            }
+            Stmt::Ret(v) => v.lower(self),
+            Stmt::FunctionCall(v) => {
+                let args = self.args(&v.args);
+                self.push(Inst::Call { dst: None, callee: v.name.value.clone().unwrap(), args });
+            }
+            Stmt::AsmCode(v) => v.lower(self),
+            // sema already put the values of the enumerators in the exprs
+            Stmt::InitStruct(_) | Stmt::InitEnum(_) | Stmt::FuncDecl(_) => {}
+            Stmt::InitFunc(v) => panic!("function {} inside of a function", v.name.value.as_ref().unwrap()),
+        }
+    }
+
+    // `x++` and `x--`
+    fn step(&mut self, sym: Option<crate::Ir::sema::SymbolId>, op: BinOp) {
+        let local = self.local(sym);
+        let (slot, ty) = (local.slot, Lower::ty(&local.ty));
+        let value = self.load_local(sym);
+        let res = self.value(ty, |dst| Inst::Bin { dst, op, ty, lhs: value, rhs: Operand::Imm(1) });
+        self.push(Inst::StoreSlot { ty, slot, offset: 0, src: res });
+    }
+
+    // a condition is a bool, branches to then when it's not 0
+    fn branch(&mut self, cond: &[crate::Ir::expr::RpnExpr], then: BlockId, other: BlockId) {
+        let cond = self.operand(cond);
+        self.terminate(Terminator::Branch { cond, then, other });
+    }
+
+    // body of a loop, a break in it jumps to end and a continue to next
+    fn loop_body(&mut self, data: &[Stmt], next: BlockId, end: BlockId) {
+        self.break_targets.push(end);
+        self.continue_targets.push(next);
+        self.stmts_in_scope(data);
+        self.break_targets.pop();
+        self.continue_targets.pop();
+    }
+}
+
+impl CreateVar {
+    fn lower(&self, lower: &mut Lower) {
+        let var_type = TypeInfo { var_type: self.Type, pointer_depth: 0, struct_name: self.enum_name.clone() };
+        let ty = Lower::ty(&var_type);
+        let value = lower.expr(&self.stmt);
+        let slot = lower.declare(self.sym, self.var.value.as_ref().unwrap(), var_type, ty.size(), ty.size(), false);
+        if let Some(value) = value {
+            lower.push(Inst::StoreSlot { ty, slot, offset: 0, src: value.operand });
+        }
+    }
+}
+
+impl CreatePointer {
+    fn lower(&self, lower: &mut Lower) {
+        let var_type = TypeInfo { var_type: self.type_, pointer_depth: self.pointer_depth, struct_name: None };
+        let value = lower.expr(&self.stmt);
+        // pointers takes 8 bytes no matter the real type
+        let slot = lower.declare(self.sym, self.var.value.as_ref().unwrap(), var_type, 8, 8, false);
+        if let Some(value) = value {
+            lower.push(Inst::StoreSlot { ty: Ty::I64, slot, offset: 0, src: value.operand });
+        }
+    }
+}
+
+impl CreateStruct {
+    fn lower(&self, lower: &mut Lower) {
+        let var_type = TypeInfo { var_type: TokenType::Struct, pointer_depth: self.pointer_depth, struct_name: Some(self.struct_name.clone()) };
+        let name = self.var_name.value.as_ref().unwrap();
+        if self.pointer_depth > 0 {
+            // sema only lets a pointer be initialized
+            let value = self.expr.as_ref().and_then(|expr| lower.expr(expr));
+            let slot = lower.declare(self.sym, name, var_type, 8, 8, false);
+            if let Some(value) = value {
+                lower.push(Inst::StoreSlot { ty: Ty::I64, slot, offset: 0, src: value.operand });
+            }
+            return;
+        }
+        let size = lower.struct_size(&self.struct_name);
+        let align = lower.struct_data(&self.struct_name).element_size;
+        lower.declare(self.sym, name, var_type, size, align, false);
+    }
+}
+
+impl InitArray {
+    fn lower(&self, lower: &mut Lower) {
+        let count: u32 = self.size.value.as_ref().unwrap().parse().unwrap();
+        let elem_type = TypeInfo { var_type: self.arr_type.token, pointer_depth: 0, struct_name: None };
+        let ty = Lower::ty(&elem_type);
+        let var_type = TypeInfo { pointer_depth: 1, ..elem_type };
+        let slot = lower.declare(self.sym, self.name.value.as_ref().unwrap(), var_type, ty.size() * count, ty.size(), true);
+        for (index, value) in self.data.iter().enumerate() {
+            let value = ty.wrap(Lower::literal(value.value.as_ref().unwrap()));
+            lower.push(Inst::StoreSlot { ty, slot, offset: index as u32 * ty.size(), src: Operand::Imm(value) });
+        }
+    }
+}
+
+impl ChangeVar {
+    fn lower(&self, lower: &mut Lower) {
+        let value = lower.operand(&self.stmt);
+        let local = lower.local(self.sym);
+        let (slot, ty) = (local.slot, Lower::ty(&local.ty));
+        lower.push(Inst::StoreSlot { ty, slot, offset: 0, src: value });
+    }
+}
+
+impl ChangePtrValue {
+    fn lower(&self, lower: &mut Lower) {
+        let value = lower.operand(&self.stmt);
+        let var_type = lower.local(self.sym).ty.clone();
+        let target = TypeInfo { pointer_depth: var_type.pointer_depth - self.pointer_depth, ..var_type };
+        let mut ptr = lower.load_local(self.sym);
+        // `**p = v` follows p once before storing
+        for _ in 1..self.pointer_depth {
+            let addr = ptr;
+            ptr = lower.value(Ty::I64, |dst| Inst::Load { dst, ty: Ty::I64, ptr: addr });
+        }
+        lower.push(Inst::Store { ty: Lower::ty(&target), ptr, src: value });
+    }
+}
+
+impl ChangeArrElement {
+    fn lower(&self, lower: &mut Lower) {
+        let value = lower.operand(&self.expr);
+        let var_type = lower.local(self.sym).ty.clone();
+        let ty = Lower::ty(&TypeInfo { pointer_depth: var_type.pointer_depth - 1, ..var_type });
+        if self.element.token == TokenType::Num {
+            let slot = lower.local(self.sym).slot;
+            let offset = Lower::literal(self.element.value.as_ref().unwrap()) as u32 * ty.size();
+            lower.push(Inst::StoreSlot { ty, slot, offset, src: value });
+        }
+        else {
+            let ptr = lower.element_addr(self.sym, &self.element, self.element_sym, ty.size());
+            lower.push(Inst::Store { ty, ptr, src: value });
+        }
+    }
+}
+
+impl ChangeStructValue {
+    fn lower(&self, lower: &mut Lower) {
+        let value = lower.operand(&self.expr);
+        let local = lower.local(self.sym);
+        let slot = local.slot;
+        let struct_name = local.ty.struct_name.clone().expect("struct var without its struct");
+        let (offset, field) = lower.field(&struct_name, &self.value_name);
+        lower.push(Inst::StoreSlot { ty: Lower::ty(&field), slot, offset, src: value });
+    }
+}
+
+impl ChangePtrStructValue {
+    fn lower(&self, lower: &mut Lower) {
+        // the value is computed first, like for every other assignment
+        let value = lower.operand(&self.expr);
+        let struct_name = lower.local(self.sym).ty.struct_name.clone().expect("struct pointer without its struct");
+        let (offset, field) = lower.field(&struct_name, &self.value_name);
+        let base = lower.load_local(self.sym);
+        let ptr = lower.value(Ty::I64, |dst| Inst::Bin { dst, op: BinOp::Add, ty: Ty::I64, lhs: base, rhs: Operand::Imm(offset as i64) });
+        lower.push(Inst::Store { ty: Lower::ty(&field), ptr, src: value });
+    }
+}
+
+impl IfStmt {
+    fn lower(&self, lower: &mut Lower) {
+        let then = lower.new_block();
+        let end = lower.new_block();
+        let other = if self.else_data.is_empty() { end } else { lower.new_block() };
+        lower.branch(&self.expr, then, other);
+        lower.switch_to(then);
+        lower.stmts_in_scope(&self.data);
+        if !self.else_data.is_empty() {
+            lower.terminate(Terminator::Jump(end));
+            lower.switch_to(other);
+            lower.stmts_in_scope(&self.else_data);
+        }
+        lower.enter(end);
+    }
+}
+
+impl WhileStmt {
+    fn lower(&self, lower: &mut Lower) {
+        let head = lower.new_block();
+        let body = lower.new_block();
+        let end = lower.new_block();
+        lower.enter(head);
+        lower.branch(&self.expr, body, end);
+        lower.switch_to(body);
+        lower.loop_body(&self.data, head, end);
+        lower.terminate(Terminator::Jump(head));
+        lower.switch_to(end);
+    }
+}
+
+impl DoWhileStmt {
+    fn lower(&self, lower: &mut Lower) {
+        let body = lower.new_block();
+        let cond = lower.new_block();
+        let end = lower.new_block();
+        lower.enter(body);
+        lower.loop_body(&self.data, cond, end);
+        lower.enter(cond);
+        lower.branch(&self.expr, body, end);
+        lower.switch_to(end);
+    }
+}
+
+impl ForStmt {
+    fn lower(&self, lower: &mut Lower) {
+        let head = lower.new_block();
+        let body = lower.new_block();
+        let step = lower.new_block();
+        let end = lower.new_block();
+        // the init clause is in scope for the whole loop
+        lower.open_scope();
+        for stmt in self.expr1.iter() {
+            lower.stmt(stmt);
+        }
+        lower.enter(head);
+        if self.expr2.is_empty() {
+            lower.terminate(Terminator::Jump(body));
+        }
+        else {
+            lower.branch(&self.expr2, body, end);
+        }
+        lower.switch_to(body);
+        lower.loop_body(&self.data, step, end);
+        lower.enter(step);
+        for stmt in self.expr3.iter() {
+            lower.stmt(stmt);
+        }
+        lower.terminate(Terminator::Jump(head));
+        lower.close_scope();
+        lower.switch_to(end);
+    }
+}
+
+impl SwitchStmt {
+    fn lower(&self, lower: &mut Lower) {
+        // sema made the value an int or a long
+        let value = lower.expr(&self.expr).expect("switch without a value");
+        let ty = Lower::ty(&value.ty);
+        let signed = !Lower::is_unsigned(&value.ty);
+        let end = lower.new_block();
+
+        // a block in front of every stmt a label is at, cases at the
+        // same stmt share it
+        let mut labels: Vec<(usize, BlockId)> = Vec::new();
+        let mut cases: Vec<(i64, BlockId)> = Vec::new();
+        let mut default = end;
+        for case in self.cases.iter() {
+            let block = match labels.iter().find(|(pos, _)| *pos == case.pos) {
+                Some((_, block)) => *block,
+                None => {
+                    let block = lower.new_block();
+                    labels.push((case.pos, block));
+                    block
+                }
+            };
+            match case.value {
+                Some(value) => cases.push((ty.wrap(value as i128), block)),
+                None => default = block,
+            }
+        }
+        lower.terminate(Terminator::Switch { ty, signed, value: value.operand, cases, default });
+
+        lower.break_targets.push(end);
+        lower.open_scope();
+        for (index, stmt) in self.data.iter().enumerate() {
+            if let Some((_, block)) = labels.iter().find(|(pos, _)| *pos == index) {
+                lower.enter(*block);
+            }
+            lower.stmt(stmt);
+        }
+        // labels at the very end of the body
+        for (_, block) in labels.iter().filter(|(pos, _)| *pos >= self.data.len()) {
+            lower.enter(*block);
+        }
+        lower.close_scope();
+        lower.break_targets.pop();
+        lower.enter(end);
+    }
+}
+
+impl Ret {
+    fn lower(&self, lower: &mut Lower) {
+        let value = lower.expr(&self.expr).map(|value| (Lower::ty(&value.ty), value.operand));
+        lower.terminate(Terminator::Ret(value));
+    }
+}
+
+impl AsmCode {
+    fn lower(&self, lower: &mut Lower) {
+        let mut vars: Vec<(String, SlotId)> = self.vars.iter()
+            .map(|(name, sym)| (name.clone(), lower.local(Some(*sym)).slot))
+            .collect();
+        vars.sort();
+        lower.push(Inst::Asm { lines: self.code.clone(), vars });
+    }
+}
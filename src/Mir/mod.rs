@@ -0,0 +1,528 @@
+//! Mid-level IR.
+//!
+//! Sits between the checked AST and the x86-64 emitter. A function is a list
+//! of basic blocks, every block is a list of typed three-address instructions
+//! ending in one explicit terminator. Values live in virtual registers
+//! (`%N`), locals live in stack slots (`$N`) until a pass decides otherwise.
+//!
+//! [`lower`] builds it from the AST, [`verify`] checks that it is well
+//! formed and `Display` prints the textual form written by `--emit ir`.
+//! [`PassManager`] runs the passes in between, the first of them takes the
+//! IR into SSA form, where phis merge the values of promoted locals, and
+//! the optimisations enabled by `-O` work on that form, after small
+//! functions were inlined into their callers. The loop passes find the
+//! loops of a function in [`loops`].
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Ir::stmt::Inline;
+use crate::Tokenizer::Span;
+
+mod constprop;
+mod dce;
+mod dom;
+mod inline;
+mod licm;
+mod loops;
+mod lower;
+mod lower_expr;
+mod lower_stmt;
+mod pass;
+mod ssa;
+mod strength;
+mod unroll;
+mod verify;
+
+pub(crate) use lower::lower;
+pub(crate) use pass::PassManager;
+pub(crate) use verify::verify;
+
+pub(crate) type VReg = usize;
+pub(crate) type BlockId = usize;
+pub(crate) type SlotId = usize;
+
+// every value is an integer of one of these widths, pointers are i64 and a
+// bool is an i8 holding 0 or 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Ty {
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl Ty {
+    pub(crate) fn size(self) -> u32 {
+        match self {
+            Ty::I8 => 1,
+            Ty::I16 => 2,
+            Ty::I32 => 4,
+            Ty::I64 => 8,
+        }
+    }
+
+    pub(crate) fn from_size(size: u32) -> Ty {
+        match size {
+            1 => Ty::I8,
+            2 => Ty::I16,
+            4 => Ty::I32,
+            8 => Ty::I64,
+            _ => panic!("no integer type of {} bytes", size),
+        }
+    }
+
+    // value wrapped to the width, as a sign extended i64
+    pub(crate) fn wrap(self, value: i128) -> i64 {
+        match self {
+            Ty::I8 => value as i8 as i64,
+            Ty::I16 => value as i16 as i64,
+            Ty::I32 => value as i32 as i64,
+            Ty::I64 => value as i64,
+        }
+    }
+}
+
+// an immediate is kept wrapped to the type it is used as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operand {
+    Reg(VReg),
+    Imm(i64),
+}
+
+impl Operand {
+    pub(crate) fn vreg(self) -> Option<VReg> {
+        match self {
+            Operand::Reg(v) => Some(v),
+            Operand::Imm(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cond {
+    Eq,
+    Ne,
+    Slt,
+    Sle,
+    Sgt,
+    Sge,
+    Ult,
+    Ule,
+    Ugt,
+    Uge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CastKind {
+    Sext,
+    Zext,
+    Trunc,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Inst {
+    // only at the start of a block, the value coming from each predecessor
+    Phi { dst: VReg, ty: Ty, args: Vec<(BlockId, Operand)> },
+    Copy { dst: VReg, ty: Ty, src: Operand },
+    Bin { dst: VReg, op: BinOp, ty: Ty, lhs: Operand, rhs: Operand },
+    Neg { dst: VReg, ty: Ty, src: Operand },
+    // dst is an i8, 1 when the condition holds
+    Cmp { dst: VReg, cond: Cond, ty: Ty, lhs: Operand, rhs: Operand },
+    Cast { dst: VReg, kind: CastKind, from: Ty, to: Ty, src: Operand },
+    SlotAddr { dst: VReg, slot: SlotId },
+    // address of string literal `index` of the module
+    StrAddr { dst: VReg, index: usize },
+    // offset is in bytes from the start of the slot
+    LoadSlot { dst: VReg, ty: Ty, slot: SlotId, offset: u32 },
+    StoreSlot { ty: Ty, slot: SlotId, offset: u32, src: Operand },
+    Load { dst: VReg, ty: Ty, ptr: Operand },
+    Store { ty: Ty, ptr: Operand, src: Operand },
+    Call { dst: Option<VReg>, callee: String, args: Vec<(Ty, Operand)> },
+    // inline asm, `(name)` in a line stands for the slot of var name
+    Asm { lines: Vec<String>, vars: Vec<(String, SlotId)> },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Terminator {
+    Jump(BlockId),
+    // cond is an i8, anything but 0 takes then
+    Branch { cond: Operand, then: BlockId, other: BlockId },
+    Switch { ty: Ty, signed: bool, value: Operand, cases: Vec<(i64, BlockId)>, default: BlockId },
+    Ret(Option<(Ty, Operand)>),
+    // nothing jumps here or control never reaches the end
+    Unreachable,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Block {
+    pub(crate) insts: Vec<Inst>,
+    pub(crate) term: Terminator,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Slot {
+    // the local it holds, for the dump
+    pub(crate) name: String,
+    pub(crate) size: u32,
+    pub(crate) align: u32,
+    // slots of scopes that are never open at the same time can share memory
+    pub(crate) scope: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Function {
+    pub(crate) name: String,
+    pub(crate) params: Vec<VReg>,
+    pub(crate) ret: Option<Ty>,
+    // `export`/`pub`, visible to other objects
+    pub(crate) exported: bool,
+    pub(crate) inline: Inline,
+    // type of every virtual register
+    pub(crate) vregs: Vec<Ty>,
+    pub(crate) slots: Vec<Slot>,
+    // parent of every scope, scope 0 is the body of the function
+    pub(crate) scopes: Vec<Option<usize>>,
+    // the entry is block 0
+    pub(crate) blocks: Vec<Block>,
+    // where in the source a register is computed, for the diagnostics of
+    // the passes
+    pub(crate) spans: HashMap<VReg, Span>,
+}
+
+impl Function {
+    pub(crate) fn new_vreg(&mut self, ty: Ty) -> VReg {
+        self.vregs.push(ty);
+        self.vregs.len() - 1
+    }
+
+    pub(crate) fn has_phis(&self) -> bool {
+        self.blocks.iter().any(|block| matches!(block.insts.first(), Some(Inst::Phi { .. })))
+    }
+
+    // blocks jumping to every block, each one once
+    pub(crate) fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for target in block.term.successors() {
+                if !preds[target].contains(&index) {
+                    preds[target].push(index);
+                }
+            }
+        }
+        preds
+    }
+}
+
+// what a call needs to know about the function it calls
+#[derive(Debug, Clone)]
+pub(crate) struct Signature {
+    pub(crate) params: Vec<Ty>,
+    pub(crate) ret: Option<Ty>,
+    pub(crate) variadic: bool,
+    // defined in another object, called through the plt
+    pub(crate) external: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Module {
+    pub(crate) functions: Vec<Function>,
+    pub(crate) signatures: HashMap<String, Signature>,
+    // string literals as written in the source, escapes included
+    pub(crate) strings: Vec<String>,
+    // what the passes found, they don't stop the compilation
+    pub(crate) warnings: Vec<String>,
+}
+
+impl Inst {
+    pub(crate) fn dst(&self) -> Option<VReg> {
+        match self {
+            Inst::Phi { dst, .. }
+            | Inst::Copy { dst, .. }
+            | Inst::Bin { dst, .. }
+            | Inst::Neg { dst, .. }
+            | Inst::Cmp { dst, .. }
+            | Inst::Cast { dst, .. }
+            | Inst::SlotAddr { dst, .. }
+            | Inst::StrAddr { dst, .. }
+            | Inst::LoadSlot { dst, .. }
+            | Inst::Load { dst, .. } => Some(*dst),
+            Inst::Call { dst, .. } => *dst,
+            Inst::StoreSlot { .. } | Inst::Store { .. } | Inst::Asm { .. } => None,
+        }
+    }
+
+    pub(crate) fn dst_mut(&mut self) -> Option<&mut VReg> {
+        match self {
+            Inst::Phi { dst, .. }
+            | Inst::Copy { dst, .. }
+            | Inst::Bin { dst, .. }
+            | Inst::Neg { dst, .. }
+            | Inst::Cmp { dst, .. }
+            | Inst::Cast { dst, .. }
+            | Inst::SlotAddr { dst, .. }
+            | Inst::StrAddr { dst, .. }
+            | Inst::LoadSlot { dst, .. }
+            | Inst::Load { dst, .. } => Some(dst),
+            Inst::Call { dst, .. } => dst.as_mut(),
+            Inst::StoreSlot { .. } | Inst::Store { .. } | Inst::Asm { .. } => None,
+        }
+    }
+
+    pub(crate) fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Inst::Phi { args, .. } => args.iter_mut().map(|(_, arg)| arg).collect(),
+            Inst::Copy { src, .. } | Inst::Neg { src, .. } | Inst::Cast { src, .. } | Inst::StoreSlot { src, .. } => vec![src],
+            Inst::Bin { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Load { ptr, .. } => vec![ptr],
+            Inst::Store { ptr, src, .. } => vec![ptr, src],
+            Inst::Call { args, .. } => args.iter_mut().map(|(_, arg)| arg).collect(),
+            Inst::SlotAddr { .. } | Inst::StrAddr { .. } | Inst::LoadSlot { .. } | Inst::Asm { .. } => Vec::new(),
+        }
+    }
+
+    pub(crate) fn operands(&self) -> Vec<Operand> {
+        self.clone().operands_mut().into_iter().map(|operand| *operand).collect()
+    }
+}
+
+impl Terminator {
+    pub(crate) fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { then, other, .. } => vec![*then, *other],
+            Terminator::Switch { cases, default, .. } => {
+                let mut res: Vec<BlockId> = cases.iter().map(|(_, target)| *target).collect();
+                res.push(*default);
+                res
+            }
+            Terminator::Ret(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub(crate) fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch { then, other, .. } => vec![then, other],
+            Terminator::Switch { cases, default, .. } => {
+                let mut res: Vec<&mut BlockId> = cases.iter_mut().map(|(_, target)| target).collect();
+                res.push(default);
+                res
+            }
+            Terminator::Ret(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub(crate) fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Ret(Some((_, value))) => vec![value],
+            Terminator::Jump(_) | Terminator::Ret(None) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub(crate) fn operands(&self) -> Vec<Operand> {
+        self.clone().operands_mut().into_iter().map(|operand| *operand).collect()
+    }
+}
+
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "i{}", self.size() * 8)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Reg(v) => write!(f, "%{}", v),
+            Operand::Imm(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::SDiv => "sdiv",
+            BinOp::UDiv => "udiv",
+            BinOp::SRem => "srem",
+            BinOp::URem => "urem",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Cond::Eq => "eq",
+            Cond::Ne => "ne",
+            Cond::Slt => "slt",
+            Cond::Sle => "sle",
+            Cond::Sgt => "sgt",
+            Cond::Sge => "sge",
+            Cond::Ult => "ult",
+            Cond::Ule => "ule",
+            Cond::Ugt => "ugt",
+            Cond::Uge => "uge",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for CastKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CastKind::Sext => "sext",
+            CastKind::Zext => "zext",
+            CastKind::Trunc => "trunc",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inst::Phi { dst, ty, args } => {
+                let args: Vec<String> = args.iter().map(|(block, arg)| format!("[bb{}: {}]", block, arg)).collect();
+                write!(f, "%{} = phi {} {}", dst, ty, args.join(", "))
+            }
+            Inst::Copy { dst, ty, src } => write!(f, "%{} = copy {} {}", dst, ty, src),
+            Inst::Bin { dst, op, ty, lhs, rhs } => write!(f, "%{} = {} {} {}, {}", dst, op, ty, lhs, rhs),
+            Inst::Neg { dst, ty, src } => write!(f, "%{} = neg {} {}", dst, ty, src),
+            Inst::Cmp { dst, cond, ty, lhs, rhs } => write!(f, "%{} = cmp {} {} {}, {}", dst, cond, ty, lhs, rhs),
+            Inst::Cast { dst, kind, from, to, src } => write!(f, "%{} = {} {} {} to {}", dst, kind, from, src, to),
+            Inst::SlotAddr { dst, slot } => write!(f, "%{} = addr ${}", dst, slot),
+            Inst::StrAddr { dst, index } => write!(f, "%{} = str {}", dst, index),
+            Inst::LoadSlot { dst, ty, slot, offset } => write!(f, "%{} = load {} ${}+{}", dst, ty, slot, offset),
+            Inst::StoreSlot { ty, slot, offset, src } => write!(f, "store {} ${}+{}, {}", ty, slot, offset, src),
+            Inst::Load { dst, ty, ptr } => write!(f, "%{} = load {} [{}]", dst, ty, ptr),
+            Inst::Store { ty, ptr, src } => write!(f, "store {} [{}], {}", ty, ptr, src),
+            Inst::Call { dst, callee, args } => {
+                if let Some(dst) = dst {
+                    write!(f, "%{} = ", dst)?;
+                }
+                let args: Vec<String> = args.iter().map(|(ty, arg)| format!("{} {}", ty, arg)).collect();
+                write!(f, "call {}({})", callee, args.join(", "))
+            }
+            Inst::Asm { lines, vars } => {
+                let vars: Vec<String> = vars.iter().map(|(name, slot)| format!("{} = ${}", name, slot)).collect();
+                write!(f, "asm {:?} [{}]", lines, vars.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jmp bb{}", target),
+            Terminator::Branch { cond, then, other } => write!(f, "br {}, bb{}, bb{}", cond, then, other),
+            Terminator::Switch { ty, signed, value, cases, default } => {
+                let cases: Vec<String> = cases.iter().map(|(value, target)| format!("{}: bb{}", value, target)).collect();
+                let kind = if *signed { "signed" } else { "unsigned" };
+                write!(f, "switch {} {} {}, default bb{} [{}]", kind, ty, value, default, cases.join(", "))
+            }
+            Terminator::Ret(Some((ty, value))) => write!(f, "ret {} {}", ty, value),
+            Terminator::Ret(None) => write!(f, "ret"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params: Vec<String> = self.params.iter().map(|v| format!("{} %{}", self.vregs[*v], v)).collect();
+        let ret = self.ret.map(|ty| format!(" -> {}", ty)).unwrap_or_default();
+        let export = if self.exported { "export " } else { "" };
+        let inline = match self.inline {
+            Inline::Auto => "",
+            Inline::Hint => "inline ",
+            Inline::Never => "noinline ",
+        };
+        writeln!(f, "{}{}fn {}({}){} {{", export, inline, self.name, params.join(", "), ret)?;
+        for (index, parent) in self.scopes.iter().enumerate() {
+            if let Some(parent) = parent {
+                writeln!(f, "    scope {} in {}", index, parent)?;
+            }
+        }
+        for (index, slot) in self.slots.iter().enumerate() {
+            writeln!(f, "    ${} {}: {} bytes, align {}, scope {}", index, slot.name, slot.size, slot.align, slot.scope)?;
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "bb{}:", index)?;
+            for inst in block.insts.iter() {
+                writeln!(f, "    {}", inst)?;
+            }
+            writeln!(f, "    {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut externs: Vec<&String> = self.signatures.iter()
+            .filter(|(_, sig)| sig.external)
+            .map(|(name, _)| name)
+            .collect();
+        externs.sort();
+        for name in externs {
+            let sig = &self.signatures[name];
+            let mut params: Vec<String> = sig.params.iter().map(|ty| ty.to_string()).collect();
+            if sig.variadic {
+                params.push("...".to_string());
+            }
+            let ret = sig.ret.map(|ty| format!(" -> {}", ty)).unwrap_or_default();
+            writeln!(f, "extern fn {}({}){}", name, params.join(", "), ret)?;
+        }
+        for (index, value) in self.strings.iter().enumerate() {
+            writeln!(f, "str {} = \"{}\"", index, value)?;
+        }
+        for func in self.functions.iter() {
+            writeln!(f)?;
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
+
+// a module of one exported function t returning an i32, built by hand for
+// the tests of the passes: the first params registers are its params and
+// it can call the extern fn f(i64) -> i32
+#[cfg(test)]
+pub(crate) fn test_module(vregs: &[Ty], params: usize, slots: &[u32], blocks: Vec<Block>) -> Module {
+    let func = Function {
+        name: "t".to_string(),
+        params: (0..params).collect(),
+        ret: Some(Ty::I32),
+        exported: true,
+        inline: Inline::Auto,
+        vregs: vregs.to_vec(),
+        slots: slots.iter().map(|size| Slot { name: String::new(), size: *size, align: 4, scope: 0 }).collect(),
+        scopes: vec![None],
+        blocks,
+        spans: HashMap::new(),
+    };
+    let mut signatures = HashMap::new();
+    signatures.insert("f".to_string(), Signature { params: vec![Ty::I64], ret: Some(Ty::I32), variadic: false, external: true });
+    Module { functions: vec![func], signatures, ..Module::default() }
+}
@@ -0,0 +1,140 @@
+//! Pass manager.
+//!
+//! Runs named passes over the module in order and checks the IR with the
+//! verifier after each of them, so a broken pass is reported by name instead
+//! of as bad asm. `-O<level>` picks the pipeline, `--passes=a,b,c`
+//! replaces it and `--print-after=<pass>` dumps the IR every time the pass
+//! ran.
+
+use super::*;
+
+type Pass = fn(&mut Module);
+
+// every pass that can be named
+const PASSES: &[(&str, Pass)] = &[
+    ("inline", super::inline::run),
+    ("ssa", super::ssa::construct),
+    ("constprop", super::constprop::run),
+    ("licm", super::licm::run),
+    ("strength-reduce", super::strength::run),
+    ("unroll", super::unroll::run),
+    ("dce", super::dce::run),
+    ("out-of-ssa", super::ssa::destruct),
+];
+
+// what runs without --passes, -O0 leaves the IR as it was lowered and
+// -O2 unrolls loops, cleaned up before and folded after
+fn pipeline(level: u8) -> &'static [&'static str] {
+    match level {
+        0 => &[],
+        1 => &["inline", "ssa", "constprop", "licm", "strength-reduce", "dce", "out-of-ssa"],
+        _ => &["inline", "ssa", "constprop", "dce", "unroll", "constprop", "licm", "strength-reduce", "dce", "out-of-ssa"],
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PassManager {
+    level: u8,
+    passes: Vec<String>,
+    print_after: Vec<String>,
+}
+
+impl PassManager {
+    // None runs the pipeline of the level
+    pub(crate) fn new(level: u8, passes: Option<&[String]>, print_after: &[String]) -> Result<PassManager, String> {
+        let passes: Vec<String> = match passes {
+            Some(passes) => passes.iter().filter(|name| !name.is_empty()).cloned().collect(),
+            None => pipeline(level).iter().map(|name| name.to_string()).collect(),
+        };
+        for name in passes.iter().chain(print_after.iter()) {
+            if PassManager::find(name).is_none() {
+                let known: Vec<&str> = PASSES.iter().map(|(name, _)| *name).collect();
+                return Err(format!("unknown pass {}, known passes are: {}", name, known.join(", ")));
+            }
+        }
+        Ok(PassManager { level, passes, print_after: print_after.to_vec() })
+    }
+
+    // the emitter cleans up the asm from -O1 on, whatever --passes says
+    pub(crate) fn level(&self) -> u8 {
+        self.level
+    }
+
+    fn find(name: &str) -> Option<Pass> {
+        PASSES.iter().find(|(known, _)| *known == name).map(|(_, pass)| *pass)
+    }
+
+    pub(crate) fn run(&self, module: &mut Module) -> Result<(), String> {
+        for name in self.passes.iter() {
+            self.run_pass(name, module)?;
+        }
+        // the emitter can't take phis
+        if module.functions.iter().any(Function::has_phis) {
+            self.run_pass("out-of-ssa", module)?;
+        }
+        Ok(())
+    }
+
+    fn run_pass(&self, name: &str, module: &mut Module) -> Result<(), String> {
+        PassManager::find(name).unwrap()(module);
+        if self.print_after.iter().any(|print| print == name) {
+            eprintln!("; IR after {}", name);
+            eprint!("{}", module);
+        }
+        verify(module).map_err(|errors| format!("malformed IR after {}:\n{}", name, errors.join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_replace_the_pipeline() {
+        let passes = ["ssa".to_string(), String::new(), "out-of-ssa".to_string()];
+        let manager = PassManager::new(2, Some(&passes), &[]).unwrap();
+        assert_eq!(manager.passes, vec!["ssa", "out-of-ssa"]);
+        // the asm is still cleaned up for the level
+        assert_eq!(manager.level(), 2);
+        assert!(PassManager::new(0, None, &[]).unwrap().passes.is_empty());
+        assert_eq!(PassManager::new(1, None, &[]).unwrap().passes, pipeline(1));
+    }
+
+    #[test]
+    fn unknown_pass_names() {
+        let known = "known passes are: inline, ssa, constprop, licm, strength-reduce, unroll, dce, out-of-ssa";
+        let error = PassManager::new(0, Some(&["ssa".to_string(), "cse".to_string()]), &[]).unwrap_err();
+        assert_eq!(error, format!("unknown pass cse, {}", known));
+        let error = PassManager::new(1, None, &["ssa".to_string(), "gvn".to_string()]).unwrap_err();
+        assert_eq!(error, format!("unknown pass gvn, {}", known));
+    }
+
+    #[test]
+    fn phis_left_by_passes_are_destructed() {
+        // s0 is stored on both sides of an if
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Branch { cond: Operand::Reg(0), then: 1, other: 2 } },
+            Block {
+                insts: vec![Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Imm(1) }],
+                term: Terminator::Jump(3),
+            },
+            Block {
+                insts: vec![Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Imm(2) }],
+                term: Terminator::Jump(3),
+            },
+            Block {
+                insts: vec![Inst::LoadSlot { dst: 1, ty: Ty::I32, slot: 0, offset: 0 }],
+                term: Terminator::Ret(Some((Ty::I32, Operand::Reg(1)))),
+            },
+        ];
+        let mut module = test_module(&[Ty::I8, Ty::I32], 1, &[4], blocks);
+        let manager = PassManager::new(0, Some(&["ssa".to_string()]), &["ssa".to_string()]).unwrap();
+        manager.run(&mut module).unwrap();
+        let func = &module.functions[0];
+        assert!(!func.has_phis());
+        assert!(func.blocks.iter().flat_map(|block| block.insts.iter())
+            .all(|inst| !matches!(inst, Inst::LoadSlot { .. } | Inst::StoreSlot { .. })));
+        assert!(matches!(func.blocks[1].insts[..], [Inst::Copy { src: Operand::Imm(1), .. }]));
+        assert!(matches!(func.blocks[2].insts[..], [Inst::Copy { src: Operand::Imm(2), .. }]));
+    }
+}
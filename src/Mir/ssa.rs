@@ -0,0 +1,410 @@
+//! SSA construction and destruction.
+//!
+//! Going into SSA promotes every slot holding a plain scalar, one whose
+//! address is never taken and that is only ever loaded and stored whole, to
+//! virtual registers: a phi goes wherever two stores can reach the same
+//! block (the iterated dominance frontier of the stores) and a walk down the
+//! dominator tree renames every load to the value stored last. Only blocks
+//! nothing reaches still access the promoted slots.
+//!
+//! Going out of SSA turns every phi into copies at the end of its
+//! predecessors, splitting the edges a copy can't be put on without running
+//! on another path too.
+
+use std::collections::HashMap;
+
+use super::*;
+use super::dom::DomTree;
+
+pub(crate) fn construct(module: &mut Module) {
+    for func in module.functions.iter_mut() {
+        Ssa::new(func).construct();
+    }
+}
+
+pub(crate) fn destruct(module: &mut Module) {
+    for func in module.functions.iter_mut() {
+        destruct_function(func);
+    }
+}
+
+struct Ssa<'a> {
+    func: &'a mut Function,
+    // type of the promoted slots
+    promoted: HashMap<SlotId, Ty>,
+    // phis placed at the start of every block and the slot they merge
+    phis: Vec<Vec<(SlotId, VReg)>>,
+    // what a load was renamed to
+    renamed: HashMap<VReg, Operand>,
+    // value every promoted slot holds at the current point of the walk
+    values: HashMap<SlotId, Vec<Operand>>,
+    // the args of the phis, gathered from the predecessors
+    phi_args: HashMap<VReg, Vec<(BlockId, Operand)>>,
+}
+
+impl Ssa<'_> {
+    fn new(func: &mut Function) -> Ssa<'_> {
+        let blocks = func.blocks.len();
+        Ssa {
+            func,
+            promoted: HashMap::new(),
+            phis: vec![Vec::new(); blocks],
+            renamed: HashMap::new(),
+            values: HashMap::new(),
+            phi_args: HashMap::new(),
+        }
+    }
+
+    fn construct(&mut self) {
+        self.find_promotable();
+        if self.promoted.is_empty() {
+            return;
+        }
+        let tree = DomTree::new(self.func);
+        self.place_phis(&tree);
+        // every slot reads as 0 before its first store
+        for slot in self.promoted.keys() {
+            self.values.insert(*slot, vec![Operand::Imm(0)]);
+        }
+        self.rename(&tree, 0);
+
+        let preds = self.func.predecessors();
+        for (block, preds) in preds.iter().enumerate() {
+            let phis = std::mem::take(&mut self.phis[block]);
+            let mut insts: Vec<Inst> = phis.iter()
+                .map(|(slot, dst)| {
+                    let mut args = self.phi_args.remove(dst).unwrap_or_default();
+                    // nothing reaches the predecessors that are left
+                    for pred in preds.iter() {
+                        if !args.iter().any(|(from, _)| from == pred) {
+                            args.push((*pred, Operand::Imm(0)));
+                        }
+                    }
+                    Inst::Phi { dst: *dst, ty: self.promoted[slot], args }
+                })
+                .collect();
+            insts.append(&mut self.func.blocks[block].insts);
+            self.func.blocks[block].insts = insts;
+        }
+        // blocks the walk never reached can still use a renamed load
+        for block in self.func.blocks.iter_mut() {
+            for inst in block.insts.iter_mut() {
+                for operand in inst.operands_mut() {
+                    Ssa::rename_operand(&self.renamed, operand);
+                }
+            }
+            for operand in block.term.operands_mut() {
+                Ssa::rename_operand(&self.renamed, operand);
+            }
+        }
+    }
+
+    fn find_promotable(&mut self) {
+        let mut promoted: HashMap<SlotId, Ty> = HashMap::new();
+        let mut escaped = vec![false; self.func.slots.len()];
+        for block in self.func.blocks.iter() {
+            for inst in block.insts.iter() {
+                let (slot, ty, offset) = match inst {
+                    Inst::LoadSlot { slot, ty, offset, .. } | Inst::StoreSlot { slot, ty, offset, .. } => (*slot, *ty, *offset),
+                    Inst::SlotAddr { slot, .. } => {
+                        escaped[*slot] = true;
+                        continue;
+                    }
+                    Inst::Asm { vars, .. } => {
+                        for (_, slot) in vars.iter() {
+                            escaped[*slot] = true;
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if offset != 0 || ty.size() != self.func.slots[slot].size || promoted.get(&slot).is_some_and(|other| *other != ty) {
+                    escaped[slot] = true;
+                }
+                promoted.insert(slot, ty);
+            }
+        }
+        promoted.retain(|slot, _| !escaped[*slot]);
+        self.promoted = promoted;
+    }
+
+    // a store of a slot needs a phi at its frontier, and that phi is a new
+    // definition needing phis at its own frontier
+    fn place_phis(&mut self, tree: &DomTree) {
+        let frontiers = tree.frontiers(self.func);
+        let mut slots: Vec<SlotId> = self.promoted.keys().copied().collect();
+        slots.sort();
+        for slot in slots {
+            let mut work: Vec<BlockId> = tree.order.iter().copied()
+                .filter(|block| self.func.blocks[*block].insts.iter()
+                    .any(|inst| matches!(inst, Inst::StoreSlot { slot: stored, .. } if *stored == slot)))
+                .collect();
+            let mut has_phi = vec![false; self.func.blocks.len()];
+            while let Some(block) = work.pop() {
+                for frontier in frontiers[block].iter() {
+                    if has_phi[*frontier] {
+                        continue;
+                    }
+                    has_phi[*frontier] = true;
+                    let dst = self.func.new_vreg(self.promoted[&slot]);
+                    self.phis[*frontier].push((slot, dst));
+                    work.push(*frontier);
+                }
+            }
+        }
+    }
+
+    fn rename_operand(renamed: &HashMap<VReg, Operand>, operand: &mut Operand) {
+        if let Operand::Reg(v) = operand
+            && let Some(value) = renamed.get(v) {
+            *operand = *value;
+        }
+    }
+
+    fn rename(&mut self, tree: &DomTree, block: BlockId) {
+        // how many values every slot got in this block, popped at the end
+        let mut pushed: Vec<SlotId> = Vec::new();
+        for (slot, dst) in self.phis[block].iter() {
+            self.values.get_mut(slot).unwrap().push(Operand::Reg(*dst));
+            pushed.push(*slot);
+        }
+
+        let insts = std::mem::take(&mut self.func.blocks[block].insts);
+        let mut kept = Vec::with_capacity(insts.len());
+        for mut inst in insts {
+            for operand in inst.operands_mut() {
+                Ssa::rename_operand(&self.renamed, operand);
+            }
+            match inst {
+                Inst::LoadSlot { dst, slot, .. } if self.promoted.contains_key(&slot) => {
+                    let value = *self.values[&slot].last().unwrap();
+                    self.renamed.insert(dst, value);
+                }
+                Inst::StoreSlot { slot, src, .. } if self.promoted.contains_key(&slot) => {
+                    self.values.get_mut(&slot).unwrap().push(src);
+                    pushed.push(slot);
+                }
+                inst => kept.push(inst),
+            }
+        }
+        self.func.blocks[block].insts = kept;
+        for operand in self.func.blocks[block].term.operands_mut() {
+            Ssa::rename_operand(&self.renamed, operand);
+        }
+
+        for succ in self.func.blocks[block].term.successors() {
+            for (slot, dst) in self.phis[succ].iter() {
+                let args = self.phi_args.entry(*dst).or_default();
+                if !args.iter().any(|(from, _)| *from == block) {
+                    args.push((block, *self.values[slot].last().unwrap()));
+                }
+            }
+        }
+
+        for child in tree.children[block].clone() {
+            self.rename(tree, child);
+        }
+        for slot in pushed {
+            self.values.get_mut(&slot).unwrap().pop();
+        }
+    }
+}
+
+fn destruct_function(func: &mut Function) {
+    let preds = func.predecessors();
+    for (block, preds) in preds.iter().enumerate() {
+        let phi_count = func.blocks[block].insts.iter().take_while(|inst| matches!(inst, Inst::Phi { .. })).count();
+        if phi_count == 0 {
+            continue;
+        }
+        let phis: Vec<Inst> = func.blocks[block].insts.drain(..phi_count).collect();
+        for pred in preds.iter() {
+            // a copy at the end of a block that also jumps elsewhere would
+            // run on the other path too, it goes on a block of its own
+            let succs = func.blocks[*pred].term.successors();
+            let from = if succs.iter().any(|succ| *succ != block) {
+                func.blocks.push(Block { insts: Vec::new(), term: Terminator::Jump(block) });
+                let split = func.blocks.len() - 1;
+                for target in func.blocks[*pred].term.successors_mut() {
+                    if *target == block {
+                        *target = split;
+                    }
+                }
+                split
+            }
+            else {
+                *pred
+            };
+
+            // the phis take their values at the same time, so with more than
+            // one every value is copied aside first in case a phi reads
+            // another one of them
+            let mut copies: Vec<(VReg, Ty, Operand)> = phis.iter()
+                .map(|phi| match phi {
+                    Inst::Phi { dst, ty, args } => {
+                        let (_, value) = args.iter().find(|(from, _)| from == pred)
+                            .unwrap_or_else(|| panic!("phi %{} has no value for bb{}", dst, pred));
+                        (*dst, *ty, *value)
+                    }
+                    _ => unreachable!(),
+                })
+                .collect();
+            if copies.len() > 1 {
+                let mut temps = Vec::new();
+                for (_, ty, value) in copies.iter_mut() {
+                    let temp = func.new_vreg(*ty);
+                    temps.push(Inst::Copy { dst: temp, ty: *ty, src: *value });
+                    *value = Operand::Reg(temp);
+                }
+                func.blocks[from].insts.append(&mut temps);
+            }
+            for (dst, ty, value) in copies {
+                func.blocks[from].insts.push(Inst::Copy { dst, ty, src: value });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the phis at the start of the block with their args
+    fn phis(func: &Function, block: BlockId) -> Vec<(VReg, Vec<(BlockId, Operand)>)> {
+        func.blocks[block].insts.iter().filter_map(|inst| match inst {
+            Inst::Phi { dst, args, .. } => Some((*dst, args.clone())),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn phis_on_iterated_frontiers() {
+        // s0 is stored before the loop and on one side of an if inside it,
+        // the if joins in bb4 and only that phi brings s0 back to bb1
+        let blocks = vec![
+            Block {
+                insts: vec![Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Imm(1) }],
+                term: Terminator::Jump(1),
+            },
+            Block {
+                insts: vec![
+                    Inst::LoadSlot { dst: 1, ty: Ty::I32, slot: 0, offset: 0 },
+                    Inst::Cmp { dst: 2, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(10) },
+                ],
+                term: Terminator::Branch { cond: Operand::Reg(2), then: 2, other: 5 },
+            },
+            Block { insts: Vec::new(), term: Terminator::Branch { cond: Operand::Reg(0), then: 3, other: 4 } },
+            Block {
+                insts: vec![
+                    Inst::Bin { dst: 3, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(1) },
+                    Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Reg(3) },
+                ],
+                term: Terminator::Jump(4),
+            },
+            Block { insts: Vec::new(), term: Terminator::Jump(1) },
+            Block {
+                insts: vec![Inst::LoadSlot { dst: 4, ty: Ty::I32, slot: 0, offset: 0 }],
+                term: Terminator::Ret(Some((Ty::I32, Operand::Reg(4)))),
+            },
+        ];
+        let mut module = test_module(&[Ty::I8, Ty::I32, Ty::I8, Ty::I32, Ty::I32], 1, &[4], blocks);
+        construct(&mut module);
+        verify(&module).unwrap();
+        let func = &module.functions[0];
+        assert!(func.blocks.iter().flat_map(|block| block.insts.iter())
+            .all(|inst| !matches!(inst, Inst::LoadSlot { .. } | Inst::StoreSlot { .. })));
+
+        let [(join, join_args)] = &phis(func, 4)[..] else { panic!("bb4 needs one phi") };
+        let [(header, header_args)] = &phis(func, 1)[..] else { panic!("bb1 needs one phi") };
+        for block in [0, 2, 3, 5] {
+            assert!(phis(func, block).is_empty());
+        }
+        assert_eq!(header_args, &vec![(0, Operand::Imm(1)), (4, Operand::Reg(*join))]);
+        assert_eq!(join_args, &vec![(2, Operand::Reg(*header)), (3, Operand::Reg(3))]);
+    }
+
+    #[test]
+    fn loads_renamed_to_the_last_store() {
+        // s1 has its address taken and stays in memory
+        let blocks = vec![Block {
+            insts: vec![
+                Inst::LoadSlot { dst: 0, ty: Ty::I32, slot: 0, offset: 0 },
+                Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Imm(7) },
+                Inst::LoadSlot { dst: 1, ty: Ty::I32, slot: 0, offset: 0 },
+                Inst::Bin { dst: 2, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Reg(1) },
+                Inst::StoreSlot { ty: Ty::I32, slot: 0, offset: 0, src: Operand::Reg(2) },
+                Inst::SlotAddr { dst: 3, slot: 1 },
+                Inst::StoreSlot { ty: Ty::I32, slot: 1, offset: 0, src: Operand::Imm(3) },
+                Inst::LoadSlot { dst: 4, ty: Ty::I32, slot: 0, offset: 0 },
+            ],
+            term: Terminator::Ret(Some((Ty::I32, Operand::Reg(4)))),
+        }];
+        let mut module = test_module(&[Ty::I32, Ty::I32, Ty::I32, Ty::I64, Ty::I32], 0, &[4, 4], blocks);
+        construct(&mut module);
+        verify(&module).unwrap();
+        let block = &module.functions[0].blocks[0];
+        assert_eq!(block.insts.len(), 3);
+        // a load before any store reads 0
+        assert!(matches!(block.insts[0], Inst::Bin { dst: 2, lhs: Operand::Imm(0), rhs: Operand::Imm(7), .. }));
+        assert!(matches!(block.insts[1], Inst::SlotAddr { dst: 3, slot: 1 }));
+        assert!(matches!(block.insts[2], Inst::StoreSlot { slot: 1, .. }));
+        assert!(matches!(block.term, Terminator::Ret(Some((Ty::I32, Operand::Reg(2))))));
+    }
+
+    #[test]
+    fn copies_on_critical_edges_get_a_block() {
+        // bb0 -> bb2 is critical, bb1 -> bb2 is not
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Branch { cond: Operand::Reg(0), then: 1, other: 2 } },
+            Block { insts: Vec::new(), term: Terminator::Jump(2) },
+            Block {
+                insts: vec![Inst::Phi { dst: 1, ty: Ty::I32, args: vec![(0, Operand::Imm(1)), (1, Operand::Imm(2))] }],
+                term: Terminator::Ret(Some((Ty::I32, Operand::Reg(1)))),
+            },
+        ];
+        let mut module = test_module(&[Ty::I8, Ty::I32], 1, &[], blocks);
+        destruct(&mut module);
+        verify(&module).unwrap();
+        let func = &module.functions[0];
+        assert_eq!(func.blocks.len(), 4);
+        assert!(!func.has_phis());
+        assert!(func.blocks[0].insts.is_empty());
+        assert!(matches!(func.blocks[0].term, Terminator::Branch { then: 1, other: 3, .. }));
+        assert!(matches!(func.blocks[1].insts[..], [Inst::Copy { dst: 1, src: Operand::Imm(2), .. }]));
+        assert!(matches!(func.blocks[3].insts[..], [Inst::Copy { dst: 1, src: Operand::Imm(1), .. }]));
+        assert!(matches!(func.blocks[3].term, Terminator::Jump(2)));
+    }
+
+    #[test]
+    fn swap_through_parallel_copy() {
+        // `t = a; a = b; b = t;` in a loop, each phi reads the other
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Jump(1) },
+            Block {
+                insts: vec![
+                    Inst::Phi { dst: 1, ty: Ty::I32, args: vec![(0, Operand::Imm(10)), (2, Operand::Reg(2))] },
+                    Inst::Phi { dst: 2, ty: Ty::I32, args: vec![(0, Operand::Imm(20)), (2, Operand::Reg(1))] },
+                ],
+                term: Terminator::Branch { cond: Operand::Reg(0), then: 2, other: 3 },
+            },
+            Block { insts: Vec::new(), term: Terminator::Jump(1) },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Reg(1)))) },
+        ];
+        let mut module = test_module(&[Ty::I8, Ty::I32, Ty::I32], 1, &[], blocks);
+        destruct(&mut module);
+        verify(&module).unwrap();
+
+        // run the copies of the back edge one after the other
+        let func = &module.functions[0];
+        let mut values: HashMap<VReg, i64> = HashMap::from([(1, 10), (2, 20)]);
+        for inst in func.blocks[2].insts.iter() {
+            let Inst::Copy { dst, src, .. } = inst else { panic!("bb2 has only copies") };
+            let value = match src {
+                Operand::Reg(v) => values[v],
+                Operand::Imm(v) => *v,
+            };
+            values.insert(*dst, value);
+        }
+        assert_eq!((values[&1], values[&2]), (20, 10));
+    }
+}
@@ -0,0 +1,251 @@
+//! Strength reduction of induction variables.
+//!
+//! An induction variable is a phi of a loop header that goes up by the same
+//! constant on every iteration, like the index of a `for` loop. A product of
+//! one with a constant, the offset of `arr[i]`, goes up by a constant too:
+//! it becomes a phi of its own, set before the loop and stepped next to the
+//! variable, so the loop adds instead of multiplying. The address the offset
+//! is added to then steps the same way and the loop walks a pointer.
+//!
+//! An index widened to 64 bits counts as stepping too, a signed index is
+//! assumed not to wrap the way C assumes it. Only loops with one jump back
+//! are looked at, with more the new phis would need a step on every path.
+
+use std::collections::HashMap;
+
+use super::*;
+use super::constprop::{fold_bin, fold_cast};
+use super::dom::reverse_postorder;
+use super::licm::{def_blocks, invariant};
+use super::loops::{self, Loop};
+
+#[derive(Debug, Clone, Copy)]
+struct Iv {
+    ty: Ty,
+    // value entering the loop, defined in the preheader or before
+    init: Operand,
+    step: i64,
+    // the register the next iteration gets and where it is computed
+    next: VReg,
+    // made by this pass, so adding to it costs nothing more
+    reduced: bool,
+}
+
+pub(crate) fn run(module: &mut Module) {
+    for func in module.functions.iter_mut() {
+        if !loops::is_ssa(func) {
+            continue;
+        }
+        for (lp, pre) in loops::prepare(func) {
+            if let [latch] = lp.latches[..] {
+                reduce(func, &lp, pre, latch);
+            }
+        }
+    }
+}
+
+// the place of the instruction defining vreg
+fn find_def(func: &Function, vreg: VReg) -> Option<(BlockId, usize)> {
+    func.blocks.iter().enumerate().find_map(|(index, block)| {
+        block.insts.iter().position(|inst| inst.dst() == Some(vreg)).map(|pos| (index, pos))
+    })
+}
+
+// the phis of the header stepping by a constant
+fn basic_ivs(func: &Function, lp: &Loop, pre: BlockId, latch: BlockId) -> HashMap<VReg, Iv> {
+    let mut ivs = HashMap::new();
+    for inst in func.blocks[lp.header].insts.iter() {
+        let Inst::Phi { dst, ty, args } = inst else {
+            break;
+        };
+        let (Some((_, init)), Some((_, Operand::Reg(next)))) = (args.iter().find(|(from, _)| *from == pre), args.iter().find(|(from, _)| *from == latch)) else {
+            continue;
+        };
+        let Some((block, pos)) = find_def(func, *next) else {
+            continue;
+        };
+        let step = match &func.blocks[block].insts[pos] {
+            Inst::Bin { op: BinOp::Add, lhs: Operand::Reg(v), rhs: Operand::Imm(step), .. }
+            | Inst::Bin { op: BinOp::Add, lhs: Operand::Imm(step), rhs: Operand::Reg(v), .. } if v == dst => *step,
+            Inst::Bin { op: BinOp::Sub, lhs: Operand::Reg(v), rhs: Operand::Imm(step), .. } if v == dst => ty.wrap(-(*step as i128)),
+            _ => continue,
+        };
+        if lp.contains(block) && args.len() == 2 {
+            ivs.insert(*dst, Iv { ty: *ty, init: *init, step, next: *next, reduced: false });
+        }
+    }
+    ivs
+}
+
+fn reduce(func: &mut Function, lp: &Loop, pre: BlockId, latch: BlockId) {
+    let mut ivs = basic_ivs(func, lp, pre, latch);
+    let mut def_block = def_blocks(func);
+    while let Some((block, pos, from)) = candidate(func, lp, &ivs, &def_block) {
+        let Some(dst) = func.blocks[block].insts[pos].dst() else {
+            unreachable!();
+        };
+        let iv = ivs[&from];
+        let ty = func.vregs[dst];
+        // the instruction, and a widening in front of it, computed from the
+        // value entering the loop in the preheader
+        let mut init = iv.init;
+        let mut step = iv.step;
+        let inst = func.blocks[block].insts.remove(pos);
+        let Inst::Bin { op, lhs, rhs, .. } = inst else {
+            unreachable!();
+        };
+        let steps = |operand: Operand| matches!(operand, Operand::Reg(v) if v == from || is_widening(func, v, from));
+        let (value, other) = if steps(lhs) { (lhs, rhs) } else { (rhs, lhs) };
+        if value != Operand::Reg(from) {
+            init = match init {
+                Operand::Imm(init) => Operand::Imm(fold_cast(CastKind::Sext, iv.ty, ty, init)),
+                Operand::Reg(_) => {
+                    let wide = func.new_vreg(ty);
+                    func.blocks[pre].insts.push(Inst::Cast { dst: wide, kind: CastKind::Sext, from: iv.ty, to: ty, src: init });
+                    Operand::Reg(wide)
+                }
+            };
+        }
+        // add and mul can't trap
+        let start = match (init, other) {
+            (Operand::Imm(init), Operand::Imm(other)) => Operand::Imm(fold_bin(op, ty, init, other).unwrap()),
+            (Operand::Imm(0), other) if op == BinOp::Add => other,
+            _ => {
+                let start = func.new_vreg(ty);
+                func.blocks[pre].insts.push(Inst::Bin { dst: start, op, ty, lhs: init, rhs: other });
+                Operand::Reg(start)
+            }
+        };
+        if let (BinOp::Mul, Operand::Imm(factor)) = (op, other) {
+            step = ty.wrap(step as i128 * factor as i128);
+        }
+        // stepped right after the variable it follows
+        let phi = func.new_vreg(ty);
+        let next = func.new_vreg(ty);
+        let (next_block, next_pos) = find_def(func, iv.next).unwrap();
+        func.blocks[next_block].insts.insert(next_pos + 1, Inst::Bin { dst: next, op: BinOp::Add, ty, lhs: Operand::Reg(phi), rhs: Operand::Imm(step) });
+        func.blocks[lp.header].insts.insert(0, Inst::Phi { dst: phi, ty, args: vec![(pre, start), (latch, Operand::Reg(next))] });
+        for inst in func.blocks.iter_mut().flat_map(|block| block.insts.iter_mut()) {
+            rename(inst.operands_mut(), dst, phi);
+        }
+        for block in func.blocks.iter_mut() {
+            rename(block.term.operands_mut(), dst, phi);
+        }
+        func.spans.remove(&dst);
+        ivs.insert(phi, Iv { ty, init: start, step, next, reduced: true });
+        def_block = def_blocks(func);
+    }
+}
+
+fn rename(operands: Vec<&mut Operand>, from: VReg, to: VReg) {
+    for operand in operands {
+        if *operand == Operand::Reg(from) {
+            *operand = Operand::Reg(to);
+        }
+    }
+}
+
+// v is the induction variable iv sign extended
+fn is_widening(func: &Function, v: VReg, iv: VReg) -> bool {
+    let Some((block, pos)) = find_def(func, v) else {
+        return false;
+    };
+    matches!(func.blocks[block].insts[pos], Inst::Cast { kind: CastKind::Sext, src: Operand::Reg(src), .. } if src == iv)
+}
+
+// an instruction of the loop that steps with an induction variable and is
+// worth a phi of its own, with the variable
+fn candidate(func: &Function, lp: &Loop, ivs: &HashMap<VReg, Iv>, def_block: &[Option<BlockId>]) -> Option<(BlockId, usize, VReg)> {
+    // the variable an operand is, directly or widened
+    let iv_of = |operand: Operand, ty: Ty| -> Option<VReg> {
+        let Operand::Reg(v) = operand else {
+            return None;
+        };
+        if ivs.get(&v).is_some_and(|iv| iv.ty == ty) {
+            return Some(v);
+        }
+        let (block, pos) = find_def(func, v)?;
+        match func.blocks[block].insts[pos] {
+            Inst::Cast { kind: CastKind::Sext, to, src: Operand::Reg(src), .. } if to == ty && ivs.contains_key(&src) => Some(src),
+            _ => None,
+        }
+    };
+    for block in reverse_postorder(func).into_iter().filter(|block| lp.contains(*block)) {
+        for (pos, inst) in func.blocks[block].insts.iter().enumerate() {
+            let Inst::Bin { dst, op, ty, lhs, rhs } = inst else {
+                continue;
+            };
+            // the steps are what the variables become
+            if ivs.values().any(|iv| iv.next == *dst) {
+                continue;
+            }
+            let found = match op {
+                BinOp::Mul => match (lhs, rhs) {
+                    (_, Operand::Imm(_)) => iv_of(*lhs, *ty),
+                    (Operand::Imm(_), _) => iv_of(*rhs, *ty),
+                    _ => None,
+                },
+                // only a reduced variable, adding to a variable of the
+                // source keeps both alive
+                BinOp::Add => [(*lhs, *rhs), (*rhs, *lhs)].into_iter().find_map(|(iv, other)| match iv {
+                    Operand::Reg(v) if ivs.get(&v).is_some_and(|iv| iv.reduced && iv.ty == *ty) && invariant(lp, def_block, other) => Some(v),
+                    _ => None,
+                }),
+                _ => None,
+            };
+            if let Some(iv) = found {
+                return Some((block, pos, iv));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_index_walks_a_pointer() {
+        // arr[i] = i for i from 0 to 10, the address is %0 + sext(i) * 4
+        let blocks = vec![
+            Block { insts: vec![Inst::SlotAddr { dst: 0, slot: 0 }], term: Terminator::Jump(1) },
+            Block {
+                insts: vec![
+                    Inst::Phi { dst: 1, ty: Ty::I32, args: vec![(0, Operand::Imm(0)), (2, Operand::Reg(6))] },
+                    Inst::Cmp { dst: 2, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(10) },
+                ],
+                term: Terminator::Branch { cond: Operand::Reg(2), then: 2, other: 3 },
+            },
+            Block {
+                insts: vec![
+                    Inst::Cast { dst: 3, kind: CastKind::Sext, from: Ty::I32, to: Ty::I64, src: Operand::Reg(1) },
+                    Inst::Bin { dst: 4, op: BinOp::Mul, ty: Ty::I64, lhs: Operand::Reg(3), rhs: Operand::Imm(4) },
+                    Inst::Bin { dst: 5, op: BinOp::Add, ty: Ty::I64, lhs: Operand::Reg(0), rhs: Operand::Reg(4) },
+                    Inst::Store { ty: Ty::I32, ptr: Operand::Reg(5), src: Operand::Reg(1) },
+                    Inst::Bin { dst: 6, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(1), rhs: Operand::Imm(1) },
+                ],
+                term: Terminator::Jump(1),
+            },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Imm(0)))) },
+        ];
+        let mut module = test_module(&[Ty::I64, Ty::I32, Ty::I8, Ty::I64, Ty::I64, Ty::I64, Ty::I32], 0, &[40], blocks);
+        run(&mut module);
+        verify(&module).unwrap();
+        let func = &module.functions[0];
+        assert!(func.blocks[2].insts.iter().all(|inst| !matches!(inst, Inst::Bin { op: BinOp::Mul, .. })));
+        let Some(Inst::Store { ptr: Operand::Reg(ptr), .. }) = func.blocks[2].insts.iter().find(|inst| matches!(inst, Inst::Store { .. })) else {
+            panic!("store is gone");
+        };
+        // a phi starting at the array and going up by 4
+        let Some(Inst::Phi { args, .. }) = func.blocks[1].insts.iter().find(|inst| inst.dst() == Some(*ptr)) else {
+            panic!("%{} is not a phi of the header", ptr);
+        };
+        assert!(args.contains(&(0, Operand::Reg(0))));
+        let Some((_, Operand::Reg(next))) = args.iter().find(|(from, _)| *from == 2) else {
+            panic!("no value from the latch");
+        };
+        let step = func.blocks[2].insts.iter().find(|inst| inst.dst() == Some(*next));
+        assert!(matches!(step, Some(Inst::Bin { op: BinOp::Add, lhs: Operand::Reg(v), rhs: Operand::Imm(4), .. }) if v == ptr));
+    }
+}
@@ -0,0 +1,273 @@
+//! Unrolling of small loops.
+//!
+//! A loop whose exit test is at the end of its header and whose body is at
+//! most one more block runs a number of times known before it starts when
+//! the test only depends on constants: the header and body are run on them
+//! here until the test fails. When that is only a few times, and the copies
+//! stay small, the loop becomes one block holding a copy of the header and
+//! the body for every iteration and the header once more for the test that
+//! ends it. Constant propagation folds what the copies compute afterwards.
+
+use std::collections::HashMap;
+
+use super::*;
+use super::constprop::{fold_bin, fold_cast, fold_cmp};
+use super::loops;
+
+// iterations a loop may run to be unrolled
+const TRIPS: usize = 16;
+// instructions and terminators of all copies together
+const SIZE: usize = 64;
+
+// a loop of the shape this pass takes
+struct Unrollable {
+    header: BlockId,
+    // the block after the header, None when the header jumps back itself
+    body: Option<BlockId>,
+    pre: BlockId,
+    latch: BlockId,
+    exit: BlockId,
+    cond: Operand,
+    // the branch stays in the loop when cond is true
+    stays_on_true: bool,
+}
+
+pub(crate) fn run(module: &mut Module) {
+    for func in module.functions.iter_mut() {
+        if !loops::is_ssa(func) {
+            continue;
+        }
+        // every unrolled loop changes the blocks, the loops are found again
+        'unroll: loop {
+            for (lp, pre) in loops::prepare(func) {
+                let Some(shape) = shape(func, &lp, pre) else {
+                    continue;
+                };
+                if let Some(trips) = trips(func, &shape) {
+                    unroll(func, &shape, trips);
+                    continue 'unroll;
+                }
+            }
+            break;
+        }
+    }
+}
+
+fn shape(func: &Function, lp: &loops::Loop, pre: BlockId) -> Option<Unrollable> {
+    let header = lp.header;
+    let Terminator::Branch { cond, then, other } = func.blocks[header].term else {
+        return None;
+    };
+    let stays_on_true = lp.contains(then);
+    let (stay, exit) = if stays_on_true { (then, other) } else { (other, then) };
+    if lp.contains(exit) {
+        return None;
+    }
+    let body = match lp.blocks[..] {
+        [_] => None,
+        [_, body] if body == stay && matches!(func.blocks[body].term, Terminator::Jump(_)) => Some(body),
+        _ => return None,
+    };
+    let asm = lp.blocks.iter()
+        .flat_map(|block| func.blocks[*block].insts.iter())
+        .any(|inst| matches!(inst, Inst::Asm { .. }));
+    if asm {
+        // the labels it defines can't be there twice
+        return None;
+    }
+    Some(Unrollable { header, body, pre, latch: body.unwrap_or(header), exit, cond, stays_on_true })
+}
+
+fn phis(func: &Function, header: BlockId) -> Vec<(VReg, Vec<(BlockId, Operand)>)> {
+    func.blocks[header].insts.iter()
+        .map_while(|inst| match inst {
+            Inst::Phi { dst, args, .. } => Some((*dst, args.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn arg_from(args: &[(BlockId, Operand)], block: BlockId) -> Operand {
+    args.iter().find(|(from, _)| *from == block).map(|(_, arg)| *arg).expect("phi without a value for a predecessor")
+}
+
+// the registers computed from known ones, the others are forgotten since
+// they are defined again on the next iteration
+fn eval(insts: &[Inst], values: &mut HashMap<VReg, i64>) {
+    for inst in insts {
+        let get = |operand: Operand| match operand {
+            Operand::Reg(v) => values.get(&v).copied(),
+            Operand::Imm(value) => Some(value),
+        };
+        let value = match inst {
+            Inst::Copy { src, .. } => get(*src),
+            Inst::Bin { op, ty, lhs, rhs, .. } => get(*lhs).zip(get(*rhs)).and_then(|(a, b)| fold_bin(*op, *ty, a, b)),
+            Inst::Neg { ty, src, .. } => get(*src).map(|a| ty.wrap(-(a as i128))),
+            Inst::Cmp { cond, ty, lhs, rhs, .. } => get(*lhs).zip(get(*rhs)).map(|(a, b)| fold_cmp(*cond, *ty, a, b)),
+            Inst::Cast { kind, from, to, src, .. } => get(*src).map(|a| fold_cast(*kind, *from, *to, a)),
+            _ => None,
+        };
+        if let Some(dst) = inst.dst() {
+            match value {
+                Some(value) => values.insert(dst, value),
+                None => values.remove(&dst),
+            };
+        }
+    }
+}
+
+// how many times the loop runs, when it is known and small enough
+fn trips(func: &Function, shape: &Unrollable) -> Option<usize> {
+    let phis = phis(func, shape.header);
+    let header = &func.blocks[shape.header].insts[phis.len()..];
+    let body: &[Inst] = shape.body.map_or(&[], |body| &func.blocks[body].insts);
+    let mut values: HashMap<VReg, i64> = HashMap::new();
+    for (dst, args) in phis.iter() {
+        if let Operand::Imm(value) = arg_from(args, shape.pre) {
+            values.insert(*dst, value);
+        }
+    }
+    for trips in 0..=TRIPS {
+        eval(header, &mut values);
+        let cond = match shape.cond {
+            Operand::Reg(v) => *values.get(&v)?,
+            Operand::Imm(value) => value,
+        };
+        if (cond != 0) != shape.stays_on_true {
+            let size = (header.len() + 1) * (trips + 1) + body.len() * trips;
+            return (size <= SIZE).then_some(trips);
+        }
+        eval(body, &mut values);
+        let next: Vec<(VReg, Option<i64>)> = phis.iter()
+            .map(|(dst, args)| match arg_from(args, shape.latch) {
+                Operand::Reg(v) => (*dst, values.get(&v).copied()),
+                Operand::Imm(value) => (*dst, Some(value)),
+            })
+            .collect();
+        for (dst, value) in next {
+            match value {
+                Some(value) => values.insert(dst, value),
+                None => values.remove(&dst),
+            };
+        }
+    }
+    None
+}
+
+// appends a copy of insts with registers of its own to out
+fn copy(func: &mut Function, insts: &[Inst], map: &mut HashMap<VReg, Operand>, out: &mut Vec<Inst>) {
+    for inst in insts {
+        let mut inst = inst.clone();
+        for operand in inst.operands_mut() {
+            if let Operand::Reg(v) = operand
+                && let Some(value) = map.get(v) {
+                *operand = *value;
+            }
+        }
+        if let Some(dst) = inst.dst_mut() {
+            let old = *dst;
+            *dst = func.new_vreg(func.vregs[old]);
+            if let Some(span) = func.spans.get(&old).cloned() {
+                func.spans.insert(*dst, span);
+            }
+            map.insert(old, Operand::Reg(*dst));
+        }
+        out.push(inst);
+    }
+}
+
+fn unroll(func: &mut Function, shape: &Unrollable, trips: usize) {
+    let phis = phis(func, shape.header);
+    let header: Vec<Inst> = func.blocks[shape.header].insts.split_off(phis.len());
+    let body: Vec<Inst> = shape.body.map_or(Vec::new(), |body| std::mem::take(&mut func.blocks[body].insts));
+    let mut map: HashMap<VReg, Operand> = phis.iter().map(|(dst, args)| (*dst, arg_from(args, shape.pre))).collect();
+    let mut insts = Vec::new();
+    for trip in 0..=trips {
+        copy(func, &header, &mut map, &mut insts);
+        if trip == trips {
+            break;
+        }
+        copy(func, &body, &mut map, &mut insts);
+        let next: Vec<(VReg, Operand)> = phis.iter()
+            .map(|(dst, args)| match arg_from(args, shape.latch) {
+                Operand::Reg(v) => (*dst, map.get(&v).copied().unwrap_or(Operand::Reg(v))),
+                imm => (*dst, imm),
+            })
+            .collect();
+        map.extend(next);
+    }
+    func.blocks[shape.header] = Block { insts, term: Terminator::Jump(shape.exit) };
+    if let Some(body) = shape.body {
+        func.blocks[body].term = Terminator::Unreachable;
+    }
+    // what the header computed is used after the loop, as the last copy
+    for (index, block) in func.blocks.iter_mut().enumerate() {
+        if index == shape.header {
+            continue;
+        }
+        let operands = block.insts.iter_mut()
+            .flat_map(|inst| inst.operands_mut())
+            .chain(block.term.operands_mut());
+        for operand in operands {
+            if let Operand::Reg(v) = operand
+                && let Some(value) = map.get(v) {
+                *operand = *value;
+            }
+        }
+    }
+    for (dst, _) in phis {
+        func.spans.remove(&dst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a counter from start while it is below end
+    fn counting(start: i64, end: i64) -> Module {
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Jump(1) },
+            Block {
+                insts: vec![
+                    Inst::Phi { dst: 0, ty: Ty::I32, args: vec![(0, Operand::Imm(start)), (2, Operand::Reg(2))] },
+                    Inst::Cmp { dst: 1, cond: Cond::Slt, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Imm(end) },
+                ],
+                term: Terminator::Branch { cond: Operand::Reg(1), then: 2, other: 3 },
+            },
+            Block {
+                insts: vec![Inst::Bin { dst: 2, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Reg(0), rhs: Operand::Imm(1) }],
+                term: Terminator::Jump(1),
+            },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Reg(0)))) },
+        ];
+        test_module(&[Ty::I32, Ty::I8, Ty::I32], 0, &[], blocks)
+    }
+
+    fn trip_count(module: &Module) -> Option<usize> {
+        let func = &module.functions[0];
+        let lp = loops::find(func).remove(0);
+        let shape = shape(func, &lp, 0).expect("loop of the wrong shape");
+        trips(func, &shape)
+    }
+
+    #[test]
+    fn counts_trips() {
+        assert_eq!(trip_count(&counting(0, 3)), Some(3));
+        assert_eq!(trip_count(&counting(5, 5)), Some(0));
+        assert_eq!(trip_count(&counting(0, TRIPS as i64 + 1)), None);
+    }
+
+    #[test]
+    fn unrolls_loop_running_zero_times() {
+        let mut module = counting(5, 5);
+        run(&mut module);
+        verify(&module).unwrap();
+        let func = &module.functions[0];
+        // the test that ends it is all that is left
+        assert!(matches!(func.blocks[1].insts[..], [Inst::Cmp { lhs: Operand::Imm(5), .. }]));
+        assert!(matches!(func.blocks[1].term, Terminator::Jump(3)));
+        assert!(matches!(func.blocks[2].term, Terminator::Unreachable));
+        assert!(matches!(func.blocks[3].term, Terminator::Ret(Some((Ty::I32, Operand::Imm(5))))));
+    }
+}
@@ -0,0 +1,326 @@
+//! IR verifier.
+//!
+//! Checks what the emitter and every pass rely on: targets and slots exist,
+//! every register that is used is defined and always used with the type it
+//! was made with, immediates are wrapped to their type and calls match the
+//! signature of the function they call. While every register is defined
+//! once, its definition also has to dominate its uses.
+
+use super::*;
+use super::dom::DomTree;
+use super::loops;
+
+struct Verifier<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    errors: Vec<String>,
+    // registers with a definition, params count as defined at the entry
+    defined: Vec<bool>,
+}
+
+pub(crate) fn verify(module: &Module) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for func in module.functions.iter() {
+        let mut verifier = Verifier { module, func, errors: Vec::new(), defined: vec![false; func.vregs.len()] };
+        verifier.function();
+        errors.append(&mut verifier.errors);
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, block: BlockId, msg: String) {
+        self.errors.push(format!("{}: bb{}: {}", self.func.name, block, msg));
+    }
+
+    fn function(&mut self) {
+        if self.func.blocks.is_empty() {
+            self.errors.push(format!("{}: no entry block", self.func.name));
+            return;
+        }
+        for (index, parent) in self.func.scopes.iter().enumerate() {
+            if matches!(parent, Some(parent) if *parent >= index) {
+                self.errors.push(format!("{}: scope {} is not nested in an earlier one", self.func.name, index));
+            }
+        }
+        for (index, slot) in self.func.slots.iter().enumerate() {
+            if slot.scope >= self.func.scopes.len() {
+                self.errors.push(format!("{}: slot ${} is in unkown scope {}", self.func.name, index, slot.scope));
+            }
+        }
+        for param in self.func.params.iter() {
+            self.def(0, *param);
+        }
+        // a register only has to be defined somewhere, the order the
+        // blocks run in is up to the passes that move code
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            for inst in block.insts.iter() {
+                if let Some(dst) = inst.dst() {
+                    self.def(index, dst);
+                }
+            }
+        }
+        let preds = self.func.predecessors();
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            let mut body = false;
+            for inst in block.insts.iter() {
+                match inst {
+                    Inst::Phi { args, .. } => {
+                        if body {
+                            self.error(index, format!("phi after the start of the block: {}", inst));
+                        }
+                        let mut from: Vec<BlockId> = args.iter().map(|(block, _)| *block).collect();
+                        from.sort();
+                        let mut expected = preds[index].clone();
+                        expected.sort();
+                        if from != expected {
+                            self.error(index, format!("phi does not have one value for every predecessor: {}", inst));
+                        }
+                    }
+                    _ => body = true,
+                }
+                self.inst(index, inst);
+            }
+            self.term(index, &block.term);
+        }
+        // out of SSA form a register is set on every path to its uses
+        if loops::is_ssa(self.func) {
+            self.dominance();
+        }
+    }
+
+    fn dominance(&mut self) {
+        let dom = DomTree::new(self.func);
+        // block and position of every definition, None for the params
+        let mut def: Vec<Option<(BlockId, usize)>> = vec![None; self.func.vregs.len()];
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            for (pos, inst) in block.insts.iter().enumerate() {
+                if let Some(dst) = inst.dst().filter(|dst| *dst < def.len()) {
+                    def[dst] = Some((index, pos));
+                }
+            }
+        }
+        for (index, block) in self.func.blocks.iter().enumerate() {
+            // where every operand is used, a phi uses its args at the end of
+            // the predecessor they come from
+            let mut uses: Vec<(BlockId, usize, Operand)> = Vec::new();
+            for (pos, inst) in block.insts.iter().enumerate() {
+                match inst {
+                    Inst::Phi { args, .. } => uses.extend(args.iter().map(|(from, arg)| (*from, usize::MAX, *arg))),
+                    _ => uses.extend(inst.operands().into_iter().map(|operand| (index, pos, operand))),
+                }
+            }
+            uses.extend(block.term.operands().into_iter().map(|operand| (index, block.insts.len(), operand)));
+            for (at, pos, operand) in uses {
+                let Some((def_block, def_pos)) = operand.vreg().and_then(|v| def.get(v).copied().flatten()) else {
+                    continue;
+                };
+                // nothing reaches unreachable code
+                if !matches!(dom.idom.get(at), Some(Some(_))) {
+                    continue;
+                }
+                let dominated = if def_block == at { def_pos < pos } else { dom.dominates(def_block, at) };
+                if !dominated {
+                    self.error(index, format!("{} is used in bb{} where its definition in bb{} doesn't dominate it", operand, at, def_block));
+                }
+            }
+        }
+    }
+
+    fn def(&mut self, block: BlockId, vreg: VReg) {
+        if vreg >= self.func.vregs.len() {
+            self.error(block, format!("%{} is out of range", vreg));
+            return;
+        }
+        self.defined[vreg] = true;
+    }
+
+    // operand used as a value of type ty
+    fn use_operand(&mut self, block: BlockId, operand: Operand, ty: Ty) {
+        match operand {
+            Operand::Reg(v) if v >= self.func.vregs.len() => self.error(block, format!("%{} is out of range", v)),
+            Operand::Reg(v) => {
+                if !self.defined[v] {
+                    self.error(block, format!("%{} is used but never defined", v));
+                }
+                if self.func.vregs[v] != ty {
+                    self.error(block, format!("%{} is {} but used as {}", v, self.func.vregs[v], ty));
+                }
+            }
+            Operand::Imm(value) => {
+                if ty.wrap(value as i128) != value {
+                    self.error(block, format!("{} does not fit in {}", value, ty));
+                }
+            }
+        }
+    }
+
+    fn dst(&mut self, block: BlockId, dst: VReg, ty: Ty) {
+        if dst < self.func.vregs.len() && self.func.vregs[dst] != ty {
+            self.error(block, format!("%{} is {} but gets a {}", dst, self.func.vregs[dst], ty));
+        }
+    }
+
+    fn slot(&mut self, block: BlockId, slot: SlotId, offset: u32, ty: Ty) {
+        match self.func.slots.get(slot) {
+            None => self.error(block, format!("unkown slot ${}", slot)),
+            Some(data) if offset + ty.size() > data.size => {
+                let size = data.size;
+                self.error(block, format!("{} at ${}+{} is past the {} bytes of the slot", ty, slot, offset, size));
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn target(&mut self, block: BlockId, target: BlockId) {
+        if target >= self.func.blocks.len() {
+            self.error(block, format!("jump to unkown bb{}", target));
+        }
+    }
+
+    fn inst(&mut self, block: BlockId, inst: &Inst) {
+        match inst {
+            Inst::Phi { dst, ty, args } => {
+                for (_, arg) in args.iter() {
+                    self.use_operand(block, *arg, *ty);
+                }
+                self.dst(block, *dst, *ty);
+            }
+            Inst::Copy { dst, ty, src } | Inst::Neg { dst, ty, src } => {
+                self.use_operand(block, *src, *ty);
+                self.dst(block, *dst, *ty);
+            }
+            Inst::Bin { dst, ty, lhs, rhs, .. } => {
+                self.use_operand(block, *lhs, *ty);
+                self.use_operand(block, *rhs, *ty);
+                self.dst(block, *dst, *ty);
+            }
+            Inst::Cmp { dst, ty, lhs, rhs, .. } => {
+                self.use_operand(block, *lhs, *ty);
+                self.use_operand(block, *rhs, *ty);
+                self.dst(block, *dst, Ty::I8);
+            }
+            Inst::Cast { dst, kind, from, to, src } => {
+                let fine = match kind {
+                    CastKind::Sext | CastKind::Zext => to.size() > from.size(),
+                    CastKind::Trunc => to.size() < from.size(),
+                };
+                if !fine {
+                    self.error(block, format!("{} from {} to {}", kind, from, to));
+                }
+                self.use_operand(block, *src, *from);
+                self.dst(block, *dst, *to);
+            }
+            Inst::SlotAddr { dst, slot } => {
+                if *slot >= self.func.slots.len() {
+                    self.error(block, format!("unkown slot ${}", slot));
+                }
+                self.dst(block, *dst, Ty::I64);
+            }
+            Inst::StrAddr { dst, index } => {
+                if *index >= self.module.strings.len() {
+                    self.error(block, format!("unkown string {}", index));
+                }
+                self.dst(block, *dst, Ty::I64);
+            }
+            Inst::LoadSlot { dst, ty, slot, offset } => {
+                self.slot(block, *slot, *offset, *ty);
+                self.dst(block, *dst, *ty);
+            }
+            Inst::StoreSlot { ty, slot, offset, src } => {
+                self.slot(block, *slot, *offset, *ty);
+                self.use_operand(block, *src, *ty);
+            }
+            Inst::Load { dst, ty, ptr } => {
+                self.use_operand(block, *ptr, Ty::I64);
+                self.dst(block, *dst, *ty);
+            }
+            Inst::Store { ty, ptr, src } => {
+                self.use_operand(block, *ptr, Ty::I64);
+                self.use_operand(block, *src, *ty);
+            }
+            Inst::Call { dst, callee, args } => {
+                let Some(sig) = self.module.signatures.get(callee) else {
+                    self.error(block, format!("call to unkown function {}", callee));
+                    return;
+                };
+                if args.len() < sig.params.len() || (!sig.variadic && args.len() > sig.params.len()) {
+                    self.error(block, format!("{} takes {} args but gets {}", callee, sig.params.len(), args.len()));
+                }
+                let params = sig.params.clone();
+                let ret = sig.ret;
+                for (index, (ty, arg)) in args.iter().enumerate() {
+                    if matches!(params.get(index), Some(param) if param != ty) {
+                        self.error(block, format!("arg {} of {} is {} but passed as {}", index, callee, params[index], ty));
+                    }
+                    self.use_operand(block, *arg, *ty);
+                }
+                match (dst, ret) {
+                    (Some(dst), Some(ret)) => self.dst(block, *dst, ret),
+                    (Some(_), None) => self.error(block, format!("{} returns nothing", callee)),
+                    _ => {}
+                }
+            }
+            Inst::Asm { vars, .. } => {
+                for (name, slot) in vars.iter() {
+                    if *slot >= self.func.slots.len() {
+                        self.error(block, format!("asm var {} is in unkown slot ${}", name, slot));
+                    }
+                }
+            }
+        }
+    }
+
+    fn term(&mut self, block: BlockId, term: &Terminator) {
+        for target in term.successors() {
+            self.target(block, target);
+        }
+        match term {
+            Terminator::Branch { cond, .. } => self.use_operand(block, *cond, Ty::I8),
+            Terminator::Switch { ty, value, cases, .. } => {
+                self.use_operand(block, *value, *ty);
+                for (index, (case, _)) in cases.iter().enumerate() {
+                    if ty.wrap(*case as i128) != *case {
+                        self.error(block, format!("case {} does not fit in {}", case, ty));
+                    }
+                    if cases[..index].iter().any(|(other, _)| other == case) {
+                        self.error(block, format!("case {} is there twice", case));
+                    }
+                }
+            }
+            Terminator::Ret(value) => match (value, self.func.ret) {
+                (Some((ty, value)), Some(ret)) => {
+                    if *ty != ret {
+                        self.error(block, format!("returns {} from a function returning {}", ty, ret));
+                    }
+                    self.use_operand(block, *value, *ty);
+                }
+                (None, None) => {}
+                (Some(_), None) => self.error(block, "returns a value from a function returning nothing".to_string()),
+                (None, Some(ret)) => self.error(block, format!("returns nothing from a function returning {}", ret)),
+            },
+            Terminator::Jump(_) | Terminator::Unreachable => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn use_not_dominated_by_definition() {
+        // %1 is only set on one of the paths to bb3
+        let blocks = vec![
+            Block { insts: Vec::new(), term: Terminator::Branch { cond: Operand::Reg(0), then: 1, other: 2 } },
+            Block {
+                insts: vec![Inst::Bin { dst: 1, op: BinOp::Add, ty: Ty::I32, lhs: Operand::Imm(1), rhs: Operand::Imm(2) }],
+                term: Terminator::Jump(3),
+            },
+            Block { insts: Vec::new(), term: Terminator::Jump(3) },
+            Block { insts: Vec::new(), term: Terminator::Ret(Some((Ty::I32, Operand::Reg(1)))) },
+        ];
+        let module = test_module(&[Ty::I8, Ty::I32], 1, &[], blocks);
+        assert_eq!(verify(&module).unwrap_err(), vec!["t: bb3: %1 is used in bb3 where its definition in bb1 doesn't dominate it"]);
+    }
+}
@@ -3,6 +3,7 @@
 use super::*;
 
 use crate::Ir::expr::*;
+use crate::Ir::stmt::TypeInfo;
 
 impl Parser {
 
@@ -10,20 +11,28 @@ impl Parser {
     pub fn eval_expr(&mut self) -> Vec<RpnExpr> {
         let mut output: Vec<RpnExpr> = Vec::new();
         let mut op_stack: Vec<Token> = Vec::new();
+        // the type token of a cast stands in for it on op_stack
+        let mut casts: Vec<Cast> = Vec::new();
         let mut previous_token: Option<Token> = None;
         while !matches!(
             self.peek(0).token,
-            TokenType::Semi | TokenType::OpenScope | TokenType::Coma
+            TokenType::Semi | TokenType::OpenScope | TokenType::Coma | TokenType::Colon
         ) {
-            if self.peek(0).token == TokenType::CloseParen && self.peek(1).token == TokenType::Semi {
+            // a ')' without its '(' in this expr closes a call or a for header
+            if self.peek(0).token == TokenType::CloseParen
+            && !op_stack.iter().any(|op| op.token == TokenType::OpenParen) {
                 break;
             }
             let token = self.consume();
             let token_copy = token.clone();
             
             match token.token {
-                TokenType::Num | TokenType::CharValue => {
-                    output.push(RpnExpr::PushNum(PushNum { data: token }));
+                TokenType::Num | TokenType::CharValue | TokenType::True | TokenType::False => {
+                    output.push(RpnExpr::PushNum(PushNum { data: token, ty: None }));
+                }
+
+                TokenType::String => {
+                    output.push(RpnExpr::PushStr(PushStr { data: token, ty: None }));
                 }
                 
                 
@@ -34,22 +43,29 @@ impl Parser {
                         self.consume();
                         let strcut_var = self.consume();
                         let res = GetStructValue {
-                            var_name: token.value.unwrap(),
+                            var_name: token,
                             struct_value_name: strcut_var.value.unwrap(),
+                            sym: None,
+                            ty: None,
                         };
                         output.push(RpnExpr::GetStructValue(res));
+                        previous_token = Some(token_copy);
                         continue;
                         
                     }
                     
                     if token.value.as_deref() == Some("sizeof") {
-                        self.consume();
-                        let var: Token = self.consume();
-                        self.consume();
-                        let res = GetSizeOf {
-                            var,
+                        let paren = self.consume();
+                        let res = if Parser::starts_type(self.peek(0)) {
+                            let of_type = self.parse_type_name();
+                            GetSizeOf { var: paren, of_type: Some(of_type), sym: None, ty: None }
+                        } else {
+                            let var: Token = self.consume();
+                            self.consume();
+                            GetSizeOf { var, of_type: None, sym: None, ty: None }
                         };
                         output.push(RpnExpr::GetSizeOf(res));
+                        previous_token = Some(token_copy);
                         continue;
                     }
                     
@@ -64,10 +80,13 @@ impl Parser {
                         let get_array_value = GetArrayValue {
                             name: token,
                             index,
+                            sym: None,
+                            index_sym: None,
+                            ty: None,
                         };
                         output.push(RpnExpr::GetArrayValue(get_array_value));
                     } else {
-                        output.push(RpnExpr::PushVar(PushVar { data: token }));
+                        output.push(RpnExpr::PushVar(PushVar { data: token, sym: None, ty: None }));
                     }
                 }
                 
@@ -76,13 +95,26 @@ impl Parser {
                     let var = self.consume();
                     let res = GetAddr {
                         var,
+                        sym: None,
+                        ty: None,
                     };  
                     output.push(RpnExpr::GetAddr(res));
+                    previous_token = Some(token_copy);
                     continue;
                 }
                 
                 
                 TokenType::OpenParen => {
+                    if Parser::starts_type(self.peek(0)) {
+                        // binds tighter than any operator, so it's popped
+                        // right after the value that follows it
+                        let cast = self.parse_cast(&token);
+                        let marker = Token { token: cast.to.var_type, value: None, span: token.span.clone() };
+                        casts.push(cast);
+                        op_stack.push(marker.clone());
+                        previous_token = Some(marker);
+                        continue;
+                    }
                     op_stack.push(token);
                 }
                 
@@ -92,54 +124,36 @@ impl Parser {
                             break;
                         }
                         let op = op_stack.pop().unwrap();
-                        output.push(RpnExpr::Operator(Operator { data: op }));
+                        output.push(Parser::pop_op(op, &mut casts));
                     }
                     op_stack.pop(); // pop '('
                 }
                 
                 _ if Parser::is_operator(&token) => {
-                    if previous_token.is_some() {
-                        
-                        if Parser::is_operator(&previous_token.clone().unwrap()) && token.token == TokenType::Mul {
-                            let mut stack_depth = 1;
-                            while self.peek(0).token == TokenType::Mul {
-                                stack_depth += 1;
-                                self.consume();
-                            }
-                            let var = self.consume();
-                            let res = Deref {
-                                var,
-                                stack_depth,
-                            };
-                            output.push(RpnExpr::Deref(res));
-                            continue;
-                        }
-                        
-                        if Parser::is_operator(&previous_token.clone().unwrap()) && token.token == TokenType::Sub {
-                            output.push(RpnExpr::Negative(Negative { data: self.consume() }));
-                            continue;
+                    // `*` and `-` where a value has to start are unary
+                    if Parser::expects_operand(&previous_token) && token.token == TokenType::Mul {
+                        let mut stack_depth = 1;
+                        while self.peek(0).token == TokenType::Mul {
+                            stack_depth += 1;
+                            self.consume();
                         }
+                        let var = self.consume();
+                        previous_token = Some(var.clone());
+                        let res = Deref {
+                            var,
+                            stack_depth,
+                            sym: None,
+                            ty: None,
+                        };
+                        output.push(RpnExpr::Deref(res));
+                        continue;
                     }
-                    else {
-                        if token.token == TokenType::Mul {
-                            let mut stack_depth = 1;
-                            while self.peek(0).token == TokenType::Mul {
-                                stack_depth += 1;
-                                self.consume();
-                            }
-                            let var = self.consume();
-                            let res = Deref {
-                                var,
-                                stack_depth,
-                            };
-                            output.push(RpnExpr::Deref(res));
-                            continue;
-                        }
-                        
-                        if token.token == TokenType::Sub {
-                            output.push(RpnExpr::Negative(Negative { data: self.consume() }));
-                            continue;
-                        }
+
+                    if Parser::expects_operand(&previous_token) && token.token == TokenType::Sub {
+                        let data = self.consume();
+                        previous_token = Some(data.clone());
+                        output.push(RpnExpr::Negative(Negative { data, sym: None, ty: None }));
+                        continue;
                     }
                     while let Some(top) = op_stack.last() {
                         if top.token == TokenType::OpenParen {
@@ -150,7 +164,7 @@ impl Parser {
                         <= Parser::bigger_operator(top)
                         {
                             let op = op_stack.pop().unwrap();
-                            output.push(RpnExpr::Operator(Operator { data: op }));
+                            output.push(Parser::pop_op(op, &mut casts));
                         } else {
                             break;
                         }
@@ -164,10 +178,56 @@ impl Parser {
         }
         
         while let Some(op) = op_stack.pop() {
-            output.push(RpnExpr::Operator(Operator { data: op }));
+            output.push(Parser::pop_op(op, &mut casts));
         }
         
         
         output
     }
-}
\ No newline at end of file
+
+    // `(type*)` of a cast, the '(' is already consumed
+    fn parse_cast(&mut self, paren: &Token) -> Cast {
+        Cast {
+            to: self.parse_type_name(),
+            span: paren.span.clone(),
+            from: None,
+            ty: None,
+        }
+    }
+
+    // `type*)` of a cast or a sizeof, consumes the ')'
+    fn parse_type_name(&mut self) -> TypeInfo {
+        let type_token = self.consume_type();
+        let mut struct_name: Option<String> = None;
+        if matches!(type_token.token, TokenType::Struct | TokenType::Enum) {
+            struct_name = self.consume().value;
+        }
+        let mut pointer_depth = 0;
+        while self.peek(0).token == TokenType::Mul {
+            pointer_depth += 1;
+            self.consume();
+        }
+        if self.peek(0).token != TokenType::CloseParen {
+            self.error("excpected ')' after the type of a cast");
+        }
+        self.consume();
+        TypeInfo { var_type: type_token.token, pointer_depth, struct_name }
+    }
+
+    fn pop_op(op: Token, casts: &mut Vec<Cast>) -> RpnExpr {
+        if Parser::starts_type(&op) {
+            return RpnExpr::Cast(casts.pop().expect("cast without its type"));
+        }
+        RpnExpr::Operator(Operator { data: op, ty: None })
+    }
+
+    // whether the next token has to start a value
+    fn expects_operand(previous_token: &Option<Token>) -> bool {
+        match previous_token {
+            None => true,
+            Some(token) => Parser::is_operator(token)
+                || Parser::starts_type(token)
+                || token.token == TokenType::OpenParen,
+        }
+    }
+}
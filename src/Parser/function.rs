@@ -1,39 +1,25 @@
 
 
 use super::*;
-use crate::Ir::stmt::{Arg, FunctionCall, InitFunc, TypeInfo};
+use crate::Ir::stmt::{Arg, FuncDecl, FunctionCall, InitFunc, Inline, TypeInfo};
 
 use crate::Ir::expr::Function;
 
 impl Parser {
 
     pub fn parse_rpn_function(&mut self, name: Token) -> RpnExpr {
-        // consume '('
-        self.consume();
-
-        let mut args = Vec::new();
-
-        while self.peek(0).token != TokenType::CloseParen {
-            let tok = self.consume();
-
-            if tok.token != TokenType::Coma {
-                args.push(tok);
-            }
-        }
-
-        // consume ')'
-        self.consume();
-
+        let call = self.gen_init_func(name);
         RpnExpr::Function(Function {
-            name,
-            args,
+            name: call.name,
+            args: call.args,
+            ty: None,
         })
     }
 
 
     pub fn gen_init_func(&mut self,var: Token) -> FunctionCall {
         if self.peek(0).token != TokenType::OpenParen {
-            panic!("Expected '('");
+            self.error("Expected '('");
         }
         self.consume();
         let mut args: Vec<Vec<RpnExpr>> = Vec::new();
@@ -45,7 +31,7 @@ impl Parser {
             args.push(expr);
         }
         if self.peek(0).token != TokenType::CloseParen {
-            panic!("Expected ')'");
+            self.error("Expected ')'");
         }
         self.consume();
         let func_call = FunctionCall {
@@ -54,19 +40,29 @@ impl Parser {
         };
         func_call
     }
-    pub fn parse_func(&mut self, var_token: Token, type_token: TypeInfo) -> Option<Stmt> {
-        self.consume();
+    // parses the arg list after '(' and consumes the closing ')'
+    // returns the args and whether the list ended with '...'
+    fn parse_func_args(&mut self) -> (Vec<Arg>, bool) {
         let mut args: Vec<Arg> = Vec::new();
-        let mut pointer_depth = 0;
+        let mut variadic = false;
         while self.peek(0).token != TokenType::CloseParen {
-            let arg_type = self.consume();
+            if self.peek(0).token == TokenType::Ellipsis {
+                self.consume();
+                variadic = true;
+                if self.peek(0).token != TokenType::CloseParen {
+                    self.error("'...' has to be the last arg");
+                }
+                break;
+            }
+            let arg_type = self.consume_type();
             let mut struct_arg_name: Option<String> = None;
 
-            if arg_type.token == TokenType::Struct {
+            if matches!(arg_type.token, TokenType::Struct | TokenType::Enum) {
                 let struct_name = self.consume();
                 struct_arg_name = Some(struct_name.value.unwrap());
             }
-            
+
+            let mut pointer_depth = 0;
             while self.peek(0).token == TokenType::Mul {
                 pointer_depth += 1;
                 self.consume();
@@ -80,29 +76,74 @@ impl Parser {
                 arg_type,
                 pointer_depth,
                 name: arg_name,
+                sym: None,
             };
             args.push(arg);
-        
+
         }
         self.consume();
-        let mut expr_arr: Vec<Stmt> = Vec::new();
-        let mut depth = 0;
-        self.func_name = var_token.value.clone().unwrap();
-        while self.peek(0).token != TokenType::CloseScope || depth > 0 {
-            let expr: Stmt = self.parse_stmt().unwrap();
-            match &expr {
-                Stmt::ForStmt(_) => depth += 1,
-                Stmt::WhileStmt(_) => depth += 1,
-                Stmt::CloseScope(_) => depth -=1,
-                _ => depth += 0,
-            }
-            expr_arr.push(expr);
+        (args, variadic)
+    }
+
+    // extern int printf(char* fmt, ...);
+    pub fn parse_func_decl(&mut self) -> Option<Stmt> {
+        // consume 'extern'
+        self.consume();
+        let type_token = self.consume_type();
+        let mut struct_name: Option<String> = None;
+        if matches!(type_token.token, TokenType::Struct | TokenType::Enum) {
+            struct_name = self.consume().value;
         }
+        let mut pointer_depth = 0;
+        while self.peek(0).token == TokenType::Mul {
+            pointer_depth += 1;
+            self.consume();
+        }
+        let name = self.consume();
+        if self.peek(0).token != TokenType::OpenParen {
+            self.error(&format!("excpected '(' after extern function: {:?}", name.value));
+        }
+        self.consume();
+        let (args, variadic) = self.parse_func_args();
+        if self.peek(0).token != TokenType::Semi {
+            self.error(&format!("excpected semi colon after extern function: {:?}", name.value));
+        }
+        self.consume();
+        let func_decl = FuncDecl {
+            name,
+            return_type: TypeInfo { var_type: type_token.token, pointer_depth, struct_name },
+            args,
+            variadic,
+        };
+        Some(Stmt::FuncDecl(func_decl))
+    }
+
+    pub fn parse_func(&mut self, var_token: Token, type_token: TypeInfo) -> Option<Stmt> {
+        self.consume();
+        let (args, variadic) = self.parse_func_args();
+        // prototype, int f(int a, long b);
+        if self.peek(0).token == TokenType::Semi {
+            self.consume();
+            let func_decl = FuncDecl {
+                name: var_token,
+                return_type: type_token,
+                args,
+                variadic,
+            };
+            return Some(Stmt::FuncDecl(func_decl));
+        }
+        if variadic {
+            self.error(&format!("only function declarations can take '...': {:?}", var_token.value));
+        }
+        self.func_name = var_token.value.clone().unwrap();
+        let expr_arr = self.parse_block();
         let init_func = InitFunc {
             name: var_token,
             return_type: type_token,
             args,
-            data: expr_arr
+            data: expr_arr,
+            exported: false,
+            inline: Inline::Auto,
         };
 
         return Some(Stmt::InitFunc(init_func));
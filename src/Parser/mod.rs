@@ -43,10 +43,18 @@ impl Parser {
         }
     }
 
+    // panics with the location of the current token
+    fn error(&self, msg: &str) -> ! {
+        match self.m_tokens.first() {
+            Some(token) => panic!("{}: {}", token.span, msg),
+            None => panic!("unexpected end of file: {}", msg),
+        }
+    }
+
     fn peek(&self, offset: usize) -> &Token {
         let pos: usize = self.m_index + offset;
-        if self.m_index >= self.m_tokens.len() {
-            panic!("Trying to parse token more than token array has\nm_index: {}",self.m_tokens.len());
+        if pos >= self.m_tokens.len() {
+            self.error("excpected more tokens");
         }
         &self.m_tokens[pos]
     }
@@ -80,6 +88,8 @@ impl Parser {
             TokenType::More => 1,
             TokenType::Remainder => 2,
             TokenType::MoreThan => 1,
+            // a cast waiting on the op stack
+            _ if Parser::starts_type(token) => 3,
             _ => panic!("Starnge token in bigger_operator"),
         }
     }
@@ -111,17 +121,74 @@ impl Parser {
             TokenType::LongType => true,
             TokenType::ShortType => true,
             TokenType::Void => true,
+            TokenType::Unsigned => true,
+            TokenType::UCharType => true,
+            TokenType::UShortType => true,
+            TokenType::UIntType => true,
+            TokenType::ULongType => true,
+            TokenType::Bool => true,
             _ => false,
         }
     }
 
+    // a type keyword or the `struct`/`enum` before a tag
+    fn starts_type(token: &Token) -> bool {
+        Parser::is_type(token) || matches!(token.token, TokenType::Struct | TokenType::Enum)
+    }
+
+    // consumes a type keyword, `unsigned` and the type after it become one
+    // token, a bare `unsigned` is an unsigned int
+    fn consume_type(&mut self) -> Token {
+        if self.peek(0).token != TokenType::Unsigned {
+            return self.consume();
+        }
+        let mut res = self.consume();
+        res.token = match self.peek(0).token {
+            TokenType::CharType => TokenType::UCharType,
+            TokenType::ShortType => TokenType::UShortType,
+            TokenType::IntType => TokenType::UIntType,
+            TokenType::LongType => TokenType::ULongType,
+            _ => return Token { token: TokenType::UIntType, ..res },
+        };
+        self.consume();
+        res
+    }
+
+    fn expect_stmt(&mut self) -> Stmt {
+        match self.parse_stmt() {
+            Some(stmt) => stmt,
+            None => self.error(&format!("Unexpected token: {:?}", self.peek(0).token)),
+        }
+    }
+
+    // parses `{ ... }`, the braces stay in the result as OpenScope and
+    // CloseScope around the body, blocks nested in it are kept inline
+    fn parse_block(&mut self) -> Vec<Stmt> {
+        if self.peek(0).token != TokenType::OpenScope {
+            self.error("excpected '{'");
+        }
+        let mut res: Vec<Stmt> = Vec::new();
+        let mut depth = 0;
+        loop {
+            let stmt = self.expect_stmt();
+            match stmt {
+                Stmt::OpenScope(_) => depth += 1,
+                Stmt::CloseScope(_) => depth -= 1,
+                _ => {}
+            }
+            res.push(stmt);
+            if depth == 0 {
+                return res;
+            }
+        }
+    }
+
     pub fn parse(&mut self) -> Vec<Stmt> {
         while !self.m_tokens.is_empty() {
             if let Some(stmt) = self.parse_stmt() {
                 self.expressions.push(stmt);
             } else {
-                println!("tokens: {:?}",self.m_tokens);
-                panic!("Unexpected token: {:?} at {}", self.peek(0).token, self.m_index);
+                self.error(&format!("Unexpected token: {:?}", self.peek(0).token));
             }
         }
 
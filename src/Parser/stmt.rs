@@ -2,13 +2,13 @@ use super::*;
 
 use crate::Ir::stmt::*;
 
-use crate::Ir::expr::PushNum;
+use crate::Ir::expr::{Cast, Operator, PushNum, PushVar};
 
 
 impl Parser {
     pub fn parse_stmt(&mut self) -> Option<Stmt> {
         if Parser::is_type(self.peek(0)) {
-            let type_token = self.consume(); 
+            let type_token = self.consume_type();
             let var_token  = self.consume();
 
             // pointer
@@ -20,18 +20,19 @@ impl Parser {
                 }
                 let var_name = self.consume();
                 if self.peek(0).token == TokenType::OpenParen {
-                    return self.parse_func(var_name, TypeInfo { var_type: type_token.token, pointer_depth: stack_depth });
+                    return self.parse_func(var_name, TypeInfo { var_type: type_token.token, pointer_depth: stack_depth, struct_name: None });
                 }
 
                 if self.peek(0).token == TokenType::Eq {
                     let expr = self.eval_expr();
                     if self.peek(0).token != TokenType::Semi {
-                        panic!("excpected semi colon");
+                        self.error("excpected semi colon");
                     }
                     self.consume();
                     let res = CreatePointer {
                         type_: type_token.token,
-                        var: var_name.value.unwrap(),
+                        var: var_name,
+                        sym: None,
                         stmt: expr,
                         pointer_depth: stack_depth,
                     };
@@ -40,11 +41,19 @@ impl Parser {
                 } else if self.peek(0).token == TokenType::Semi {
                     self.consume();
                     let mut res: Vec<RpnExpr> = Vec::new();
-                    let expr = RpnExpr::PushNum(PushNum { data: Token { token: TokenType::Num, value: Some("0xDEADBEEFDEADBEEF".to_string()) } });
+                    let expr = RpnExpr::PushNum(PushNum { data: Token { token: TokenType::Num, value: Some("0xDEADBEEFDEADBEEF".to_string()), span: var_name.span.clone() }, ty: None });
                     res.push(expr);
+                    let cast = Cast {
+                        to: TypeInfo { var_type: type_token.token, pointer_depth: stack_depth, struct_name: None },
+                        span: var_name.span.clone(),
+                        from: None,
+                        ty: None,
+                    };
+                    res.push(RpnExpr::Cast(cast));
                     let some =  CreatePointer { 
-                        type_:TokenType::Num, 
-                        var: var_name.value.unwrap(), 
+                        type_: type_token.token, 
+                        var: var_name, 
+                        sym: None,
                         stmt: res,
                         pointer_depth: stack_depth,
                     };
@@ -74,13 +83,14 @@ impl Parser {
                 }
                 let init_array = InitArray {
                     name: var_token,
+                    sym: None,
                     arr_type: type_token,
                     size: arr_size,
                     data: data,
                 };
 
                 if self.peek(0).token != TokenType::Semi {
-                    panic!("Expected semi colon");
+                    self.error("Expected semi colon");
                 }
                 self.consume();
 
@@ -91,14 +101,16 @@ impl Parser {
 
             if self.peek(0).token == TokenType::Semi {
                 self.consume();
-                let some =  PushNum { data: Token { token: TokenType::Num, value: Some("0".to_string()) } };
+                let some =  PushNum { data: Token { token: TokenType::Num, value: Some("0".to_string()), span: var_token.span.clone() }, ty: None };
                 let expr = RpnExpr::PushNum(some);
                 let mut res: Vec<RpnExpr> = Vec::new();
                 res.push(expr);
 
                 let new_var = CreateVar {
                     Type: type_token.token,
-                    var: var_token.value.clone().unwrap(),
+                    enum_name: None,
+                    var: var_token.clone(),
+                    sym: None,
                     stmt: res,
                 };
                 
@@ -112,12 +124,14 @@ impl Parser {
                 let res: Vec<RpnExpr> = self.eval_expr();
                 
                 if self.peek(0).token != TokenType::Semi {
-                    panic!("Expected semi colon");
+                    self.error("Expected semi colon");
                 }
                 self.consume();
                 let new_var = CreateVar {
                     Type: type_token.token,
-                    var: var_token.value.clone().unwrap(),
+                    enum_name: None,
+                    var: var_token.clone(),
+                    sym: None,
                     stmt: res,
                 };
                 
@@ -127,11 +141,44 @@ impl Parser {
             else if self.peek(0).token == TokenType::OpenParen {
                 // the pointer depth will always be zero because if we had * in return type
                 // it would be in another section
-                return self.parse_func(var_token, TypeInfo { var_type: type_token.token, pointer_depth: 0 });
+                return self.parse_func(var_token, TypeInfo { var_type: type_token.token, pointer_depth: 0, struct_name: None });
 
             }
         }
 
+        if self.peek(0).token == TokenType::Extern {
+            return self.parse_func_decl();
+        }
+
+        if self.peek(0).token == TokenType::Export {
+            self.consume();
+            match self.parse_stmt() {
+                Some(Stmt::InitFunc(mut v)) => {
+                    v.exported = true;
+                    return Some(Stmt::InitFunc(v));
+                }
+                _ => self.error("only functions can be exported"),
+            }
+        }
+
+        if matches!(self.peek(0).token, TokenType::Inline | TokenType::NoInline) {
+            let keyword = self.consume().token;
+            let inline = if keyword == TokenType::Inline { Inline::Hint } else { Inline::Never };
+            if matches!(self.peek(0).token, TokenType::Inline | TokenType::NoInline) && self.peek(0).token != keyword {
+                self.error("a function can't be both inline and noinline");
+            }
+            match self.parse_stmt() {
+                Some(Stmt::InitFunc(mut v)) => {
+                    if v.inline != Inline::Auto && v.inline != inline {
+                        self.error("a function can't be both inline and noinline");
+                    }
+                    v.inline = inline;
+                    return Some(Stmt::InitFunc(v));
+                }
+                _ => self.error("only function definitions can be inline or noinline"),
+            }
+        }
+
         if self.peek(0).token == TokenType::Mul {
             self.consume();
             let mut pointer_depth: u32 = 1;
@@ -144,18 +191,19 @@ impl Parser {
                 self.consume();
                 let expr = self.eval_expr();
                 if self.peek(0).token != TokenType::Semi {
-                    panic!("no semi colon");
+                    self.error("no semi colon");
                 }
                 self.consume();
                 let res = ChangePtrValue {
-                    var: var.value.unwrap(),
+                    var,
+                    sym: None,
                     stmt: expr,
                     pointer_depth,
                 };
                 return Some(Stmt::ChangePtrValue(res));
 
             } else {
-                panic!("strange syntax pointer");
+                self.error("strange syntax pointer");
             }
         }
 
@@ -180,11 +228,12 @@ impl Parser {
                     self.consume();
                     let expr = self.eval_expr();
                     if self.peek(0).token != TokenType::Semi {
-                        panic!("excpected semi colon");
+                        self.error("excpected semi colon");
                     }
                     self.consume();
                     let res = ChangePtrStructValue {
-                        struct_name: var.value.unwrap(),
+                        struct_name: var.clone(),
+                        sym: None,
                         value_name: struct_var.value.unwrap(),
                         expr,
                     };
@@ -201,11 +250,12 @@ impl Parser {
                     self.consume();
                     let expr = self.eval_expr();
                     if self.peek(0).token != TokenType::Semi {
-                        panic!("excpected semi colon");
+                        self.error("excpected semi colon");
                     }
                     self.consume();
                     let res = ChangeStructValue {
-                        struct_name: var.value.unwrap(),
+                        struct_name: var.clone(),
+                        sym: None,
                         value_name: struct_var.value.unwrap(),
                         expr,
                     };
@@ -224,6 +274,7 @@ impl Parser {
                 self.consume();
                 let res = AsmCode {
                     code: asm_code,
+                    vars: HashMap::new(),
                 };
                 return Some(Stmt::AsmCode(res))
             }
@@ -236,12 +287,14 @@ impl Parser {
                     self.consume();
                     let res = self.eval_expr();
                     if self.peek(0).token != TokenType::Semi {
-                        panic!("Expected semi colon");
+                        self.error("Expected semi colon");
                     }
                     self.consume();
                     let change_arr_elemnet = ChangeArrElement {
                         arr_name: var,
+                        sym: None,
                         element,
+                        element_sym: None,
                         expr: res,
                     };
                     return Some(Stmt::ChangeArrElement(change_arr_elemnet));
@@ -252,44 +305,55 @@ impl Parser {
             if self.peek(0).token == TokenType::Eq {
                 self.consume();
                 let res = self.eval_expr();
-                if self.peek(0).token != TokenType::Semi && self.peek(0).token != TokenType::CloseParen {
-                    panic!("Expected semi colon or ')'");
+                if self.peek(0).token != TokenType::Semi {
+                    self.error("Expected semi colon");
+                }
+                self.consume();
+                let change_var = ChangeVar {
+                    stmt: res,
+                    var,
+                    sym: None,
+                };
+                return Some(Stmt::ChangeVar(change_var));
+            }
+            if let Some(op) = Parser::compound_op(self.peek(0).token) {
+                let op_token = self.consume();
+                // the value of the rhs as a whole goes into the operator
+                let mut res = vec![RpnExpr::PushVar(PushVar { data: var.clone(), sym: None, ty: None })];
+                res.extend(self.eval_expr());
+                res.push(RpnExpr::Operator(Operator { data: Token { token: op, value: None, span: op_token.span }, ty: None }));
+                if self.peek(0).token != TokenType::Semi {
+                    self.error("Expected semi colon");
                 }
                 self.consume();
                 let change_var = ChangeVar {
                     stmt: res,
-                    var: var.value.unwrap(),
+                    var,
+                    sym: None,
                 };
                 return Some(Stmt::ChangeVar(change_var));
             }
             if self.peek(0).token == TokenType::Inc {
                 self.consume();
-                if self.peek(0).token != TokenType::CloseParen {
-                    if self.peek(0).token != TokenType::Semi {
-                        println!("excpected ;");
-                    }
-                    else {
-                        self.consume();
-                    }
+                if self.peek(0).token != TokenType::Semi {
+                    self.error("excpected semi colon");
                 }
+                self.consume();
                 let inc_var = IncVar {
-                    var
+                    var,
+                    sym: None,
                 };
                 return  Some(Stmt::IncVar(inc_var));
             }
             else if self.peek(0).token == TokenType::Dec {
                 self.consume();
-                if self.peek(0).token != TokenType::CloseParen {
-                    if self.peek(0).token != TokenType::Semi {
-                        println!("excpected ;");
-                    }
-                    else {
-                        self.consume();
-                    }
+                if self.peek(0).token != TokenType::Semi {
+                    self.error("excpected semi colon");
                 }
                 self.consume();
                 let dec_var = DecVar {
-                    var
+                    var,
+                    sym: None,
                 };
                 return  Some(Stmt::DecVar(dec_var));
             }
@@ -304,26 +368,16 @@ impl Parser {
         if self.peek(0).token == TokenType::If {
             self.consume();
             let res = self.eval_expr();
-            let mut expr_arr: Vec<Stmt> = Vec::new(); 
-            while self.peek(0).token != TokenType::CloseScope && self.peek(0).token != TokenType::Else {
-                let expr = self.parse_stmt().unwrap();
-                expr_arr.push(expr);
-            }
-            if self.peek(0).token != TokenType::Else {
-                    let expr = self.parse_stmt().unwrap();
-                    expr_arr.push(expr);
-                }
+            let expr_arr = self.parse_block();
             let mut else_expr_arr: Vec<Stmt> = Vec::new(); 
-            if self.m_tokens.len() >= 1{
-                if self.peek(0).token == TokenType::Else {
-                    self.consume();
-                    while self.peek(0).token != TokenType::CloseScope {
-                        let expr = self.parse_stmt().unwrap();
-                        else_expr_arr.push(expr);
-                    }
-                    let expr = self.parse_stmt().unwrap();
-                    else_expr_arr.push(expr);
-                    
+            if !self.m_tokens.is_empty() && self.peek(0).token == TokenType::Else {
+                self.consume();
+                if self.peek(0).token == TokenType::If {
+                    // else if, the nested if owns the rest of the chain
+                    else_expr_arr.push(self.expect_stmt());
+                }
+                else {
+                    else_expr_arr = self.parse_block();
                 }
             }
             let if_var = IfStmt {
@@ -336,36 +390,67 @@ impl Parser {
         if self.peek(0).token == TokenType::While {
             self.consume();
             let res = self.eval_expr();
-            let mut expr_arr: Vec<Stmt> = Vec::new();
-            while self.peek(0).token != TokenType::CloseScope {
-                let expr = self.parse_stmt().unwrap();
-                expr_arr.push(expr);
-            }
+            let expr_arr = self.parse_block();
             let while_var = WhileStmt {
                 expr: res,
                 data: expr_arr,
             };
             return Some(Stmt::WhileStmt(while_var));
         }
+        if self.peek(0).token == TokenType::Do {
+            self.consume();
+            let expr_arr = self.parse_block();
+            if self.peek(0).token != TokenType::While {
+                self.error("excpected while after the body of do");
+            }
+            self.consume();
+            let res = self.eval_expr();
+            if self.peek(0).token != TokenType::Semi {
+                self.error("excpected semi colon");
+            }
+            self.consume();
+            let do_while = DoWhileStmt {
+                data: expr_arr,
+                expr: res,
+            };
+            return Some(Stmt::DoWhileStmt(do_while));
+        }
         if self.peek(0).token == TokenType::For {
             self.consume();
             if self.peek(0).token != TokenType::OpenParen {
-                panic!("excpected '('");
+                self.error("excpected '('");
             }
             self.consume();
-            let first_expr = Box::new(self.parse_stmt().unwrap());
-            let second_expr = self.eval_expr();
+            let mut first_expr: Vec<Stmt> = Vec::new();
+            if self.peek(0).token == TokenType::Semi {
+                self.consume();
+            } else {
+                // int i = 0, j = 10; declares both as int
+                let type_len = if Parser::starts_type(self.peek(0)) { self.type_len() } else { 0 };
+                let type_tokens: Vec<Token> = self.m_tokens[..type_len].to_vec();
+                for _ in 0..self.split_clause(TokenType::Semi, &type_tokens) {
+                    first_expr.push(self.expect_stmt());
+                }
+            }
+            let mut second_expr: Vec<RpnExpr> = Vec::new();
+            if self.peek(0).token != TokenType::Semi {
+                second_expr = self.eval_expr();
+            }
+            if self.peek(0).token != TokenType::Semi {
+                self.error("excpected semi colon");
+            }
             self.consume();
-            let third_expr = Box::new(self.parse_stmt().unwrap());
+            let mut third_expr: Vec<Stmt> = Vec::new();
             if self.peek(0).token != TokenType::CloseParen {
-                panic!("excpected ')'");
+                for _ in 0..self.split_clause(TokenType::CloseParen, &[]) {
+                    third_expr.push(self.expect_stmt());
+                }
             }
-            self.consume();
-            let mut expr_arr: Vec<Stmt> = Vec::new(); 
-            while self.peek(0).token != TokenType::CloseScope {
-                let expr = self.parse_stmt().unwrap();
-                expr_arr.push(expr);
+            if self.peek(0).token != TokenType::CloseParen {
+                self.error("excpected ')'");
             }
+            self.consume();
+            let expr_arr = self.parse_block();
             let for_var = ForStmt {
                 expr1: first_expr,
                 expr2: second_expr,
@@ -375,6 +460,110 @@ impl Parser {
             return Some(Stmt::ForStmt(for_var));
         }
 
+        if self.peek(0).token == TokenType::Switch {
+            let switch = self.consume();
+            let expr = self.eval_expr();
+            if self.peek(0).token != TokenType::OpenScope {
+                self.error("excpected '{' after switch");
+            }
+            // like parse_block, but the labels at the top level of the body
+            // are kept aside with the position they point at
+            let mut data: Vec<Stmt> = Vec::new();
+            let mut cases: Vec<SwitchCase> = Vec::new();
+            let mut depth = 0;
+            loop {
+                let label = self.peek(0).clone();
+                if matches!(label.token, TokenType::Case | TokenType::Default) {
+                    if depth != 1 {
+                        self.error("case label in a block inside the switch");
+                    }
+                    self.consume();
+                    let mut expr: Option<Vec<RpnExpr>> = None;
+                    if label.token == TokenType::Case {
+                        expr = Some(self.eval_expr());
+                    }
+                    if self.peek(0).token != TokenType::Colon {
+                        self.error("excpected ':' after a case label");
+                    }
+                    self.consume();
+                    cases.push(SwitchCase { expr, value: None, pos: data.len(), span: label.span });
+                    continue;
+                }
+                let stmt = self.expect_stmt();
+                match stmt {
+                    Stmt::OpenScope(_) => depth += 1,
+                    Stmt::CloseScope(_) => depth -= 1,
+                    _ => {}
+                }
+                data.push(stmt);
+                if depth == 0 {
+                    break;
+                }
+            }
+            let res = SwitchStmt {
+                expr,
+                cases,
+                data,
+                span: switch.span,
+            };
+            return Some(Stmt::SwitchStmt(res));
+        }
+
+        if self.peek(0).token == TokenType::Break {
+            let token = self.consume();
+            if self.peek(0).token != TokenType::Semi {
+                self.error("excpected semi colon");
+            }
+            self.consume();
+            return Some(Stmt::Break(Break { span: token.span }));
+        }
+
+        if self.peek(0).token == TokenType::Continue {
+            let token = self.consume();
+            if self.peek(0).token != TokenType::Semi {
+                self.error("excpected semi colon");
+            }
+            self.consume();
+            return Some(Stmt::Continue(Continue { span: token.span }));
+        }
+
+        if self.peek(0).token == TokenType::Enum {
+            self.consume();
+            let enum_name = self.consume();
+            if self.peek(0).token == TokenType::OpenScope {
+                return Some(Stmt::InitEnum(self.parse_enum(enum_name)));
+            }
+            if self.peek(0).token == TokenType::Mul {
+                self.error("pointers to enums are not supported");
+            }
+            let var_name = self.consume();
+            let ty = TypeInfo { var_type: TokenType::Enum, pointer_depth: 0, struct_name: enum_name.value.clone() };
+            if self.peek(0).token == TokenType::OpenParen {
+                return self.parse_func(var_name, ty);
+            }
+            let mut stmt: Vec<RpnExpr> = Vec::new();
+            if self.peek(0).token == TokenType::Eq {
+                self.consume();
+                stmt = self.eval_expr();
+            } else {
+                // zero, even when no enumerator has that value
+                stmt.push(RpnExpr::PushNum(PushNum { data: Token { token: TokenType::Num, value: Some("0".to_string()), span: var_name.span.clone() }, ty: None }));
+                stmt.push(RpnExpr::Cast(Cast { to: ty, span: var_name.span.clone(), from: None, ty: None }));
+            }
+            if self.peek(0).token != TokenType::Semi {
+                self.error("excpected semi colon");
+            }
+            self.consume();
+            let res = CreateVar {
+                Type: TokenType::Enum,
+                enum_name: enum_name.value,
+                var: var_name,
+                sym: None,
+                stmt,
+            };
+            return Some(Stmt::CreateVar(res));
+        }
+
         if self.peek(0).token == TokenType::Struct {
             self.consume();
             let struct_name = self.consume();
@@ -384,7 +573,7 @@ impl Parser {
                 let mut counter = 0;
                 let mut elements: HashMap<String, StructArg> = HashMap::new();
                 while self.peek(0).token != TokenType::CloseScope {
-                    let arg_type = self.consume();
+                    let arg_type = self.consume_type();
                     let mut pointer_depth = 0;
                     if self.peek(0).token == TokenType::Mul {
                         pointer_depth += 1;
@@ -397,7 +586,7 @@ impl Parser {
 
                     let name = self.consume();
                     if self.peek(0).token != TokenType::Semi {
-                        panic!("expceted semi colon");
+                        self.error("expceted semi colon");
                     }
                     self.consume();
                     let res = StructArg {
@@ -412,10 +601,11 @@ impl Parser {
                 self.consume(); // CloseScope
                 let res = InitStruct {
                     name: struct_name.value.unwrap(),
+                    span: struct_name.span,
                     elements,
                 };
                 if self.peek(0).token != TokenType::Semi {
-                    panic!("excpected semi colon");
+                    self.error("excpected semi colon");
                 }
                 self.consume();
                 return Some(Stmt::InitStruct(res));
@@ -439,13 +629,14 @@ impl Parser {
 
                 }
                 let res = CreateStruct {
-                    var_name: var_name.value.unwrap(),
+                    var_name,
+                    sym: None,
                     struct_name: struct_name.value.unwrap(),
                     pointer_depth,
                     expr,
                 };
                 if self.peek(0).token != TokenType::Semi {
-                    panic!("nigga place that semi colon");
+                    self.error("excpected semi colon");
                 }
                 self.consume();
                 
@@ -455,18 +646,112 @@ impl Parser {
         }
 
         if self.peek(0).token == TokenType::Return {
-            self.consume();
+            let ret = self.consume();
             let expr = self.eval_expr();
             if self.peek(0).token != TokenType::Semi {
-                panic!("excpected ;");
+                self.error("excpected ;");
             }
             self.consume();
             let return_ = Ret {
                 expr: expr,
                 func_name: self.func_name.clone(),
+                span: ret.span,
             };
             return Some(Stmt::Ret(return_));
         }
         None
     }
-}
\ No newline at end of file
+
+    // `{ A, B = 5, C = -1 };` after the name of an enum
+    fn parse_enum(&mut self, name: Token) -> InitEnum {
+        self.consume(); // '{'
+        let mut enumerators: Vec<Enumerator> = Vec::new();
+        while self.peek(0).token != TokenType::CloseScope {
+            let enumerator = self.consume();
+            if enumerator.token != TokenType::Var {
+                self.error("excpected the name of an enumerator");
+            }
+            let mut init: Option<Token> = None;
+            let mut negative = false;
+            if self.peek(0).token == TokenType::Eq {
+                self.consume();
+                if self.peek(0).token == TokenType::Sub {
+                    self.consume();
+                    negative = true;
+                }
+                if self.peek(0).token != TokenType::Num {
+                    self.error("the value of an enumerator has to be a number");
+                }
+                init = Some(self.consume());
+            }
+            if self.peek(0).token == TokenType::Coma {
+                self.consume();
+            } else if self.peek(0).token != TokenType::CloseScope {
+                self.error("excpected ',' or '}' after an enumerator");
+            }
+            enumerators.push(Enumerator { name: enumerator, init, negative, value: None });
+        }
+        self.consume(); // '}'
+        if self.peek(0).token != TokenType::Semi {
+            self.error("excpected semi colon");
+        }
+        self.consume();
+        InitEnum { name, enumerators }
+    }
+
+    // `x += e` and the like, gives the operator applied before storing
+    fn compound_op(token: TokenType) -> Option<TokenType> {
+        match token {
+            TokenType::AddEq => Some(TokenType::Add),
+            TokenType::SubEq => Some(TokenType::Sub),
+            TokenType::MulEq => Some(TokenType::Mul),
+            TokenType::DivEq => Some(TokenType::Div),
+            TokenType::RemEq => Some(TokenType::Remainder),
+            _ => None,
+        }
+    }
+
+    // tokens of the type a declaration starts with, `unsigned int` and
+    // `struct Name` are two
+    fn type_len(&self) -> usize {
+        match self.peek(0).token {
+            TokenType::Unsigned if Parser::is_type(self.peek(1)) => 2,
+            TokenType::Struct | TokenType::Enum => 2,
+            _ => 1,
+        }
+    }
+
+    // a clause of a for header runs up to `end` outside of parens, every
+    // comma in it becomes a ';' followed by `type_tokens` and the clause
+    // ends with a ';', so each part parses as a stmt of its own.
+    // gives the number of parts
+    fn split_clause(&mut self, end: TokenType, type_tokens: &[Token]) -> usize {
+        let mut parts = 1;
+        let mut depth = 0;
+        let mut index = 0;
+        loop {
+            let token = self.peek(index).clone();
+            match token.token {
+                TokenType::OpenParen => depth += 1,
+                TokenType::CloseParen if depth > 0 => depth -= 1,
+                _ if token.token == end && depth == 0 => break,
+                TokenType::Coma if depth == 0 => {
+                    parts += 1;
+                    self.m_tokens[self.m_index + index] = Token { token: TokenType::Semi, value: None, span: token.span.clone() };
+                    for (offset, type_token) in type_tokens.iter().enumerate() {
+                        let type_token = Token { span: token.span.clone(), ..type_token.clone() };
+                        self.m_tokens.insert(self.m_index + index + 1 + offset, type_token);
+                    }
+                    index += type_tokens.len();
+                }
+                _ => {}
+            }
+            index += 1;
+        }
+        if end != TokenType::Semi {
+            let semi = Token { token: TokenType::Semi, value: None, span: self.peek(index).span.clone() };
+            self.m_tokens.insert(self.m_index + index, semi);
+        }
+        parts
+    }
+}
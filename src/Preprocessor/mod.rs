@@ -0,0 +1,607 @@
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::Import::Importer;
+use crate::Tokenizer::{Span, Token, TokenType, Tokenizer};
+
+// splices imports in as it comes across them, so macros defined in an
+// imported file are visible after the import and an import in a skipped
+// group is never loaded
+pub struct Preprocessor {
+    m_macros: HashMap<String, Macro>,
+    m_conds: Vec<Cond>,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    // None for object like macros
+    params: Option<Vec<String>>,
+    body: Vec<Token>,
+}
+
+// one #if/#ifdef/#ifndef until its #endif
+struct Cond {
+    // tokens of the current group are kept
+    active: bool,
+    // one of the groups was already kept, the rest are skipped
+    taken: bool,
+    // the whole #if sits inside a skipped group
+    parent_active: bool,
+    seen_else: bool,
+}
+
+// a token waiting for expansion and the macros it came out of,
+// those are not expanded again inside it
+type HideToken = (Token, Vec<String>);
+
+impl Preprocessor {
+    pub fn new(defines: &[String], undefines: &[String]) -> Self {
+        let mut res = Preprocessor {
+            m_macros: HashMap::new(),
+            m_conds: Vec::new(),
+        };
+        for define in defines {
+            // -D NAME is the same as -D NAME=1
+            let (name, value) = define.split_once('=').unwrap_or((define.as_str(), "1"));
+            let mut tokenizer = Tokenizer::new(value.to_string(), Rc::from("<command line>"));
+            tokenizer.tokenize();
+            res.m_macros.insert(name.to_string(), Macro { params: None, body: tokenizer.m_res });
+        }
+        for name in undefines {
+            res.m_macros.remove(name);
+        }
+        res
+    }
+
+    // a directive or macro use that can't be preprocessed
+    fn error(span: &Span, msg: &str) -> String {
+        format!("{}: {}", span, msg)
+    }
+
+    fn same_line(a: &Token, b: &Token) -> bool {
+        a.span.line == b.span.line && a.span.file == b.span.file
+    }
+
+    fn is_active(&self) -> bool {
+        self.m_conds.last().is_none_or(|cond| cond.active)
+    }
+
+    pub fn process(&mut self, tokens: Vec<Token>, importer: &mut Importer) -> Result<Vec<Token>, String> {
+        let mut res: Vec<Token> = Vec::new();
+        self.process_file(&tokens, importer, &mut res)?;
+        if !self.m_conds.is_empty() {
+            let span = tokens.last().map(|v| v.span.clone()).unwrap_or_default();
+            return Err(Preprocessor::error(&span, "#if without #endif"));
+        }
+        Ok(res)
+    }
+
+    // the tokens of one file, a group opened in it may be closed in the
+    // file importing it
+    fn process_file(&mut self, tokens: &[Token], importer: &mut Importer, res: &mut Vec<Token>) -> Result<(), String> {
+        // text between two directives, expanded as one piece so macro
+        // args can span lines
+        let mut text: Vec<Token> = Vec::new();
+        let mut index = 0;
+        while index < tokens.len() {
+            let token = &tokens[index];
+            let line_start = index == 0 || !Preprocessor::same_line(&tokens[index - 1], token);
+            if token.token == TokenType::Hash && line_start {
+                let mut end = index + 1;
+                while end < tokens.len() && Preprocessor::same_line(token, &tokens[end]) {
+                    end += 1;
+                }
+                res.extend(self.expand(std::mem::take(&mut text))?);
+                self.directive(token, &tokens[index + 1..end])?;
+                index = end;
+                continue;
+            }
+            if token.token == TokenType::Import && self.is_active() {
+                res.extend(self.expand(std::mem::take(&mut text))?);
+                let file = match tokens.get(index + 1) {
+                    Some(file) if file.token == TokenType::String => file,
+                    _ => return Err(Preprocessor::error(&token.span, "excpected a file name after import")),
+                };
+                if !tokens.get(index + 2).is_some_and(|v| v.token == TokenType::Semi) {
+                    return Err(Preprocessor::error(&file.span, "excpected semi colon after import"));
+                }
+                if let Some(imported) = importer.import(file)? {
+                    self.process_file(&imported, importer, res)?;
+                    importer.finish();
+                }
+                index += 3;
+                continue;
+            }
+            if self.is_active() {
+                if matches!(token.token, TokenType::Hash | TokenType::HashHash) {
+                    return Err(Preprocessor::error(&token.span, "'#' outside of a directive"));
+                }
+                text.push(token.clone());
+            }
+            index += 1;
+        }
+        res.extend(self.expand(text)?);
+        Ok(())
+    }
+
+    fn directive(&mut self, hash: &Token, line: &[Token]) -> Result<(), String> {
+        // a lone '#' does nothing
+        let Some(name) = line.first() else {
+            return Ok(());
+        };
+        let kind = match name.token {
+            TokenType::If => "if".to_string(),
+            TokenType::Else => "else".to_string(),
+            TokenType::Var => name.value.clone().unwrap(),
+            _ => return Err(Preprocessor::error(&name.span, &format!("unknown directive: #{}", name.spelling()))),
+        };
+        let active = self.is_active();
+        match kind.as_str() {
+            "if" | "ifdef" | "ifndef" => {
+                let value = if !active {
+                    false
+                } else if kind == "if" {
+                    self.eval_cond(&line[1..], &name.span)? != 0
+                } else {
+                    let macro_name = match line.get(1) {
+                        Some(v) if v.token == TokenType::Var => v.value.clone().unwrap(),
+                        _ => return Err(Preprocessor::error(&name.span, &format!("#{} excpects a macro name", kind))),
+                    };
+                    self.m_macros.contains_key(&macro_name) == (kind == "ifdef")
+                };
+                self.m_conds.push(Cond { active: value, taken: value, parent_active: active, seen_else: false });
+            }
+            "elif" => {
+                let Some(cond) = self.m_conds.last() else {
+                    return Err(Preprocessor::error(&hash.span, "#elif without #if"));
+                };
+                if cond.seen_else {
+                    return Err(Preprocessor::error(&hash.span, "#elif after #else"));
+                }
+                let value = cond.parent_active && !cond.taken && self.eval_cond(&line[1..], &name.span)? != 0;
+                let cond = self.m_conds.last_mut().unwrap();
+                cond.active = value;
+                cond.taken |= value;
+            }
+            "else" => {
+                let Some(cond) = self.m_conds.last_mut() else {
+                    return Err(Preprocessor::error(&hash.span, "#else without #if"));
+                };
+                if cond.seen_else {
+                    return Err(Preprocessor::error(&hash.span, "#else after #else"));
+                }
+                cond.active = cond.parent_active && !cond.taken;
+                cond.taken = true;
+                cond.seen_else = true;
+            }
+            "endif" => {
+                if self.m_conds.pop().is_none() {
+                    return Err(Preprocessor::error(&hash.span, "#endif without #if"));
+                }
+            }
+            _ if !active => {}
+            "define" => self.define(&name.span, &line[1..])?,
+            "undef" => match line.get(1) {
+                Some(v) if v.token == TokenType::Var => {
+                    self.m_macros.remove(v.value.as_ref().unwrap());
+                }
+                _ => return Err(Preprocessor::error(&name.span, "#undef excpects a macro name")),
+            },
+            _ => return Err(Preprocessor::error(&name.span, &format!("unknown directive: #{}", kind))),
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, span: &Span, line: &[Token]) -> Result<(), String> {
+        let name = match line.first() {
+            Some(v) if v.token == TokenType::Var => v,
+            _ => return Err(Preprocessor::error(span, "#define excpects a macro name")),
+        };
+        let macro_name = name.value.clone().unwrap();
+        if macro_name == "defined" {
+            return Err(Preprocessor::error(&name.span, "'defined' cannot be a macro name"));
+        }
+        // NAME(a, b) is a function like macro only when '(' touches the name
+        let function_like = line.get(1).is_some_and(|v| {
+            v.token == TokenType::OpenParen && v.span.col == name.span.col + macro_name.len() as u32
+        });
+        if !function_like {
+            let body = line[1..].to_vec();
+            Preprocessor::check_paste(&body, false)?;
+            self.m_macros.insert(macro_name, Macro { params: None, body });
+            return Ok(());
+        }
+
+        let mut params: Vec<String> = Vec::new();
+        let mut index = 2;
+        loop {
+            match line.get(index) {
+                Some(v) if v.token == TokenType::CloseParen && params.is_empty() => break,
+                Some(v) if v.token == TokenType::Var => {
+                    let param = v.value.clone().unwrap();
+                    if params.contains(&param) {
+                        return Err(Preprocessor::error(&v.span, &format!("duplicate macro param: {}", param)));
+                    }
+                    params.push(param);
+                }
+                Some(v) => return Err(Preprocessor::error(&v.span, "excpected a macro param name")),
+                None => return Err(Preprocessor::error(&name.span, "unterminated macro param list")),
+            }
+            index += 1;
+            match line.get(index) {
+                Some(v) if v.token == TokenType::Coma => index += 1,
+                Some(v) if v.token == TokenType::CloseParen => break,
+                _ => return Err(Preprocessor::error(&name.span, "excpected ',' or ')' in macro param list")),
+            }
+        }
+        let body = line[index + 1..].to_vec();
+        Preprocessor::check_paste(&body, true)?;
+        self.m_macros.insert(macro_name, Macro { params: Some(params), body });
+        Ok(())
+    }
+
+    // '#' only stringifies in function like macros, in an object like
+    // macro it is a plain token
+    fn check_paste(body: &[Token], function_like: bool) -> Result<(), String> {
+        if let Some(first) = body.first() && first.token == TokenType::HashHash {
+            return Err(Preprocessor::error(&first.span, "'##' cannot start a macro body"));
+        }
+        if let Some(last) = body.last() && (last.token == TokenType::HashHash || (function_like && last.token == TokenType::Hash)) {
+            return Err(Preprocessor::error(&last.span, "'#' or '##' cannot end a macro body"));
+        }
+        Ok(())
+    }
+
+    fn expand(&self, tokens: Vec<Token>) -> Result<Vec<Token>, String> {
+        let input: VecDeque<HideToken> = tokens.into_iter().map(|v| (v, Vec::new())).collect();
+        Ok(self.expand_hidden(input)?.into_iter().map(|(v, _)| v).collect())
+    }
+
+    // the result of an expansion is pushed back in front of the input and
+    // rescanned together with whatever follows it
+    fn expand_hidden(&self, mut input: VecDeque<HideToken>) -> Result<Vec<HideToken>, String> {
+        let mut res: Vec<HideToken> = Vec::new();
+        while let Some((token, hidden)) = input.pop_front() {
+            let mac = match (&token.token, &token.value) {
+                (TokenType::Var, Some(name)) if !hidden.contains(name) => self.m_macros.get(name),
+                _ => None,
+            };
+            let Some(mac) = mac else {
+                res.push((token, hidden));
+                continue;
+            };
+            let name = token.value.clone().unwrap();
+            let mut new_hidden = hidden.clone();
+            new_hidden.push(name.clone());
+
+            let body: Vec<Token> = match &mac.params {
+                None => self.substitute(mac, &[], &[], &token.span)?,
+                Some(params) => {
+                    // a function like macro name without '(' is a plain name
+                    if !input.front().is_some_and(|(v, _)| v.token == TokenType::OpenParen) {
+                        res.push((token, hidden));
+                        continue;
+                    }
+                    let args = Preprocessor::collect_args(&mut input, &token)?;
+                    // f() passes a single empty arg
+                    let args_count = if params.is_empty() && args.len() == 1 && args[0].is_empty() { 0 } else { args.len() };
+                    if args_count != params.len() {
+                        return Err(Preprocessor::error(&token.span, &format!("macro {} takes {} args but {} were passed", name, params.len(), args_count)));
+                    }
+                    self.substitute(mac, params, &args, &token.span)?
+                }
+            };
+            for v in body.into_iter().rev() {
+                let mut v = v;
+                // expanded tokens point at the place the macro was used
+                v.span = token.span.clone();
+                input.push_front((v, new_hidden.clone()));
+            }
+        }
+        Ok(res)
+    }
+
+    // reads `( a, b )` after the macro name, commas inside nested
+    // parens don't split args
+    fn collect_args(input: &mut VecDeque<HideToken>, name: &Token) -> Result<Vec<Vec<HideToken>>, String> {
+        input.pop_front();
+        let mut args: Vec<Vec<HideToken>> = vec![Vec::new()];
+        let mut depth = 0;
+        loop {
+            let Some((token, hidden)) = input.pop_front() else {
+                return Err(Preprocessor::error(&name.span, &format!("unterminated call of macro {}", name.spelling())));
+            };
+            match token.token {
+                TokenType::CloseParen if depth == 0 => break,
+                TokenType::Coma if depth == 0 => {
+                    args.push(Vec::new());
+                    continue;
+                }
+                TokenType::OpenParen => depth += 1,
+                TokenType::CloseParen => depth -= 1,
+                _ => {}
+            }
+            args.last_mut().unwrap().push((token, hidden));
+        }
+        Ok(args)
+    }
+
+    fn substitute(&self, mac: &Macro, params: &[String], args: &[Vec<HideToken>], span: &Span) -> Result<Vec<Token>, String> {
+        let param_index = |token: &Token| -> Option<usize> {
+            if token.token != TokenType::Var {
+                return None;
+            }
+            params.iter().position(|v| Some(v) == token.value.as_ref())
+        };
+        let raw = |index: usize| -> Vec<Token> { args[index].iter().map(|(v, _)| v.clone()).collect() };
+
+        let body = &mac.body;
+        let mut res: Vec<Token> = Vec::new();
+        let mut index = 0;
+        while index < body.len() {
+            let token = &body[index];
+            // #param
+            if token.token == TokenType::Hash && mac.params.is_some() {
+                let Some(arg) = body.get(index + 1).and_then(param_index) else {
+                    return Err(Preprocessor::error(&token.span, "'#' has to be followed by a macro param"));
+                };
+                let text: Vec<String> = raw(arg).iter().map(|v| v.spelling()).collect();
+                let value = text.join(" ").replace('\\', "\\\\").replace('"', "\\\"");
+                res.push(Token { token: TokenType::String, value: Some(value), span: span.clone() });
+                index += 2;
+                continue;
+            }
+            // a ## b
+            if token.token == TokenType::HashHash {
+                let next = &body[index + 1];
+                let mut rhs = match param_index(next) {
+                    Some(arg) => raw(arg),
+                    None => vec![next.clone()],
+                };
+                if !rhs.is_empty() {
+                    let first = rhs.remove(0);
+                    match res.pop() {
+                        Some(lhs) => res.push(Preprocessor::paste(&lhs, &first, span)?),
+                        None => res.push(first),
+                    }
+                }
+                res.extend(rhs);
+                index += 2;
+                continue;
+            }
+            match param_index(token) {
+                // operands of ## are pasted as written, other args are
+                // fully expanded before they are put in
+                Some(arg) if body.get(index + 1).is_some_and(|v| v.token == TokenType::HashHash) => {
+                    res.extend(raw(arg));
+                }
+                Some(arg) => {
+                    let expanded = self.expand_hidden(args[arg].iter().cloned().collect())?;
+                    res.extend(expanded.into_iter().map(|(v, _)| v));
+                }
+                None => res.push(token.clone()),
+            }
+            index += 1;
+        }
+        Ok(res)
+    }
+
+    fn paste(lhs: &Token, rhs: &Token, span: &Span) -> Result<Token, String> {
+        let text = format!("{}{}", lhs.spelling(), rhs.spelling());
+        let mut tokenizer = Tokenizer::new(text.clone(), span.file.clone());
+        tokenizer.tokenize();
+        if tokenizer.m_res.len() != 1 {
+            return Err(Preprocessor::error(span, &format!("pasting {} and {} does not give a valid token", lhs.spelling(), rhs.spelling())));
+        }
+        let mut res = tokenizer.m_res.remove(0);
+        res.span = span.clone();
+        Ok(res)
+    }
+
+    fn eval_cond(&self, line: &[Token], span: &Span) -> Result<i64, String> {
+        // defined NAME and defined(NAME) are replaced before expansion
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut index = 0;
+        while index < line.len() {
+            let token = &line[index];
+            if token.token == TokenType::Var && token.value.as_deref() == Some("defined") {
+                let paren = line.get(index + 1).is_some_and(|v| v.token == TokenType::OpenParen);
+                let name_index = if paren { index + 2 } else { index + 1 };
+                let name = match line.get(name_index) {
+                    Some(v) if v.token == TokenType::Var => v.value.clone().unwrap(),
+                    _ => return Err(Preprocessor::error(&token.span, "'defined' excpects a macro name")),
+                };
+                if paren && !line.get(name_index + 1).is_some_and(|v| v.token == TokenType::CloseParen) {
+                    return Err(Preprocessor::error(&token.span, "excpected ')' after defined("));
+                }
+                let value = if self.m_macros.contains_key(&name) { "1" } else { "0" };
+                tokens.push(Token { token: TokenType::Num, value: Some(value.to_string()), span: token.span.clone() });
+                index = if paren { name_index + 2 } else { name_index + 1 };
+                continue;
+            }
+            tokens.push(token.clone());
+            index += 1;
+        }
+        // names left after expansion are 0 like in C
+        let tokens: Vec<Token> = self.expand(tokens)?.into_iter().map(|v| {
+            if v.token == TokenType::Var {
+                Token { token: TokenType::Num, value: Some("0".to_string()), span: v.span }
+            } else {
+                v
+            }
+        }).collect();
+        if tokens.is_empty() {
+            return Err(Preprocessor::error(span, "#if without a condition"));
+        }
+        let mut pos = 0;
+        let res = Preprocessor::cond_or(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(Preprocessor::error(&tokens[pos].span, &format!("unexpected {} in #if", tokens[pos].spelling())));
+        }
+        Ok(res)
+    }
+
+    fn cond_or(tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let mut lhs = Preprocessor::cond_and(tokens, pos)?;
+        while tokens.get(*pos).is_some_and(|v| v.token == TokenType::Or) {
+            *pos += 1;
+            let rhs = Preprocessor::cond_and(tokens, pos)?;
+            lhs = (lhs != 0 || rhs != 0) as i64;
+        }
+        Ok(lhs)
+    }
+
+    fn cond_and(tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let mut lhs = Preprocessor::cond_compare(tokens, pos)?;
+        while tokens.get(*pos).is_some_and(|v| v.token == TokenType::And) {
+            *pos += 1;
+            let rhs = Preprocessor::cond_compare(tokens, pos)?;
+            lhs = (lhs != 0 && rhs != 0) as i64;
+        }
+        Ok(lhs)
+    }
+
+    fn cond_compare(tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let mut lhs = Preprocessor::cond_add(tokens, pos)?;
+        while let Some(op) = tokens.get(*pos).map(|v| v.token) {
+            let cmp: fn(i64, i64) -> bool = match op {
+                TokenType::AsertEq => |a, b| a == b,
+                TokenType::NotEq => |a, b| a != b,
+                TokenType::Less => |a, b| a < b,
+                TokenType::LessThan => |a, b| a <= b,
+                TokenType::More => |a, b| a > b,
+                TokenType::MoreThan => |a, b| a >= b,
+                _ => break,
+            };
+            *pos += 1;
+            let rhs = Preprocessor::cond_add(tokens, pos)?;
+            lhs = cmp(lhs, rhs) as i64;
+        }
+        Ok(lhs)
+    }
+
+    fn cond_add(tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let mut lhs = Preprocessor::cond_mul(tokens, pos)?;
+        while let Some(op) = tokens.get(*pos).map(|v| v.token) {
+            if !matches!(op, TokenType::Add | TokenType::Sub) {
+                break;
+            }
+            *pos += 1;
+            let rhs = Preprocessor::cond_mul(tokens, pos)?;
+            lhs = if op == TokenType::Add { lhs.wrapping_add(rhs) } else { lhs.wrapping_sub(rhs) };
+        }
+        Ok(lhs)
+    }
+
+    fn cond_mul(tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let mut lhs = Preprocessor::cond_unary(tokens, pos)?;
+        while let Some(op) = tokens.get(*pos) {
+            if !matches!(op.token, TokenType::Mul | TokenType::Div | TokenType::Remainder) {
+                break;
+            }
+            let op = op.clone();
+            *pos += 1;
+            let rhs = Preprocessor::cond_unary(tokens, pos)?;
+            lhs = match op.token {
+                TokenType::Mul => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err(Preprocessor::error(&op.span, "division by zero in #if")),
+                TokenType::Div => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn cond_unary(tokens: &[Token], pos: &mut usize) -> Result<i64, String> {
+        let Some(token) = tokens.get(*pos) else {
+            let span = tokens.last().map(|v| v.span.clone()).unwrap_or_default();
+            return Err(Preprocessor::error(&span, "unexpected end of #if"));
+        };
+        *pos += 1;
+        Ok(match token.token {
+            TokenType::Not => (Preprocessor::cond_unary(tokens, pos)? == 0) as i64,
+            TokenType::Sub => Preprocessor::cond_unary(tokens, pos)?.wrapping_neg(),
+            TokenType::Add => Preprocessor::cond_unary(tokens, pos)?,
+            TokenType::Num | TokenType::CharValue => token.value.as_ref().unwrap().parse()
+                .map_err(|_| Preprocessor::error(&token.span, "number too big for #if"))?,
+            TokenType::OpenParen => {
+                let res = Preprocessor::cond_or(tokens, pos)?;
+                if !tokens.get(*pos).is_some_and(|v| v.token == TokenType::CloseParen) {
+                    return Err(Preprocessor::error(&token.span, "missing ')' in #if"));
+                }
+                *pos += 1;
+                res
+            }
+            _ => return Err(Preprocessor::error(&token.span, &format!("unexpected {} in #if", token.spelling()))),
+        })
+    }
+}
+
+// turns tokens back into source text for --emit preprocessed, tokens
+// keep their line and column when they still have them
+pub fn to_source(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut line_len = 0;
+    let mut prev: Option<&Token> = None;
+    for token in tokens {
+        let new_line = prev.is_some_and(|v| !Preprocessor::same_line(v, token));
+        if new_line {
+            out.push('\n');
+            line_len = 0;
+        }
+        let col = token.span.col.saturating_sub(1) as usize;
+        if line_len == 0 || col >= line_len {
+            let pad = col.saturating_sub(line_len);
+            out.push_str(&" ".repeat(pad));
+            line_len += pad;
+        } else {
+            out.push(' ');
+            line_len += 1;
+        }
+        let text = token.spelling();
+        line_len += text.len();
+        out.push_str(&text);
+        prev = Some(token);
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::Import::Importer;
+    use crate::Tokenizer::{Token, TokenType, Tokenizer};
+
+    use super::Preprocessor;
+
+    fn process(src: &str) -> Result<Vec<Token>, String> {
+        let mut tokenizer = Tokenizer::new(src.to_string(), Rc::from("test.v"));
+        tokenizer.tokenize();
+        Preprocessor::new(&[], &[]).process(tokenizer.m_res, &mut Importer::new(Vec::new()))
+    }
+
+    #[test]
+    fn bad_directives_are_errors() {
+        assert_eq!(process("#if 1\nint x;\n").unwrap_err(), "test.v:2:6: #if without #endif");
+        assert_eq!(process("#define\n").unwrap_err(), "test.v:1:2: #define excpects a macro name");
+        assert_eq!(process("#define F(a, b) a\nint x = F(1);\n").unwrap_err(), "test.v:2:9: macro F takes 2 args but 1 were passed");
+    }
+
+    #[test]
+    fn paste_in_object_like_macro() {
+        let tokens = process("#define CAT a ## b\nint CAT;\n").unwrap();
+        assert_eq!(tokens[1].value.as_deref(), Some("ab"));
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(process("#define CAT a ##\n").unwrap_err(), "test.v:1:15: '#' or '##' cannot end a macro body");
+    }
+
+    #[test]
+    fn import_in_skipped_group() {
+        let tokens = process("#if 0\nimport \"missing.v\";\n#endif\n#ifdef NOT_DEFINED\nimport \"missing.v\";\n#endif\nint x;\n").unwrap();
+        assert!(tokens.iter().all(|v| v.token != TokenType::Import));
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(process("#if 1\nimport \"missing.v\";\n#endif\n").unwrap_err(), "test.v:2:8: cannot find import \"missing.v\"");
+    }
+}
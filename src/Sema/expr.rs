@@ -0,0 +1,432 @@
+use super::*;
+
+use crate::Ir::expr::{Cast, Deref, Function, GetAddr, GetArrayValue, GetSizeOf, GetStructValue, Negative, Operator, PushNum, PushStr, PushVar};
+
+impl Sema {
+    // types every node of an rpn expression, the last node is the value
+    // of the whole expression, an empty one is void
+    pub(super) fn check_expr(&mut self, expr: &mut Vec<RpnExpr>) -> TypeInfo {
+        // every value on the stack with the index right after the last
+        // node computing it, that's where its conversion goes
+        let mut stack: Vec<(TypeInfo, usize)> = Vec::new();
+        let mut res: Vec<RpnExpr> = Vec::with_capacity(expr.len());
+        for mut i in std::mem::take(expr) {
+            if let RpnExpr::PushVar(v) = &i && let Some(constant) = self.enum_constant(&v.data) {
+                i = constant;
+            }
+            let ty = match &mut i {
+                // enumerators come typed
+                RpnExpr::PushNum(v) => v.ty.clone().unwrap_or_else(|| v.check()),
+                RpnExpr::PushStr(v) => v.check(),
+                RpnExpr::PushVar(v) => v.check(self),
+                RpnExpr::Negative(v) => v.check(self),
+                RpnExpr::GetArrayValue(v) => v.check(self),
+                RpnExpr::Deref(v) => v.check(self),
+                RpnExpr::GetAddr(v) => v.check(self),
+                RpnExpr::GetSizeOf(v) => v.check(self),
+                RpnExpr::GetStructValue(v) => v.check(self),
+                RpnExpr::Function(v) => v.check(self),
+                RpnExpr::Operator(v) => {
+                    let rhs = stack.pop();
+                    let lhs = stack.pop();
+                    match (lhs, rhs) {
+                        (Some((lhs, lhs_end)), Some((rhs, _))) => {
+                            // an operand made of a literal ends with it
+                            let lhs_null = Sema::is_null(&res[lhs_end - 1]);
+                            let rhs_null = Sema::is_null(res.last().unwrap());
+                            let (ty, lhs_to, rhs_to) = v.check(self, (&lhs, lhs_null), (&rhs, rhs_null));
+                            // rhs goes first, converting lhs shifts it
+                            let end = res.len();
+                            Sema::convert_at(&mut res, end, &rhs, &rhs_to, &v.data.span);
+                            Sema::convert_at(&mut res, lhs_end, &lhs, &lhs_to, &v.data.span);
+                            ty
+                        }
+                        _ => {
+                            self.error(&v.data.span, format!("missing operand for '{}'", v.data.spelling()));
+                            Sema::int_type()
+                        }
+                    }
+                }
+                RpnExpr::Convert(v) => v.ty.clone().unwrap(),
+                RpnExpr::Cast(v) => match stack.pop() {
+                    Some((value, _)) => v.check(self, &value),
+                    None => {
+                        self.error(&v.span, "missing operand for a cast".to_string());
+                        v.to.clone()
+                    }
+                },
+            };
+            i.set_ty(ty.clone());
+            res.push(i);
+            stack.push((ty, res.len()));
+        }
+        *expr = res;
+        stack.pop().map(|(ty, _)| ty).unwrap_or(TypeInfo { var_type: TokenType::Void, pointer_depth: 0, struct_name: None })
+    }
+
+    // the value of an enumerator, unless a variable shadows it
+    fn enum_constant(&self, token: &Token) -> Option<RpnExpr> {
+        let name = token.value.as_deref()?;
+        if self.lookup(name).is_some() {
+            return None;
+        }
+        let constant = self.enumerators.get(name)?;
+        let data = Token { token: TokenType::Num, value: Some(constant.value.to_string()), span: token.span.clone() };
+        let ty = TypeInfo { var_type: TokenType::Enum, pointer_depth: 0, struct_name: Some(constant.enum_name.clone()) };
+        Some(RpnExpr::PushNum(PushNum { data, ty: Some(ty) }))
+    }
+
+    // puts a conversion of the value ending at `at` into the expression,
+    // values of the same size and kind are left alone
+    pub(super) fn convert_at(expr: &mut Vec<RpnExpr>, at: usize, from: &TypeInfo, to: &TypeInfo, span: &Span) {
+        let (Some(from_repr), Some(to_repr)) = (Sema::repr(from), Sema::repr(to)) else {
+            return;
+        };
+        if from_repr == to_repr {
+            return;
+        }
+        let convert = Convert { from: from.clone(), span: span.clone(), ty: Some(to.clone()) };
+        expr.insert(at, RpnExpr::Convert(convert));
+    }
+
+    // how a value is held in a register, pointers are plain addresses and
+    // enums plain ints
+    fn repr(ty: &TypeInfo) -> Option<TokenType> {
+        if ty.pointer_depth > 0 {
+            Some(TokenType::ULongType)
+        }
+        else if ty.var_type == TokenType::Enum {
+            Some(TokenType::IntType)
+        }
+        else if Sema::is_integer(ty) {
+            Some(ty.var_type)
+        }
+        else {
+            None
+        }
+    }
+
+    // the literal 0, the only integer a pointer can be compared with
+    fn is_null(node: &RpnExpr) -> bool {
+        matches!(node, RpnExpr::PushNum(v) if Sema::literal_value(&v.data, false) == Some(0))
+    }
+
+    // value of an expression made of just an integer literal
+    pub(super) fn expr_literal(expr: &[RpnExpr]) -> Option<i128> {
+        match expr {
+            [RpnExpr::PushNum(v)] => Sema::literal_value(&v.data, false),
+            [RpnExpr::Negative(v)] if v.data.token != TokenType::Var => Sema::literal_value(&v.data, true),
+            _ => None,
+        }
+    }
+
+    pub(super) fn literal_value(token: &Token, negative: bool) -> Option<i128> {
+        let value = token.value.as_deref()?;
+        // the value of a negative enumerator
+        let (negative, value) = match value.strip_prefix('-') {
+            Some(rest) => (!negative, rest),
+            None => (negative, value),
+        };
+        let magnitude = match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+            None => value.parse::<u64>().ok()?,
+        };
+        Some(if negative { -(magnitude as i128) } else { magnitude as i128 })
+    }
+
+    // the smallest of int, long and unsigned long a literal fits in
+    fn literal_type(value: Option<i128>) -> TypeInfo {
+        let var_type = match value {
+            Some(v) if i32::try_from(v).is_ok() => TokenType::IntType,
+            Some(v) if i64::try_from(v).is_ok() => TokenType::LongType,
+            Some(_) => TokenType::ULongType,
+            None => TokenType::IntType,
+        };
+        TypeInfo { var_type, pointer_depth: 0, struct_name: None }
+    }
+
+    // whether every value of `from`, or the literal, fits in `to`
+    pub(super) fn fits(to: TokenType, from: TokenType, literal: Option<i128>) -> bool {
+        let bits = Sema::int_size(to) * 8;
+        let (min, max) = if Sema::is_unsigned(to) {
+            (0i128, (1i128 << bits) - 1)
+        } else {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        };
+        match literal {
+            Some(v) => v >= min && v <= max,
+            None => {
+                let from_bits = Sema::int_size(from) * 8;
+                if Sema::is_unsigned(from) {
+                    (1i128 << from_bits) - 1 <= max
+                } else {
+                    -(1i128 << (from_bits - 1)) >= min && (1i128 << (from_bits - 1)) - 1 <= max
+                }
+            }
+        }
+    }
+
+    // arg count and arg types of a call, gives the return type
+    pub(super) fn check_call(&mut self, name: &Token, args: &mut [Vec<RpnExpr>]) -> Option<TypeInfo> {
+        let func_name = name.value.clone().unwrap();
+        self.called.entry(func_name.clone()).or_insert_with(|| name.span.clone());
+        let arg_types: Vec<TypeInfo> = args.iter_mut().map(|arg| self.check_expr(arg)).collect();
+        let Some(func_data) = self.functions.get(&func_name).cloned() else {
+            self.error(&name.span, format!("call to unknown function: {}", func_name));
+            return None;
+        };
+        if func_data.variadic {
+            if args.len() < func_data.args.len() {
+                self.error(&name.span, format!("function {} takes at least {} args but {} were passed", func_name, func_data.args.len(), args.len()));
+            }
+        }
+        else if args.len() != func_data.args.len() {
+            self.error(&name.span, format!("function {} takes {} args but {} were passed", func_name, func_data.args.len(), args.len()));
+        }
+        for (index, (arg, arg_type)) in args.iter_mut().zip(arg_types.iter()).enumerate() {
+            let expected = match func_data.args.get(index) {
+                Some(arg_data) => Sema::arg_type(arg_data),
+                // the rest of a variadic call gets the default promotions
+                None if Sema::is_integer(arg_type) => Sema::promote(arg_type),
+                None => continue,
+            };
+            if !Sema::assignable(&expected, arg_type, arg) {
+                self.error(&name.span, format!("arg {} of {} is {} but {} was passed",
+                    index + 1, func_name, Sema::type_name(&expected), Sema::type_name(arg_type)));
+                continue;
+            }
+            self.convert(arg, arg_type, &expected, &name.span);
+        }
+        Some(func_data.return_type)
+    }
+}
+
+impl PushNum {
+    fn check(&self) -> TypeInfo {
+        if matches!(self.data.token, TokenType::True | TokenType::False) {
+            return Sema::bool_type();
+        }
+        Sema::literal_type(Sema::literal_value(&self.data, false))
+    }
+}
+
+impl PushStr {
+    fn check(&self) -> TypeInfo {
+        TypeInfo { var_type: TokenType::CharType, pointer_depth: 1, struct_name: None }
+    }
+}
+
+impl PushVar {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        let Some((id, var)) = sema.resolve(&self.data) else {
+            return Sema::int_type();
+        };
+        self.sym = Some(id);
+        if var.ty.var_type == TokenType::Struct && var.ty.pointer_depth == 0 {
+            sema.error(&self.data.span, format!("cannot copy a struct: {}", self.data.value.as_ref().unwrap()));
+        }
+        var.ty
+    }
+}
+
+impl Negative {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        if self.data.token != TokenType::Var {
+            return Sema::literal_type(Sema::literal_value(&self.data, true));
+        }
+        let Some((id, var)) = sema.resolve(&self.data) else {
+            return Sema::int_type();
+        };
+        self.sym = Some(id);
+        if !Sema::is_integer(&var.ty) {
+            sema.error(&self.data.span, format!("cannot negate {}", Sema::type_name(&var.ty)));
+            return Sema::int_type();
+        }
+        var.ty
+    }
+}
+
+impl GetArrayValue {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        if self.index.token == TokenType::Var
+        && let Some((id, index)) = sema.resolve(&self.index) {
+            self.index_sym = Some(id);
+            if !Sema::is_integer(&index.ty) {
+                sema.error(&self.index.span, format!("array index is {}", Sema::type_name(&index.ty)));
+            }
+        }
+        let Some((id, var)) = sema.resolve(&self.name) else {
+            return Sema::int_type();
+        };
+        self.sym = Some(id);
+        if var.ty.pointer_depth == 0 {
+            sema.error(&self.name.span, format!("{} is not an array", self.name.value.as_ref().unwrap()));
+            return Sema::int_type();
+        }
+        TypeInfo { pointer_depth: var.ty.pointer_depth - 1, ..var.ty }
+    }
+}
+
+impl Deref {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        let Some((id, var)) = sema.resolve(&self.var) else {
+            return Sema::int_type();
+        };
+        self.sym = Some(id);
+        if var.ty.pointer_depth < self.stack_depth {
+            sema.error(&self.var.span, format!("dereferencing {} which is {}", self.var.value.as_ref().unwrap(), Sema::type_name(&var.ty)));
+            return Sema::int_type();
+        }
+        TypeInfo { pointer_depth: var.ty.pointer_depth - self.stack_depth, ..var.ty }
+    }
+}
+
+impl GetAddr {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        let Some((id, var)) = sema.resolve(&self.var) else {
+            return Sema::int_type();
+        };
+        self.sym = Some(id);
+        TypeInfo { pointer_depth: var.ty.pointer_depth + 1, ..var.ty }
+    }
+}
+
+impl GetSizeOf {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        match &self.of_type {
+            Some(ty) => sema.check_tag(&self.var.span, ty),
+            None => self.sym = sema.resolve(&self.var).map(|(id, _)| id),
+        }
+        Sema::int_type()
+    }
+}
+
+impl GetStructValue {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        let Some((id, var)) = sema.resolve(&self.var_name) else {
+            return Sema::int_type();
+        };
+        self.sym = Some(id);
+        match sema.get_field(&self.var_name, &var.ty, 0, &self.struct_value_name) {
+            Some(field) => field,
+            None => Sema::int_type(),
+        }
+    }
+}
+
+impl Function {
+    fn check(&mut self, sema: &mut Sema) -> TypeInfo {
+        let Some(return_type) = sema.check_call(&self.name, &mut self.args) else {
+            return Sema::int_type();
+        };
+        if return_type.var_type == TokenType::Void && return_type.pointer_depth == 0 {
+            sema.error(&self.name.span, format!("void function {} used as a value", self.name.value.as_ref().unwrap()));
+            return Sema::int_type();
+        }
+        return_type
+    }
+}
+
+impl Cast {
+    // any integer or pointer can be cast to any other
+    fn check(&mut self, sema: &mut Sema, value: &TypeInfo) -> TypeInfo {
+        if !Sema::is_scalar(&self.to) {
+            sema.error(&self.span, format!("cannot cast to {}", Sema::type_name(&self.to)));
+        }
+        else if !Sema::is_scalar(value) {
+            sema.error(&self.span, format!("cannot cast {} to {}", Sema::type_name(value), Sema::type_name(&self.to)));
+        }
+        sema.check_tag(&self.span, &self.to);
+        self.from = Some(value.clone());
+        self.to.clone()
+    }
+}
+
+impl Operator {
+    // gives the type of the result and the types the lhs and the rhs are
+    // converted to before the operator runs, each operand comes with
+    // whether it is the literal 0
+    fn check(&self, sema: &mut Sema, (lhs, lhs_null): (&TypeInfo, bool), (rhs, rhs_null): (&TypeInfo, bool)) -> (TypeInfo, TypeInfo, TypeInfo) {
+        let op = self.data.spelling();
+        let unchanged = (Sema::int_type(), lhs.clone(), rhs.clone());
+        if matches!(self.data.token, TokenType::And | TokenType::Or | TokenType::Not) {
+            sema.error(&self.data.span, format!("operator '{}' is not supported", op));
+            return unchanged;
+        }
+        if !Sema::is_scalar(lhs) || !Sema::is_scalar(rhs) {
+            sema.error(&self.data.span, format!("invalid operands to '{}': {} and {}", op, Sema::type_name(lhs), Sema::type_name(rhs)));
+            return unchanged;
+        }
+        let lhs_ptr = lhs.pointer_depth > 0;
+        let rhs_ptr = rhs.pointer_depth > 0;
+        // the integer side of pointer arithmetic is scaled as a long
+        let long = TypeInfo { var_type: TokenType::LongType, pointer_depth: 0, struct_name: None };
+        match self.data.token {
+            TokenType::Add if lhs_ptr && rhs_ptr => {
+                sema.error(&self.data.span, format!("cannot add two pointers: {} and {}", Sema::type_name(lhs), Sema::type_name(rhs)));
+                unchanged
+            }
+            TokenType::Add if lhs_ptr => (lhs.clone(), lhs.clone(), long),
+            TokenType::Add if rhs_ptr => (rhs.clone(), long, rhs.clone()),
+            TokenType::Sub if lhs_ptr && rhs_ptr => {
+                if lhs != rhs {
+                    sema.error(&self.data.span, format!("subtracting {} from {}", Sema::type_name(rhs), Sema::type_name(lhs)));
+                }
+                (long, lhs.clone(), rhs.clone())
+            }
+            TokenType::Sub if lhs_ptr => (lhs.clone(), lhs.clone(), long),
+            TokenType::Add | TokenType::Sub | TokenType::Mul | TokenType::Div | TokenType::Remainder => {
+                if lhs_ptr || rhs_ptr {
+                    sema.error(&self.data.span, format!("invalid operands to '{}': {} and {}", op, Sema::type_name(lhs), Sema::type_name(rhs)));
+                    return unchanged;
+                }
+                let common = Sema::common_type(lhs, rhs);
+                (common.clone(), common.clone(), common)
+            }
+            // comparisons, pointers have to point at the same type and the
+            // only integer a pointer is compared with is 0
+            _ if lhs_ptr && rhs_ptr => {
+                if lhs != rhs {
+                    sema.error(&self.data.span, format!("comparison of distinct pointer types: {} and {}", Sema::type_name(lhs), Sema::type_name(rhs)));
+                }
+                (Sema::bool_type(), lhs.clone(), lhs.clone())
+            }
+            _ if lhs_ptr || rhs_ptr => {
+                if !(lhs_ptr && rhs_null || rhs_ptr && lhs_null) {
+                    sema.error(&self.data.span, format!("comparison between pointer and integer: {} and {}", Sema::type_name(lhs), Sema::type_name(rhs)));
+                }
+                let ptr = if lhs_ptr { lhs } else { rhs };
+                (Sema::bool_type(), ptr.clone(), ptr.clone())
+            }
+            _ => {
+                let common = Sema::common_type(lhs, rhs);
+                (Sema::bool_type(), common.clone(), common)
+            }
+        }
+    }
+}
+
+impl Sema {
+    // type of field `name` of the struct `var` is an instance of, `.` takes a
+    // struct and `->` a pointer to one
+    pub(super) fn get_field(&mut self, var: &Token, ty: &TypeInfo, pointer_depth: u32, name: &str) -> Option<TypeInfo> {
+        let var_name = var.value.as_deref().unwrap_or_default();
+        if ty.var_type != TokenType::Struct || ty.pointer_depth != pointer_depth {
+            let expected = if pointer_depth == 0 { "a struct" } else { "a pointer to a struct" };
+            self.error(&var.span, format!("{} is {} not {}", var_name, Sema::type_name(ty), expected));
+            return None;
+        }
+        let struct_name = ty.struct_name.clone().unwrap_or_default();
+        let Some(elements) = self.structs.get(&struct_name) else {
+            self.error(&var.span, format!("no struct with name: {}", struct_name));
+            return None;
+        };
+        match elements.get(name) {
+            Some(field) => Some(Sema::field_type(field)),
+            None => {
+                self.error(&var.span, format!("struct {} has no field: {}", struct_name, name));
+                None
+            }
+        }
+    }
+}
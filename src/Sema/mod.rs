@@ -0,0 +1,657 @@
+//! Semantic analysis.
+//!
+//! Runs over the parsed [`Stmt`] tree before [`crate::Gen::Gen`] and does all
+//! the name resolution and type checking, so code generation only has to
+//! emit instructions. Every identifier is looked up in the symbol table and
+//! every node of every expression gets its type through
+//! [`RpnExpr::set_ty`]. Errors don't stop the pass, all of them are
+//! collected and returned together.
+//!
+//! Integers follow the usual arithmetic conversions of C: char and short
+//! are promoted to int, and the operands of a binary operator are brought to
+//! a common type. Every place a value changes type gets an explicit
+//! [`Convert`] node, so codegen never has to guess how to extend a value.
+//!
+//! Enumerators are replaced with their value while typing an expression.
+//! The value has the type of its enum, which converts to an integer freely
+//! but only takes values of the same enum, or a cast.
+
+use std::collections::HashMap;
+
+use crate::Ir::expr::{Convert, RpnExpr};
+use crate::Ir::r#gen::FuncData;
+use crate::Ir::sema::{EnumConstant, Symbol, SymbolId};
+use crate::Ir::stmt::{Arg, StructArg, TypeInfo};
+use crate::Ir::Stmt;
+use crate::Tokenizer::{Span, Token, TokenType};
+
+mod expr;
+mod stmt;
+
+// what a file defines and what it calls that has to come from somewhere
+// else, for the checks across the files compiled together
+pub struct Linkage {
+    // functions defined in the file, with whether they are exported
+    defined: HashMap<String, (bool, Span)>,
+    // functions only declared in the file, with the first call
+    external_calls: Vec<(String, Span)>,
+    // every function the file calls
+    called: Vec<String>,
+}
+
+pub struct Sema {
+    // every variable of the file, nodes refer to them by index
+    symbols: Vec<Symbol>,
+    // names visible at the current point, innermost scope last
+    scopes: Vec<HashMap<String, SymbolId>>,
+    // variables of the current function whose scope already ended
+    out_of_scope: HashMap<String, SymbolId>,
+    functions: HashMap<String, FuncData>,
+    // functions called anywhere in the file, with the first call
+    called: HashMap<String, Span>,
+    structs: HashMap<String, HashMap<String, StructArg>>,
+    // where every enum was declared
+    enums: HashMap<String, Span>,
+    enumerators: HashMap<String, EnumConstant>,
+    // function whose body is being checked
+    current_func: String,
+    // switches and loops around the stmt being checked, a break needs one
+    break_depth: u32,
+    // loops around the stmt being checked, a continue needs one
+    loop_depth: u32,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+    // warn about assignments that drop bits of a value
+    warn_narrowing: bool,
+}
+
+impl Sema {
+    pub fn new(warn_narrowing: bool) -> Sema {
+        Sema {
+            symbols: Vec::new(),
+            scopes: Vec::new(),
+            out_of_scope: HashMap::new(),
+            functions: HashMap::new(),
+            called: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            enumerators: HashMap::new(),
+            current_func: String::new(),
+            break_depth: 0,
+            loop_depth: 0,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            warn_narrowing,
+        }
+    }
+
+    // checks the whole file, on success every expression in it is typed
+    pub fn check(&mut self, ast: &mut [Stmt]) -> Result<(), Vec<String>> {
+        self.collect_decls(ast);
+        for i in ast.iter_mut() {
+            self.check_stmt(i);
+        }
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // warnings of the last check, they don't stop the compilation
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // the functions the last checked file shares with the other files
+    pub fn linkage(&self, ast: &[Stmt]) -> Linkage {
+        let defined: HashMap<String, (bool, Span)> = ast.iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::InitFunc(v) => Some((v.name.value.clone().unwrap(), (v.exported, v.name.span.clone()))),
+                _ => None,
+            })
+            .collect();
+        let mut external_calls: Vec<(String, Span)> = self.called.iter()
+            .filter(|(name, _)| !defined.contains_key(*name))
+            .map(|(name, span)| (name.clone(), span.clone()))
+            .collect();
+        external_calls.sort_by_key(|(_, span)| (span.line, span.col));
+        Linkage { defined, external_calls, called: self.called.keys().cloned().collect() }
+    }
+
+    // a function without export is a local label of its object, a call
+    // from another file would only fail to link
+    pub fn check_linkage(units: &[Linkage]) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        for (index, unit) in units.iter().enumerate() {
+            for (name, span) in unit.external_calls.iter() {
+                let definition = units.iter().enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .find_map(|(_, other)| other.defined.get(name));
+                if let Some((false, at)) = definition {
+                    errors.push(format!("{}: call to {}, which is defined at {} without export", span, name, at));
+                }
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    // functions only their own file sees that no file calls, the ones
+    // another file calls are an error of check_linkage instead
+    pub fn unused_functions(units: &[Linkage]) -> Vec<String> {
+        let mut warnings: Vec<(Span, String)> = Vec::new();
+        for unit in units.iter() {
+            for (name, (exported, span)) in unit.defined.iter() {
+                if !exported && name != "main" && !units.iter().any(|other| other.called.contains(name)) {
+                    warnings.push((span.clone(), format!("{}: unused function: {}", span, name)));
+                }
+            }
+        }
+        warnings.sort_by(|(a, _), (b, _)| (&a.file, a.line, a.col).cmp(&(&b.file, b.line, b.col)));
+        warnings.into_iter().map(|(_, warning)| warning).collect()
+    }
+
+    fn error(&mut self, span: &Span, msg: String) {
+        let error = format!("{}: {}", span, msg);
+        // a declaration and its implicit initializer can hit the same problem
+        if self.errors.last() != Some(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    fn warning(&mut self, span: &Span, msg: String) {
+        self.warnings.push(format!("{}: {}", span, msg));
+    }
+
+    fn collect_decls(&mut self, ast: &[Stmt]) {
+        for i in ast.iter() {
+            match i {
+                Stmt::InitFunc(v) => {
+                    let res = FuncData {
+                        return_type: v.return_type.clone(),
+                        args: v.args.clone(),
+                        variadic: false,
+                        is_extern: false,
+                    };
+                    self.declare_func(&v.name, res);
+                }
+                Stmt::FuncDecl(v) => {
+                    let res = FuncData {
+                        return_type: v.return_type.clone(),
+                        args: v.args.clone(),
+                        variadic: v.variadic,
+                        is_extern: true,
+                    };
+                    self.declare_func(&v.name, res);
+                }
+                Stmt::InitStruct(v) => {
+                    if self.structs.contains_key(&v.name) {
+                        self.error(&v.span, format!("redefinition of struct: {}", v.name));
+                        continue;
+                    }
+                    self.structs.insert(v.name.clone(), v.elements.clone());
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    // every declaration and the definition of a function have to agree
+    // on the return type and the arg types, arg names can differ
+    fn declare_func(&mut self, name_token: &Token, new: FuncData) {
+        let name = name_token.value.clone().unwrap();
+        let Some(prev) = self.functions.get(&name) else {
+            self.functions.insert(name, new);
+            return;
+        };
+        if !prev.is_extern && !new.is_extern {
+            self.error(&name_token.span, format!("redefinition of function: {}", name));
+            return;
+        }
+        let same_return = prev.return_type == new.return_type;
+        let same_args = prev.args.len() == new.args.len()
+            && prev.args.iter().zip(new.args.iter()).all(|(a, b)| Sema::arg_type(a) == Sema::arg_type(b));
+        if !same_return || !same_args || prev.variadic != new.variadic {
+            let msg = format!("conflicting declarations of function {}:\n    {}\n    {}",
+                name,
+                crate::Gen::Gen::c_prototype(&name, &prev.return_type, &prev.args, prev.variadic).unwrap_or_else(|error| error),
+                crate::Gen::Gen::c_prototype(&name, &new.return_type, &new.args, new.variadic).unwrap_or_else(|error| error),
+            );
+            self.error(&name_token.span, msg);
+            return;
+        }
+        // a definition in this file wins over the declaration
+        if !new.is_extern {
+            self.functions.insert(name, new);
+        }
+    }
+
+    fn arg_type(arg: &Arg) -> TypeInfo {
+        TypeInfo {
+            var_type: arg.arg_type.token,
+            pointer_depth: arg.pointer_depth,
+            struct_name: arg.struct_name.clone(),
+        }
+    }
+
+    fn field_type(field: &StructArg) -> TypeInfo {
+        TypeInfo {
+            var_type: field.arg_type.token,
+            pointer_depth: field.pointer_depth,
+            struct_name: None,
+        }
+    }
+
+    fn is_integer(ty: &TypeInfo) -> bool {
+        ty.pointer_depth == 0 && Sema::int_rank(ty.var_type) > 0
+    }
+
+    fn is_unsigned(token: TokenType) -> bool {
+        matches!(token, TokenType::Bool | TokenType::UCharType | TokenType::UShortType | TokenType::UIntType | TokenType::ULongType)
+    }
+
+    fn int_rank(token: TokenType) -> u32 {
+        match token {
+            TokenType::Bool => 1,
+            TokenType::CharType | TokenType::UCharType => 2,
+            TokenType::ShortType | TokenType::UShortType => 3,
+            TokenType::IntType | TokenType::UIntType | TokenType::Enum => 4,
+            TokenType::LongType | TokenType::ULongType => 5,
+            _ => 0,
+        }
+    }
+
+    fn int_size(token: TokenType) -> u32 {
+        match Sema::int_rank(token) {
+            1 | 2 => 1,
+            3 => 2,
+            4 => 4,
+            _ => 8,
+        }
+    }
+
+    fn to_unsigned(token: TokenType) -> TokenType {
+        match token {
+            TokenType::CharType => TokenType::UCharType,
+            TokenType::ShortType => TokenType::UShortType,
+            TokenType::IntType => TokenType::UIntType,
+            TokenType::LongType => TokenType::ULongType,
+            other => other,
+        }
+    }
+
+    // the bits of `value` as a `to` holds them, sign extended when it's signed
+    fn wrap(value: i128, to: TokenType) -> i64 {
+        let bits = Sema::int_size(to) * 8;
+        let value = value & ((1i128 << bits) - 1);
+        if !Sema::is_unsigned(to) && value >= 1i128 << (bits - 1) {
+            (value - (1i128 << bits)) as i64
+        } else {
+            value as i64
+        }
+    }
+
+    // bool, char and short, signed or not, fit in an int, and enums are ints
+    fn promote(ty: &TypeInfo) -> TypeInfo {
+        if Sema::int_rank(ty.var_type) < Sema::int_rank(TokenType::IntType) || ty.var_type == TokenType::Enum {
+            return Sema::int_type();
+        }
+        ty.clone()
+    }
+
+    // the type both operands of an arithmetic operator are converted to
+    fn common_type(lhs: &TypeInfo, rhs: &TypeInfo) -> TypeInfo {
+        let lhs = Sema::promote(lhs);
+        let rhs = Sema::promote(rhs);
+        if lhs.var_type == rhs.var_type {
+            return lhs;
+        }
+        let lhs_rank = Sema::int_rank(lhs.var_type);
+        let rhs_rank = Sema::int_rank(rhs.var_type);
+        if Sema::is_unsigned(lhs.var_type) == Sema::is_unsigned(rhs.var_type) {
+            return if lhs_rank > rhs_rank { lhs } else { rhs };
+        }
+        let (unsigned, signed) = if Sema::is_unsigned(lhs.var_type) { (lhs, rhs) } else { (rhs, lhs) };
+        let unsigned_rank = Sema::int_rank(unsigned.var_type);
+        let signed_rank = Sema::int_rank(signed.var_type);
+        if unsigned_rank >= signed_rank {
+            unsigned
+        }
+        // a long holds every unsigned int
+        else if Sema::int_size(signed.var_type) > Sema::int_size(unsigned.var_type) {
+            signed
+        }
+        else {
+            TypeInfo { var_type: Sema::to_unsigned(signed.var_type), ..signed }
+        }
+    }
+
+    // anything an if or a loop can test
+    fn is_scalar(ty: &TypeInfo) -> bool {
+        ty.pointer_depth > 0 || Sema::is_integer(ty)
+    }
+
+    fn bool_type() -> TypeInfo {
+        TypeInfo { var_type: TokenType::Bool, pointer_depth: 0, struct_name: None }
+    }
+
+    fn int_type() -> TypeInfo {
+        TypeInfo { var_type: TokenType::IntType, pointer_depth: 0, struct_name: None }
+    }
+
+    fn type_name(ty: &TypeInfo) -> String {
+        let base = match ty.var_type {
+            TokenType::IntType => "int".to_string(),
+            TokenType::CharType => "char".to_string(),
+            TokenType::ShortType => "short".to_string(),
+            TokenType::LongType => "long".to_string(),
+            TokenType::UCharType => "unsigned char".to_string(),
+            TokenType::UShortType => "unsigned short".to_string(),
+            TokenType::UIntType => "unsigned int".to_string(),
+            TokenType::ULongType => "unsigned long".to_string(),
+            TokenType::Bool => "bool".to_string(),
+            TokenType::Void => "void".to_string(),
+            TokenType::Struct => format!("struct {}", ty.struct_name.as_deref().unwrap_or("?")),
+            TokenType::Enum => format!("enum {}", ty.struct_name.as_deref().unwrap_or("?")),
+            other => format!("{:?}", other),
+        };
+        format!("{}{}", base, "*".repeat(ty.pointer_depth as usize))
+    }
+
+    // the struct or enum a type names has to exist
+    fn check_tag(&mut self, span: &Span, ty: &TypeInfo) {
+        let Some(name) = &ty.struct_name else {
+            return;
+        };
+        match ty.var_type {
+            TokenType::Struct if !self.structs.contains_key(name) => {
+                self.error(span, format!("no struct with name: {}", name));
+            }
+            TokenType::Enum if !self.enums.contains_key(name) => {
+                self.error(span, format!("no enum with name: {}", name));
+            }
+            _ => {}
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    // resolves a use of a variable, reporting it when there's no such variable
+    fn resolve(&mut self, token: &Token) -> Option<(SymbolId, Symbol)> {
+        let res = self.resolve_target(token);
+        if let Some((id, _)) = res {
+            self.symbols[id].used = true;
+        }
+        res
+    }
+
+    // a variable that is only stored to, its value isn't used
+    fn resolve_target(&mut self, token: &Token) -> Option<(SymbolId, Symbol)> {
+        let name = token.value.as_deref().unwrap_or_default();
+        if let Some(id) = self.lookup(name) {
+            return Some((id, self.symbols[id].clone()));
+        }
+        match self.out_of_scope.get(name) {
+            Some(id) => {
+                let msg = format!("variable used outside its scope: {}, declared at {}", name, self.symbols[*id].span);
+                self.error(&token.span, msg);
+            }
+            None => self.error(&token.span, format!("use of undeclared variable: {}", name)),
+        }
+        None
+    }
+
+    // adds a variable to the innermost scope, shadowing the ones of the
+    // outer scopes with the same name
+    fn declare(&mut self, token: &Token, ty: TypeInfo, is_array: bool) -> SymbolId {
+        let name = token.value.clone().unwrap();
+        let id = self.symbols.len();
+        self.symbols.push(Symbol { name: name.clone(), ty, is_array, span: token.span.clone(), used: false });
+        let Some(scope) = self.scopes.last_mut() else {
+            self.error(&token.span, format!("variable outside of a function: {}", name));
+            return id;
+        };
+        if let Some(prev) = scope.insert(name.clone(), id) {
+            let msg = format!("redefinition of variable: {}, first declared at {}", name, self.symbols[prev].span);
+            self.error(&token.span, msg);
+        }
+        id
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            let mut unused: Vec<SymbolId> = scope.values().copied().filter(|id| !self.symbols[*id].used).collect();
+            unused.sort();
+            for id in unused {
+                let (span, name) = (self.symbols[id].span.clone(), self.symbols[id].name.clone());
+                self.warning(&span, format!("unused variable: {}", name));
+            }
+            for (name, id) in scope {
+                // still reachable through an outer variable it shadowed
+                if self.lookup(&name).is_none() {
+                    self.out_of_scope.insert(name, id);
+                }
+            }
+        }
+    }
+
+    // warns about the first stmt after a return, break or continue that no
+    // case label makes reachable again
+    fn warn_unreachable(&mut self, data: &[Stmt], labels: &[usize]) {
+        let mut dead = false;
+        for (index, stmt) in data.iter().enumerate() {
+            if labels.contains(&index) {
+                dead = false;
+            }
+            if matches!(stmt, Stmt::OpenScope(_) | Stmt::CloseScope(_)) {
+                continue;
+            }
+            if dead {
+                match Sema::stmt_span(stmt) {
+                    Some(span) => self.warning(&span, "unreachable statement".to_string()),
+                    None => self.warnings.push(format!("in {}: unreachable statement", self.current_func)),
+                }
+                dead = false;
+                continue;
+            }
+            dead = matches!(stmt, Stmt::Ret(_) | Stmt::Break(_) | Stmt::Continue(_));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::Parser::Parser;
+    use crate::Tokenizer::Tokenizer;
+
+    use super::{Linkage, Sema};
+
+    // the linkage of a file that checks without errors
+    fn linkage(file: &str, src: &str) -> Linkage {
+        let mut tokenizer = Tokenizer::new(src.to_string(), Rc::from(file));
+        tokenizer.tokenize();
+        let mut ast = Parser::new(tokenizer.m_res).parse();
+        let mut sema = Sema::new(false);
+        sema.check(&mut ast).expect("sema errors");
+        sema.linkage(&ast)
+    }
+
+    fn check(src: &str) -> Result<(), Vec<String>> {
+        let mut tokenizer = Tokenizer::new(src.to_string(), Rc::from("test.v"));
+        tokenizer.tokenize();
+        let mut ast = Parser::new(tokenizer.m_res).parse();
+        Sema::new(false).check(&mut ast)
+    }
+
+    fn errors(src: &str) -> Vec<String> {
+        check(src).unwrap_err()
+    }
+
+    // the warnings of a file that checks, narrowing included
+    fn warnings(src: &str) -> Vec<String> {
+        let mut tokenizer = Tokenizer::new(src.to_string(), Rc::from("test.v"));
+        tokenizer.tokenize();
+        let mut ast = Parser::new(tokenizer.m_res).parse();
+        let mut sema = Sema::new(true);
+        sema.check(&mut ast).expect("sema errors");
+        sema.take_warnings()
+    }
+
+    #[test]
+    fn struct_redefinition() {
+        let src = "struct P { int x; };\nstruct P { int y; };\nint main() { return 0; }";
+        assert_eq!(errors(src), vec!["test.v:2:8: redefinition of struct: P"]);
+    }
+
+    #[test]
+    fn struct_arg_by_value() {
+        let src = "struct P { int x; };\nint f(struct P p) { return 1; }\nint main() { return 0; }";
+        assert_eq!(errors(src), vec!["test.v:2:16: struct arg p is passed by value, pass a pointer"]);
+        let src = "struct P { int x; };\nint f(struct P* p) { p->x = 1; return 1; }\nint main() { return 0; }";
+        assert_eq!(check(src), Ok(()));
+    }
+
+    #[test]
+    fn call_to_function_of_other_file() {
+        let lib = linkage("a.v", "int helper(int x) { return x; }\nexport int shared(int x) { return x; }\n");
+        let main = linkage("b.v", "int helper(int x);\nint shared(int x);\nint main() { return shared(1) + helper(2); }\n");
+        let errors = Sema::check_linkage(&[lib, main]).unwrap_err();
+        assert_eq!(errors, vec!["b.v:3:33: call to helper, which is defined at a.v:1:5 without export".to_string()]);
+    }
+
+    #[test]
+    fn shadowing_and_sibling_scopes() {
+        // only the outer x of f is unused, the inner ones shadow it
+        let src = "int f() { int x = 1; { int x = 2; x = x + 1; } { int x = 3; return x; } }\nint g() { int x = 4; return x; }\nint main() { return f() + g(); }";
+        assert_eq!(warnings(src), vec!["test.v:1:15: unused variable: x"]);
+        let src = "int main() {\n{ int y = 1; }\ny = 2;\nint x = 1;\nint x = 2;\nreturn z; }";
+        let expected = [
+            "test.v:3:1: variable used outside its scope: y, declared at test.v:2:7",
+            "test.v:5:5: redefinition of variable: x, first declared at test.v:4:5",
+            "test.v:6:8: use of undeclared variable: z",
+        ];
+        assert_eq!(errors(src), expected);
+    }
+
+    #[test]
+    fn usual_arithmetic_conversions() {
+        // the narrowing warnings name the type each sum was done in, widening
+        // w doesn't warn
+        let src = "int main() { short s = 1; unsigned int u = 2; int i = -1; long l = 3; unsigned long ul = 4;\n\
+            char a = s + s;\nchar b = u + i;\nchar c = u + l;\nchar d = ul + l;\nlong w = i;\nint f = u;\n\
+            return a + b + c + d + w + f; }";
+        let expected = [
+            "test.v:2:6: conversion from int to char may change the value",
+            "test.v:3:6: conversion from unsigned int to char may change the value",
+            "test.v:4:6: conversion from long to char may change the value",
+            "test.v:5:6: conversion from unsigned long to char may change the value",
+            "test.v:7:5: conversion from unsigned int to int may change the value",
+            // w makes the sum a long
+            "test.v:8:1: conversion from long to int may change the value",
+        ];
+        assert_eq!(warnings(src), expected);
+        let src = "int main() { int x = 1; int* p = &x;\nint y = p;\nlong* q = x;\nreturn y + *q; }";
+        assert_eq!(errors(src), vec!["test.v:2:5: cannot assign int* to int", "test.v:3:7: cannot assign int to long*"]);
+    }
+
+    #[test]
+    fn bool_conditions() {
+        // any scalar is a condition, not only a bool
+        let src = "int main() { bool b = true; bool f = false; int x = 2; int* p = &x;\n\
+            while (x) { x = x - 1; }\nif (p) { x = 1; }\nif (f) { return 2; }\nif (b) { return x; }\nreturn 0; }";
+        assert_eq!(check(src), Ok(()));
+        let src = "struct P { int x; };\nint main() { struct P s; struct P* sp = &s;\nif (*sp) { return 1; }\nwhile (*sp) { return 2; }\nreturn 0; }";
+        assert_eq!(errors(src), vec!["test.v:3:6: condition is struct P", "test.v:4:9: condition is struct P"]);
+    }
+
+    #[test]
+    fn enum_constants() {
+        let src = "enum Color { Red, Green = 5, Blue };\n\
+            int main() { enum Color c = Blue; switch (c) { case Green: return 1; case Blue: return 2; } return sizeof(enum Color) + Red; }";
+        assert_eq!(check(src), Ok(()));
+        let src = "enum Color { Red, Green = 5, Blue };\nint f(enum Color c) { return c; }\n\
+            int main() { int x = 1;\nenum Color c = x;\nenum Shape s = Red;\nreturn f(x) + c + s; }";
+        let expected = [
+            "test.v:4:12: cannot assign int to enum Color",
+            "test.v:5:12: no enum with name: Shape",
+            "test.v:5:12: cannot assign enum Color to enum Shape",
+            "test.v:6:8: arg 1 of f is enum Color but int was passed",
+        ];
+        assert_eq!(errors(src), expected);
+    }
+
+    #[test]
+    fn duplicate_case_values() {
+        let src = "int main() { int x = 3; int r = 0; switch (x) { case 1: r = 1; case 2: r = r + 2; break; default: r = 9; } return r; }";
+        assert_eq!(check(src), Ok(()));
+        // Green is 5 too
+        let src = "enum Color { Red, Green = 5, Blue };\nint main() { int x = 3; int y = 1; switch (x) {\n\
+            case 5: return 1;\ncase Green: return 2;\ncase y: return 3;\n} return 0; }";
+        let expected = ["test.v:4:1: duplicate case value: 5, first used at test.v:3:1", "test.v:5:1: case label is not an integer constant"];
+        assert_eq!(errors(src), expected);
+    }
+
+    #[test]
+    fn do_while_loops() {
+        let src = "int main() { int i = 0; do { i = i + 1; if (i == 2) { continue; } if (i > 5) { break; } } while (i < 10); return i; }";
+        assert_eq!(check(src), Ok(()));
+        // the condition comes after the body but can't see into it
+        let src = "int main() { do { int k = 1; } while (k < 3);\nreturn 0; }";
+        assert_eq!(errors(src), vec!["test.v:1:39: variable used outside its scope: k, declared at test.v:1:23"]);
+    }
+
+    #[test]
+    fn optional_for_clauses() {
+        let src = "int main() { int n = 0; for (;;) { break; }\n\
+            for (int i = 0, j = 10; i < j; i += 1) { n = n + j; }\n\
+            int k = 0; for (; k < 3;) { k++; } for (k = 0; ; k++) { break; } return n; }";
+        assert_eq!(check(src), Ok(()));
+        // the init variable belongs to the loop
+        let src = "int main() { for (int i = 0; i < 3; i++) { }\nreturn i; }";
+        assert_eq!(errors(src), vec!["test.v:2:8: variable used outside its scope: i, declared at test.v:1:23"]);
+    }
+
+    #[test]
+    fn casts_between_scalars() {
+        let src = "int main() { int x = 5; int* p = &x; long a = (long)p; int* q = (int*)a; char c = (char)*q; return (int)c; }";
+        assert_eq!(check(src), Ok(()));
+        let src = "struct P { int x; };\nint main() { struct P s; struct P* sp = &s;\nint x = (int)*sp;\nint y = (struct P)x;\nreturn 0; }";
+        let expected = ["test.v:3:9: cannot cast struct P to int", "test.v:4:9: cannot cast to struct P", "test.v:4:5: cannot assign struct P to int"];
+        assert_eq!(errors(src), expected);
+    }
+
+    #[test]
+    fn pointer_comparisons() {
+        let src = "int main() { int x = 5; int* p = &x; int* q = p; if (p == q) { return 1; } if (p != 0) { return 2; } if (0 == p) { return 3; } return 0; }";
+        assert_eq!(check(src), Ok(()));
+        let src = "int main() { int x = 5; int* p = &x; char* c = 0;\nif (p == 1) { return 1; }\nif (p == c) { return 2; }\nif (x < p) { return 3; }\nreturn 0; }";
+        let expected = [
+            "test.v:2:7: comparison between pointer and integer: int* and int",
+            "test.v:3:7: comparison of distinct pointer types: int* and char*",
+            "test.v:4:7: comparison between pointer and integer: int and int*",
+        ];
+        assert_eq!(errors(src), expected);
+    }
+
+    #[test]
+    fn call_to_exported_or_extern_function() {
+        let lib = linkage("a.v", "export int shared(int x) { return x; }\n");
+        let main = linkage("b.v", "int shared(int x);\nextern int puts(char* s);\nint main() { puts(\"hi\"); return shared(1); }\n");
+        assert!(Sema::check_linkage(&[lib, main]).is_ok());
+    }
+
+    #[test]
+    fn unused_function_called_by_other_file() {
+        let lib = linkage("a.v", "int helper(int x) { return x; }\nint lonely(int x) { return x; }\n");
+        let main = linkage("b.v", "int helper(int x);\nint main() { return helper(2); }\n");
+        assert_eq!(Sema::unused_functions(&[lib, main]), vec!["a.v:2:5: unused function: lonely".to_string()]);
+    }
+}
@@ -0,0 +1,518 @@
+use super::*;
+
+use crate::Ir::stmt::*;
+
+impl Sema {
+    pub(super) fn check_stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::CreateVar(v) => v.check(self),
+            Stmt::OpenScope(_) => self.push_scope(),
+            Stmt::CloseScope(_) => self.pop_scope(),
+            Stmt::ChangeVar(v) => v.check(self),
+            Stmt::IfStmt(v) => v.check(self),
+            Stmt::WhileStmt(v) => v.check(self),
+            Stmt::ForStmt(v) => v.check(self),
+            Stmt::DoWhileStmt(v) => v.check(self),
+            Stmt::SwitchStmt(v) => v.check(self),
+            Stmt::Break(v) => {
+                if self.break_depth == 0 {
+                    self.error(&v.span, "break outside of a loop or a switch".to_string());
+                }
+            }
+            Stmt::Continue(v) => {
+                if self.loop_depth == 0 {
+                    self.error(&v.span, "continue outside of a loop".to_string());
+                }
+            }
+            Stmt::IncVar(v) => v.sym = self.check_step(&v.var),
+            Stmt::DecVar(v) => v.sym = self.check_step(&v.var),
+            Stmt::InitFunc(v) => v.check(self),
+            Stmt::FuncDecl(_) => {
+                // registered in collect_decls
+            }
+            Stmt::Ret(v) => v.check(self),
+            Stmt::FunctionCall(v) => {
+                self.check_call(&v.name, &mut v.args);
+            }
+            Stmt::AsmCode(v) => v.check(self),
+            Stmt::InitArray(v) => v.check(self),
+            Stmt::ChangeArrElement(v) => v.check(self),
+            Stmt::CreatePointer(v) => v.check(self),
+            Stmt::ChangePtrValue(v) => v.check(self),
+            Stmt::InitStruct(_) => {
+                // registered in collect_decls
+            }
+            Stmt::InitEnum(v) => v.check(self),
+            Stmt::CreateStruct(v) => v.check(self),
+            Stmt::ChangeStructValue(v) => v.check(self),
+            Stmt::ChangePtrStructValue(v) => v.check(self),
+        }
+    }
+
+    // `x++` and `x--`
+    fn check_step(&mut self, var: &Token) -> Option<SymbolId> {
+        let (id, symbol) = self.resolve(var)?;
+        if !Sema::is_integer(&symbol.ty) {
+            self.error(&var.span, format!("cannot increment or decrement {}", Sema::type_name(&symbol.ty)));
+        }
+        Some(id)
+    }
+
+    // a condition is true when it's not zero, it's turned into a bool
+    fn check_cond(&mut self, expr: &mut Vec<RpnExpr>) {
+        let ty = self.check_expr(expr);
+        let span = expr.first().map(Sema::expr_span);
+        if !Sema::is_scalar(&ty) {
+            let msg = format!("condition is {}", Sema::type_name(&ty));
+            match span {
+                Some(span) => self.error(&span, msg),
+                None => self.errors.push(msg),
+            }
+            return;
+        }
+        // a scalar condition isn't empty
+        self.convert(expr, &ty, &Sema::bool_type(), &span.unwrap());
+    }
+
+    // where the first token of an expression node came from
+    fn expr_span(expr: &RpnExpr) -> Span {
+        match expr {
+            RpnExpr::PushNum(v) => v.data.span.clone(),
+            RpnExpr::PushStr(v) => v.data.span.clone(),
+            RpnExpr::PushVar(v) => v.data.span.clone(),
+            RpnExpr::Operator(v) => v.data.span.clone(),
+            RpnExpr::Function(v) => v.name.span.clone(),
+            RpnExpr::Negative(v) => v.data.span.clone(),
+            RpnExpr::GetArrayValue(v) => v.name.span.clone(),
+            RpnExpr::Deref(v) => v.var.span.clone(),
+            RpnExpr::GetAddr(v) => v.var.span.clone(),
+            RpnExpr::GetSizeOf(v) => v.var.span.clone(),
+            RpnExpr::GetStructValue(v) => v.var_name.span.clone(),
+            RpnExpr::Convert(v) => v.span.clone(),
+            RpnExpr::Cast(v) => v.span.clone(),
+        }
+    }
+
+    // where a stmt starts, None for the ones without a token of their own
+    pub(super) fn stmt_span(stmt: &Stmt) -> Option<Span> {
+        match stmt {
+            Stmt::CreateVar(v) => Some(v.var.span.clone()),
+            Stmt::ChangeVar(v) => Some(v.var.span.clone()),
+            Stmt::IfStmt(v) => v.expr.first().map(Sema::expr_span),
+            Stmt::WhileStmt(v) => v.expr.first().map(Sema::expr_span),
+            Stmt::ForStmt(v) => v.expr1.iter().find_map(Sema::stmt_span)
+                .or_else(|| v.expr2.first().map(Sema::expr_span))
+                .or_else(|| v.data.iter().find_map(Sema::stmt_span)),
+            Stmt::DoWhileStmt(v) => v.data.iter().find_map(Sema::stmt_span).or_else(|| v.expr.first().map(Sema::expr_span)),
+            Stmt::SwitchStmt(v) => Some(v.span.clone()),
+            Stmt::Break(v) => Some(v.span.clone()),
+            Stmt::Continue(v) => Some(v.span.clone()),
+            Stmt::IncVar(v) => Some(v.var.span.clone()),
+            Stmt::DecVar(v) => Some(v.var.span.clone()),
+            Stmt::InitFunc(v) => Some(v.name.span.clone()),
+            Stmt::FuncDecl(v) => Some(v.name.span.clone()),
+            Stmt::Ret(v) => Some(v.span.clone()),
+            Stmt::FunctionCall(v) => Some(v.name.span.clone()),
+            Stmt::InitArray(v) => Some(v.name.span.clone()),
+            Stmt::ChangeArrElement(v) => Some(v.arr_name.span.clone()),
+            Stmt::CreatePointer(v) => Some(v.var.span.clone()),
+            Stmt::ChangePtrValue(v) => Some(v.var.span.clone()),
+            Stmt::InitEnum(v) => Some(v.name.span.clone()),
+            Stmt::InitStruct(v) => Some(v.span.clone()),
+            Stmt::CreateStruct(v) => Some(v.var_name.span.clone()),
+            Stmt::ChangeStructValue(v) => Some(v.struct_name.span.clone()),
+            Stmt::ChangePtrStructValue(v) => Some(v.struct_name.span.clone()),
+            Stmt::OpenScope(_) | Stmt::CloseScope(_) | Stmt::AsmCode(_) => None,
+        }
+    }
+
+    // an integer takes any integer, an enum only the same enum, a pointer takes the same pointer type,
+    // void* or a literal 0, anything else between pointers and integers
+    // needs a cast
+    pub(super) fn assignable(target: &TypeInfo, value: &TypeInfo, expr: &[RpnExpr]) -> bool {
+        if value == target {
+            return true;
+        }
+        if target.pointer_depth == 0 {
+            // only an enumerator of its own enum
+            target.var_type != TokenType::Enum && Sema::is_integer(target) && Sema::is_integer(value)
+        } else {
+            (Sema::is_integer(value) && Sema::expr_literal(expr) == Some(0))
+            || (value.pointer_depth > 0 && (value.var_type == TokenType::Void || target.var_type == TokenType::Void))
+        }
+    }
+
+    // converts the value of a whole expression to the type it's stored as
+    pub(super) fn convert(&mut self, expr: &mut Vec<RpnExpr>, from: &TypeInfo, to: &TypeInfo, span: &Span) {
+        // anything becomes a bool by comparing it with 0
+        if self.warn_narrowing && Sema::is_integer(from) && Sema::is_integer(to) && to.var_type != TokenType::Bool
+        && !Sema::fits(to.var_type, from.var_type, Sema::expr_literal(expr)) {
+            self.warning(span, format!("conversion from {} to {} may change the value", Sema::type_name(from), Sema::type_name(to)));
+        }
+        let end = expr.len();
+        Sema::convert_at(expr, end, from, to, span);
+    }
+
+    fn check_assign(&mut self, span: &Span, target: &TypeInfo, expr: &mut Vec<RpnExpr>, value: &TypeInfo) {
+        if !Sema::assignable(target, value, expr) {
+            self.error(span, format!("cannot assign {} to {}", Sema::type_name(value), Sema::type_name(target)));
+            return;
+        }
+        self.convert(expr, value, target, span);
+    }
+
+    fn check_field(&mut self, span: &Span, name: &str, field: &TypeInfo, expr: &mut Vec<RpnExpr>, value: &TypeInfo) {
+        if !Sema::assignable(field, value, expr) {
+            self.error(span, format!("cannot assign {} to field {} of type {}", Sema::type_name(value), name, Sema::type_name(field)));
+            return;
+        }
+        self.convert(expr, value, field, span);
+    }
+
+    fn check_body(&mut self, data: &mut [Stmt]) {
+        for i in data.iter_mut() {
+            self.check_stmt(i);
+        }
+        self.warn_unreachable(data, &[]);
+    }
+
+    // a break or a continue in it belongs to the loop
+    fn check_loop_body(&mut self, data: &mut [Stmt]) {
+        self.break_depth += 1;
+        self.loop_depth += 1;
+        self.check_body(data);
+        self.break_depth -= 1;
+        self.loop_depth -= 1;
+    }
+}
+
+impl CreateVar {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.stmt);
+        let var_type = TypeInfo { var_type: self.Type, pointer_depth: 0, struct_name: self.enum_name.clone() };
+        sema.check_tag(&self.var.span, &var_type);
+        if self.Type == TokenType::Void {
+            sema.error(&self.var.span, format!("variable declared void: {}", self.var.value.as_ref().unwrap()));
+        }
+        else {
+            sema.check_assign(&self.var.span, &var_type, &mut self.stmt, &ty);
+        }
+        self.sym = Some(sema.declare(&self.var, var_type, false));
+    }
+}
+
+impl CreatePointer {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.stmt);
+        let var_type = TypeInfo { var_type: self.type_, pointer_depth: self.pointer_depth, struct_name: None };
+        sema.check_assign(&self.var.span, &var_type, &mut self.stmt, &ty);
+        self.sym = Some(sema.declare(&self.var, var_type, false));
+    }
+}
+
+impl ChangeVar {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.stmt);
+        let Some((id, var)) = sema.resolve_target(&self.var) else {
+            return;
+        };
+        self.sym = Some(id);
+        if var.is_array {
+            sema.error(&self.var.span, format!("cannot assign to array: {}", self.var.value.as_ref().unwrap()));
+            return;
+        }
+        sema.check_assign(&self.var.span, &var.ty, &mut self.stmt, &ty);
+    }
+}
+
+impl ChangePtrValue {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.stmt);
+        let Some((id, var)) = sema.resolve(&self.var) else {
+            return;
+        };
+        self.sym = Some(id);
+        if var.ty.pointer_depth < self.pointer_depth {
+            sema.error(&self.var.span, format!("dereferencing {} which is {}", self.var.value.as_ref().unwrap(), Sema::type_name(&var.ty)));
+            return;
+        }
+        let target = TypeInfo { pointer_depth: var.ty.pointer_depth - self.pointer_depth, ..var.ty };
+        sema.check_assign(&self.var.span, &target, &mut self.stmt, &ty);
+    }
+}
+
+impl InitArray {
+    fn check(&mut self, sema: &mut Sema) {
+        match self.size.value.as_ref().and_then(|v| v.parse::<usize>().ok()) {
+            Some(size) if self.data.len() > size => {
+                sema.error(&self.name.span, format!("array {} of size {} initialized with {} values", self.name.value.as_ref().unwrap(), size, self.data.len()));
+            }
+            Some(_) => {}
+            None => sema.error(&self.size.span, "array size has to be a number".to_string()),
+        }
+        let ty = TypeInfo { var_type: self.arr_type.token, pointer_depth: 1, struct_name: None };
+        self.sym = Some(sema.declare(&self.name, ty, true));
+    }
+}
+
+impl ChangeArrElement {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.expr);
+        if self.element.token == TokenType::Var
+        && let Some((id, index)) = sema.resolve(&self.element) {
+            self.element_sym = Some(id);
+            if !Sema::is_integer(&index.ty) {
+                sema.error(&self.element.span, format!("array index is {}", Sema::type_name(&index.ty)));
+            }
+        }
+        let Some((id, var)) = sema.resolve(&self.arr_name) else {
+            return;
+        };
+        self.sym = Some(id);
+        if !var.is_array {
+            sema.error(&self.arr_name.span, format!("{} is not an array", self.arr_name.value.as_ref().unwrap()));
+            return;
+        }
+        let target = TypeInfo { pointer_depth: var.ty.pointer_depth - 1, ..var.ty };
+        sema.check_assign(&self.arr_name.span, &target, &mut self.expr, &ty);
+    }
+}
+
+impl IfStmt {
+    fn check(&mut self, sema: &mut Sema) {
+        sema.check_cond(&mut self.expr);
+        sema.check_body(&mut self.data);
+        sema.check_body(&mut self.else_data);
+    }
+}
+
+impl WhileStmt {
+    fn check(&mut self, sema: &mut Sema) {
+        sema.check_cond(&mut self.expr);
+        sema.check_loop_body(&mut self.data);
+    }
+}
+
+impl DoWhileStmt {
+    fn check(&mut self, sema: &mut Sema) {
+        // the body is a scope of its own, the condition can't see into it
+        sema.check_loop_body(&mut self.data);
+        sema.check_cond(&mut self.expr);
+    }
+}
+
+impl ForStmt {
+    fn check(&mut self, sema: &mut Sema) {
+        // the loop var lives in a scope around the body
+        sema.push_scope();
+        sema.check_body(&mut self.expr1);
+        if !self.expr2.is_empty() {
+            sema.check_cond(&mut self.expr2);
+        }
+        sema.check_loop_body(&mut self.data);
+        sema.check_body(&mut self.expr3);
+        sema.pop_scope();
+    }
+}
+
+impl SwitchStmt {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.expr);
+        // the value is compared as an int at least
+        let switch_type = Sema::promote(&ty);
+        if Sema::is_integer(&ty) {
+            sema.convert(&mut self.expr, &ty, &switch_type, &self.span);
+        } else {
+            sema.error(&self.span, format!("switch on {}", Sema::type_name(&ty)));
+        }
+        let mut values: HashMap<i64, Span> = HashMap::new();
+        let mut default: Option<Span> = None;
+        for case in self.cases.iter_mut() {
+            let Some(expr) = case.expr.as_mut() else {
+                if let Some(prev) = &default {
+                    let msg = format!("multiple default labels in one switch, first one at {}", prev);
+                    sema.error(&case.span, msg);
+                }
+                default = Some(case.span.clone());
+                continue;
+            };
+            let case_type = sema.check_expr(expr);
+            let literal = Sema::expr_literal(expr).filter(|_| Sema::is_integer(&case_type));
+            let Some(value) = literal else {
+                sema.error(&case.span, "case label is not an integer constant".to_string());
+                continue;
+            };
+            let value = Sema::wrap(value, switch_type.var_type);
+            if let Some(prev) = values.get(&value) {
+                let msg = format!("duplicate case value: {}, first used at {}", value, prev);
+                sema.error(&case.span, msg);
+                continue;
+            }
+            values.insert(value, case.span.clone());
+            case.value = Some(value);
+        }
+        sema.break_depth += 1;
+        for i in self.data.iter_mut() {
+            sema.check_stmt(i);
+        }
+        // a case label makes the stmts after a break reachable again
+        let labels: Vec<usize> = self.cases.iter().map(|case| case.pos).collect();
+        sema.warn_unreachable(&self.data, &labels);
+        sema.break_depth -= 1;
+    }
+}
+
+impl InitFunc {
+    fn check(&mut self, sema: &mut Sema) {
+        sema.current_func = self.name.value.clone().unwrap();
+        sema.push_scope();
+        for arg in self.args.iter_mut() {
+            sema.check_tag(&arg.name.span, &Sema::arg_type(arg));
+            if arg.pointer_depth == 0 && arg.arg_type.token == TokenType::Struct {
+                sema.error(
+                    &arg.name.span,
+                    format!(
+                        "struct arg {} is passed by value, pass a pointer",
+                        arg.name.value.as_ref().unwrap()
+                    ),
+                );
+            }
+            let id = sema.declare(&arg.name, Sema::arg_type(arg), false);
+            // an arg is part of the signature even when the body ignores it
+            sema.symbols[id].used = true;
+            arg.sym = Some(id);
+        }
+        // the braces of the body share the scope of the args, so a local
+        // can't redeclare an arg
+        let len = self.data.len();
+        sema.check_body(&mut self.data[1..len - 1]);
+        sema.pop_scope();
+        sema.out_of_scope.clear();
+        sema.current_func = String::new();
+    }
+}
+
+impl Ret {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.expr);
+        let Some(func_data) = sema.functions.get(&self.func_name) else {
+            sema.error(&self.span, "return outside of a function".to_string());
+            return;
+        };
+        let return_type = func_data.return_type.clone();
+        if !Sema::assignable(&return_type, &ty, &self.expr) {
+            sema.error(&self.span, format!("returning {} from {} which returns {}",
+                Sema::type_name(&ty), self.func_name, Sema::type_name(&return_type)));
+            return;
+        }
+        sema.convert(&mut self.expr, &ty, &return_type, &self.span);
+    }
+}
+
+impl AsmCode {
+    fn check(&mut self, sema: &mut Sema) {
+        for line in self.code.iter() {
+            // (var) is replaced with the stack slot of var
+            let mut rest = line.as_str();
+            while let Some(start) = rest.find('(') {
+                let end = rest[start..].find(')').map(|v| v + start).unwrap_or(rest.len());
+                let name = &rest[start + 1..end];
+                match sema.lookup(name) {
+                    Some(id) => {
+                        sema.symbols[id].used = true;
+                        self.vars.insert(name.to_string(), id);
+                    }
+                    None => {
+                        let msg = format!("in asm of {}: use of undeclared variable: {}", sema.current_func, name);
+                        sema.errors.push(msg);
+                    }
+                }
+                rest = &rest[(end + 1).min(rest.len())..];
+            }
+        }
+    }
+}
+
+impl CreateStruct {
+    fn check(&mut self, sema: &mut Sema) {
+        if !sema.structs.contains_key(&self.struct_name) {
+            sema.error(&self.var_name.span, format!("no struct with name: {}", self.struct_name));
+        }
+        let var_type = TypeInfo {
+            var_type: TokenType::Struct,
+            pointer_depth: self.pointer_depth,
+            struct_name: Some(self.struct_name.clone()),
+        };
+        if let Some(expr) = self.expr.as_mut() {
+            let ty = sema.check_expr(expr);
+            if self.pointer_depth == 0 {
+                sema.error(&self.var_name.span, format!("cannot copy a struct: {}", self.var_name.value.as_ref().unwrap()));
+            }
+            else if ty != var_type {
+                sema.error(&self.var_name.span, format!("cannot assign {} to {}", Sema::type_name(&ty), Sema::type_name(&var_type)));
+            }
+        }
+        self.sym = Some(sema.declare(&self.var_name, var_type, false));
+    }
+}
+
+impl ChangeStructValue {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.expr);
+        let Some((id, var)) = sema.resolve_target(&self.struct_name) else {
+            return;
+        };
+        self.sym = Some(id);
+        if let Some(field) = sema.get_field(&self.struct_name, &var.ty, 0, &self.value_name) {
+            sema.check_field(&self.struct_name.span, &self.value_name, &field, &mut self.expr, &ty);
+        }
+    }
+}
+
+impl ChangePtrStructValue {
+    fn check(&mut self, sema: &mut Sema) {
+        let ty = sema.check_expr(&mut self.expr);
+        let Some((id, var)) = sema.resolve(&self.struct_name) else {
+            return;
+        };
+        self.sym = Some(id);
+        if let Some(field) = sema.get_field(&self.struct_name, &var.ty, 1, &self.value_name) {
+            sema.check_field(&self.struct_name.span, &self.value_name, &field, &mut self.expr, &ty);
+        }
+    }
+}
+
+impl InitEnum {
+    // gives every enumerator its value, one more than the one before when
+    // it doesn't have one
+    fn check(&mut self, sema: &mut Sema) {
+        let name = self.name.value.clone().unwrap();
+        if let Some(prev) = sema.enums.get(&name) {
+            let msg = format!("redefinition of enum: {}, first declared at {}", name, prev);
+            sema.error(&self.name.span, msg);
+            return;
+        }
+        sema.enums.insert(name.clone(), self.name.span.clone());
+        let mut next: i128 = 0;
+        for i in self.enumerators.iter_mut() {
+            let enumerator = i.name.value.clone().unwrap();
+            let value = match &i.init {
+                Some(init) => Sema::literal_value(init, i.negative).unwrap_or_default(),
+                None => next,
+            };
+            next = value + 1;
+            let Ok(value) = i32::try_from(value) else {
+                sema.error(&i.name.span, format!("value of enumerator {} doesn't fit in an int: {}", enumerator, value));
+                continue;
+            };
+            i.value = Some(value as i64);
+            if let Some(prev) = sema.enumerators.get(&enumerator) {
+                let msg = format!("redefinition of enumerator: {}, first declared at {}", enumerator, prev.span);
+                sema.error(&i.name.span, msg);
+                continue;
+            }
+            let constant = EnumConstant { value: value as i64, enum_name: name.clone(), span: i.name.span.clone() };
+            sema.enumerators.insert(enumerator, constant);
+        }
+    }
+}
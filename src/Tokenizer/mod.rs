@@ -1,4 +1,5 @@
 use std::fmt;
+use std::rc::Rc;
 
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -7,10 +8,26 @@ pub enum TokenType {
     CharType,
     ShortType,
     LongType,
+    // `unsigned` folded into the type after it by the parser
+    UCharType,
+    UShortType,
+    UIntType,
+    ULongType,
+    Unsigned,
+    Bool,
+    // `true` and `false`, with 1 and 0 as their value
+    True,
+    False,
     Var,
     CharValue,
     Num,
     Eq,
+    // compound assignments, `x += e` is `x = x + (e)`
+    AddEq,
+    SubEq,
+    MulEq,
+    DivEq,
+    RemEq,
     Add,
     Mul,
     Sub,
@@ -32,6 +49,13 @@ pub enum TokenType {
     Or,
     While,
     For,
+    Do,
+    Continue,
+    Switch,
+    Case,
+    Default,
+    Break,
+    Colon,
     Inc,
     Dec,
     Void,
@@ -39,6 +63,7 @@ pub enum TokenType {
     Coma,
     String,
     Struct,
+    Enum,
     OpenBracket,
     Dot,
     CloseBracket,
@@ -46,19 +71,133 @@ pub enum TokenType {
     Address,
     Access,
     Semi,
+    Extern,
+    Ellipsis,
+    Export,
+    Inline,
+    NoInline,
+    Import,
+    Hash,
+    HashHash,
 }
+// where a token starts, lines and columns count from 1
+#[derive(Clone, Default)]
+pub struct Span {
+    pub file: Rc<str>,
+    pub line: u32,
+    pub col: u32,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+// the parse result dump prints every token, keep spans short there
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token: TokenType,
-    pub value: Option<String>
+    pub value: Option<String>,
+    pub span: Span,
+}
+
+impl Token {
+    // the source text of the token, used when the preprocessor turns
+    // tokens back into text
+    pub fn spelling(&self) -> String {
+        let text = match self.token {
+            TokenType::IntType => "int",
+            TokenType::CharType => "char",
+            TokenType::ShortType => "short",
+            TokenType::LongType => "long",
+            TokenType::UCharType => "unsigned char",
+            TokenType::UShortType => "unsigned short",
+            TokenType::UIntType => "unsigned int",
+            TokenType::ULongType => "unsigned long",
+            TokenType::Unsigned => "unsigned",
+            TokenType::Bool => "bool",
+            TokenType::True => "true",
+            TokenType::False => "false",
+            TokenType::Var | TokenType::Num => return self.value.clone().unwrap(),
+            TokenType::CharValue => {
+                let value: u8 = self.value.as_ref().unwrap().parse().unwrap();
+                return format!("'{}'", value as char);
+            }
+            TokenType::String => return format!("\"{}\"", self.value.as_ref().unwrap()),
+            TokenType::Eq => "=",
+            TokenType::AddEq => "+=",
+            TokenType::SubEq => "-=",
+            TokenType::MulEq => "*=",
+            TokenType::DivEq => "/=",
+            TokenType::RemEq => "%=",
+            TokenType::Add => "+",
+            TokenType::Mul => "*",
+            TokenType::Sub => "-",
+            TokenType::Div => "/",
+            TokenType::OpenParen => "(",
+            TokenType::CloseParen => ")",
+            TokenType::OpenScope => "{",
+            TokenType::CloseScope => "}",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::AsertEq => "==",
+            TokenType::NotEq => "!=",
+            TokenType::Not => "!",
+            TokenType::Less => "<",
+            TokenType::LessThan => "<=",
+            TokenType::More => ">",
+            TokenType::MoreThan => ">=",
+            TokenType::And => "and",
+            TokenType::Or => "or",
+            TokenType::While => "while",
+            TokenType::For => "for",
+            TokenType::Do => "do",
+            TokenType::Continue => "continue",
+            TokenType::Switch => "switch",
+            TokenType::Case => "case",
+            TokenType::Default => "default",
+            TokenType::Break => "break",
+            TokenType::Colon => ":",
+            TokenType::Inc => "++",
+            TokenType::Dec => "--",
+            TokenType::Void => "void",
+            TokenType::Return => "return",
+            TokenType::Coma => ",",
+            TokenType::Struct => "struct",
+            TokenType::Enum => "enum",
+            TokenType::OpenBracket => "[",
+            TokenType::Dot => ".",
+            TokenType::CloseBracket => "]",
+            TokenType::Remainder => "%",
+            TokenType::Address => "&",
+            TokenType::Access => "->",
+            TokenType::Semi => ";",
+            TokenType::Extern => "extern",
+            TokenType::Ellipsis => "...",
+            TokenType::Export => "export",
+            TokenType::Inline => "inline",
+            TokenType::NoInline => "noinline",
+            TokenType::Import => "import",
+            TokenType::Hash => "#",
+            TokenType::HashHash => "##",
+        };
+        text.to_string()
+    }
 }
 
 impl fmt::Display for Tokenizer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (index, token)  in self.m_res.iter().enumerate() {
             let _ = match &token.value {
-                Some(v) => write!(f, "Token {}: type: {:?}, value: \"{}\"\n", index, token.token, v),
-                None => write!(f, "Token {}: type: {:?}, value: None\n", index, token.token),
+                Some(v) => writeln!(f, "Token {}: type: {:?}, value: \"{}\" at {}", index, token.token, v, token.span),
+                None => writeln!(f, "Token {}: type: {:?}, value: None at {}", index, token.token, token.span),
             };
         }
         Ok(())
@@ -71,17 +210,25 @@ pub struct Tokenizer {
     m_src: Vec<char>,
     m_buf: String,
     pub m_res: Vec<Token>,
+    m_file: Rc<str>,
+    m_line: u32,
+    m_col: u32,
+    // start of the token being read
+    m_start: (u32, u32),
 }
 
 impl Tokenizer {
 
-    pub fn new(file: String) -> Self {
+    pub fn new(file: String, path: Rc<str>) -> Self {
         Tokenizer { 
             m_index: 0,
             m_src: file.chars().collect(),
             m_buf: String::new(),
             m_res: Vec::new(),
-
+            m_file: path,
+            m_line: 1,
+            m_col: 1,
+            m_start: (1, 1),
         }
     }
 
@@ -89,6 +236,7 @@ impl Tokenizer {
         let x = Token {
             token,
             value,
+            span: Span { file: self.m_file.clone(), line: self.m_start.0, col: self.m_start.1 },
         };
         self.m_res.push(x);
     }
@@ -96,8 +244,10 @@ impl Tokenizer {
 
     fn peek(&self, offset: usize) -> char {
         let pos: usize = self.m_index + offset;
+        // '\0' past the end, so a token can end the source
+        // (-D values and pasted tokens have no trailing newline)
         if pos >= self.m_src.len() {
-            panic!("Trying to peek more than m_src len");
+            return '\0';
         }
         self.m_src[pos]
     }
@@ -107,11 +257,19 @@ impl Tokenizer {
             panic!("Trying to consume more than m_src len");
         }
         self.m_index += 1;
-        self.m_src[self.m_index - 1]
+        let c = self.m_src[self.m_index - 1];
+        if c == '\n' {
+            self.m_line += 1;
+            self.m_col = 1;
+        } else {
+            self.m_col += 1;
+        }
+        c
     }
 
     pub fn tokenize(&mut self) {
-        while self.m_index <= self.m_src.len()  - 1 {
+        while self.m_index < self.m_src.len() {
+            self.m_start = (self.m_line, self.m_col);
             if self.peek(0).is_alphabetic() {
                 let v = self.consume();
                 self.m_buf.push(v);
@@ -124,15 +282,31 @@ impl Tokenizer {
                     "short" => self.push_token(TokenType::ShortType, None),
                     "long" => self.push_token(TokenType::LongType, None),
                     "char" => self.push_token(TokenType::CharType, None),
+                    "unsigned" => self.push_token(TokenType::Unsigned, None),
+                    "bool" => self.push_token(TokenType::Bool, None),
+                    "true" => self.push_token(TokenType::True, Some("1".to_string())),
+                    "false" => self.push_token(TokenType::False, Some("0".to_string())),
                     "if" => self.push_token(TokenType::If, None),
                     "else" => self.push_token(TokenType::Else, None),
                     "and" => self.push_token(TokenType::And, None),
                     "or" => self.push_token(TokenType::Or, None),
                     "while" => self.push_token(TokenType::While, None),
                     "for" => self.push_token(TokenType::For, None),
+                    "do" => self.push_token(TokenType::Do, None),
+                    "continue" => self.push_token(TokenType::Continue, None),
+                    "switch" => self.push_token(TokenType::Switch, None),
+                    "case" => self.push_token(TokenType::Case, None),
+                    "default" => self.push_token(TokenType::Default, None),
+                    "break" => self.push_token(TokenType::Break, None),
                     "void" => self.push_token(TokenType::Void, None),
                     "return" => self.push_token(TokenType::Return, None),
                     "struct" => self.push_token(TokenType::Struct, None),
+                    "enum" => self.push_token(TokenType::Enum, None),
+                    "extern" => self.push_token(TokenType::Extern, None),
+                    "export" | "pub" => self.push_token(TokenType::Export, None),
+                    "inline" => self.push_token(TokenType::Inline, None),
+                    "noinline" => self.push_token(TokenType::NoInline, None),
+                    "import" => self.push_token(TokenType::Import, None),
                     // we think its variable
                     _ => self.push_token(TokenType::Var, Some(self.m_buf.clone())),
                     }
@@ -152,7 +326,14 @@ impl Tokenizer {
             else {
                 let smth = self.consume();
                 match smth {
-                    '%' => self.push_token(TokenType::Remainder, None),
+                    '%' => {
+                        if self.peek(0) == '=' {
+                            self.consume();
+                            self.push_token(TokenType::RemEq, None);
+                        } else {
+                            self.push_token(TokenType::Remainder, None);
+                        }
+                    }
                     '\'' => {
                         let character = self.consume();
                         if character.is_ascii() {
@@ -163,7 +344,13 @@ impl Tokenizer {
                         self.consume();
                     } 
                     '.' => {
-                        self.push_token(TokenType::Dot, None);
+                        if self.peek(0) == '.' && self.peek(1) == '.' {
+                            self.consume();
+                            self.consume();
+                            self.push_token(TokenType::Ellipsis, None);
+                        } else {
+                            self.push_token(TokenType::Dot, None);
+                        }
                     }
                     '=' => {
                         if self.peek(0) == '=' {
@@ -174,10 +361,14 @@ impl Tokenizer {
                         }
                     },
                     ';' => self.push_token(TokenType::Semi, None),
+                    ':' => self.push_token(TokenType::Colon, None),
                     '+' => {
                             if self.peek(0) == '+' {
                                 self.push_token(TokenType::Inc, Some("++".to_string()));
                                 self.consume();
+                            } else if self.peek(0) == '=' {
+                                self.consume();
+                                self.push_token(TokenType::AddEq, None);
                             } else {
                                 self.push_token(TokenType::Add, Some('+'.to_string()))
                             }
@@ -191,13 +382,49 @@ impl Tokenizer {
                             if self.peek(0) == '-' {
                                 self.push_token(TokenType::Dec, Some("--".to_string()));
                                 self.consume();
+                            } else if self.peek(0) == '=' {
+                                self.consume();
+                                self.push_token(TokenType::SubEq, None);
                             } else {
                                 self.push_token(TokenType::Sub, Some('-'.to_string()))
                             }
                         },
-                    '&' => self.push_token(TokenType::Address, None),
-                    '*' => self.push_token(TokenType::Mul, Some('*'.to_string())),
-                    '/' => self.push_token(TokenType::Div, Some('/'.to_string())),
+                    '&' => {
+                        if self.peek(0) == '&' {
+                            self.consume();
+                            self.push_token(TokenType::And, None);
+                        } else {
+                            self.push_token(TokenType::Address, None);
+                        }
+                    }
+                    '|' if self.peek(0) == '|' => {
+                        self.consume();
+                        self.push_token(TokenType::Or, None);
+                    }
+                    '#' => {
+                        if self.peek(0) == '#' {
+                            self.consume();
+                            self.push_token(TokenType::HashHash, None);
+                        } else {
+                            self.push_token(TokenType::Hash, None);
+                        }
+                    }
+                    '*' => {
+                        if self.peek(0) == '=' {
+                            self.consume();
+                            self.push_token(TokenType::MulEq, None);
+                        } else {
+                            self.push_token(TokenType::Mul, Some('*'.to_string()));
+                        }
+                    }
+                    '/' => {
+                        if self.peek(0) == '=' {
+                            self.consume();
+                            self.push_token(TokenType::DivEq, None);
+                        } else {
+                            self.push_token(TokenType::Div, Some('/'.to_string()));
+                        }
+                    }
                     '(' => self.push_token(TokenType::OpenParen, Some('('.to_string())),
                     ')' => self.push_token(TokenType::CloseParen, Some(')'.to_string())),
                     '{' => self.push_token(TokenType::OpenScope, None),
@@ -233,6 +460,11 @@ impl Tokenizer {
                         while self.peek(0) != '"' {
                             let v = self.consume();
                             self.m_buf.push(v);
+                            // keep escapes as is so \" doesn't end the string
+                            if v == '\\' {
+                                let v = self.consume();
+                                self.m_buf.push(v);
+                            }
                         }
                         self.consume();
                         self.push_token(TokenType::String, Some(self.m_buf.clone()));
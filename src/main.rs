@@ -1,44 +1,202 @@
-use clap::Parser as CliParser;
-use std::{fs::File, io::{Read, Write}};
+use clap::{Parser as CliParser, ValueEnum};
+use std::{fs::File, io::Write, path::Path, process::Command};
+
+use crate::Ir::Stmt;
+use crate::Tokenizer::Token;
 
 mod Tokenizer;
 mod Parser;
 mod Gen;
 mod Ir;
+mod Mir;
+mod Import;
+mod Preprocessor;
+mod Sema;
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum Emit {
+    Asm,
+    Ir,
+    Obj,
+    Exe,
+    Header,
+    Preprocessed,
+}
 
 #[derive(CliParser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
-   #[arg(short, long, required = true, help = "provide file main.v")]
-   file: String,
+   #[arg(short, long, required = true, num_args = 1.., help = "provide file main.v, every file is compiled into its own object")]
+   file: Vec<String>,
+   #[arg(long, help = "link against the system libc, main becomes the entry point")]
+   libc: bool,
+   #[arg(short, long, default_value = "main", help = "executable name when linking")]
+   output: String,
+   #[arg(long, value_enum, help = "asm writes <file>.asm, ir writes the IR the asm is made from to <file>.ir, obj assembles it into an object without the _start stub, exe links every object (default with --libc), header writes a C header for the exported functions, preprocessed writes <file>.i")]
+   emit: Option<Emit>,
+   #[arg(short = 'I', long = "include", help = "directory searched for imports after the importing file's own")]
+   include: Vec<String>,
+   #[arg(short = 'D', help = "define a macro, NAME or NAME=value")]
+   define: Vec<String>,
+   #[arg(short = 'U', help = "undefine a macro, applied after every -D")]
+   undefine: Vec<String>,
+   #[arg(long, help = "warn when an assignment, argument or return may not fit in its type")]
+   warn_narrowing: bool,
+   #[arg(short = 'O', default_value_t = 0, help = "optimisation level, 1 and above inline small functions, fold and propagate constants, move invariant code out of loops, reduce induction variables, remove dead code and clean up the asm, 2 also unrolls small loops")]
+   opt_level: u8,
+   #[arg(long, value_delimiter = ',', help = "IR passes to run in order instead of the ones of the level, e.g. --passes=ssa,out-of-ssa")]
+   passes: Option<Vec<String>>,
+   #[arg(long, value_delimiter = ',', help = "print the IR to stderr after every run of the pass")]
+   print_after: Vec<String>,
 }
 
 
+// None when the preprocessor found an error, which is printed
+fn preprocess_file(path: &str, cli: &Cli) -> Result<Option<Vec<Token>>, Box<dyn std::error::Error>> {
+    let mut importer = Import::Importer::new(cli.include.clone());
+    let tokens = importer.load(Path::new(path))?;
+    let mut preprocessor = Preprocessor::Preprocessor::new(&cli.define, &cli.undefine);
+    match preprocessor.process(tokens, &mut importer) {
+        Ok(tokens) => Ok(Some(tokens)),
+        Err(error) => {
+            print_errors(Err(vec![error]));
+            Ok(None)
+        }
+    }
+}
+
+fn parse_tokens(tokens: Vec<Token>) -> Result<Vec<Stmt>, Box<dyn std::error::Error>> {
+    let mut parser = Parser::Parser::new(tokens);
+    Ok(parser.parse())
+}
+
+// prints every error and warning sema found, returns how many errors
+// there were and what the file shares with the others
+fn check_ast(ast: &mut [Stmt], cli: &Cli) -> (usize, Sema::Linkage) {
+    let mut sema = Sema::Sema::new(cli.warn_narrowing);
+    let res = sema.check(ast);
+    for warning in sema.take_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    (print_errors(res), sema.linkage(ast))
+}
+
+fn print_errors(res: Result<(), Vec<String>>) -> usize {
+    match res {
+        Ok(()) => 0,
+        Err(errors) => {
+            for error in errors.iter() {
+                eprintln!("error: {}", error);
+            }
+            errors.len()
+        }
+    }
+}
+
+fn print_warnings(generator: &mut Gen::Gen) {
+    for warning in generator.take_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+fn defines_main(ast: &[Stmt]) -> bool {
+    ast.iter().any(|stmt| matches!(stmt, Stmt::InitFunc(v) if v.name.value.as_deref() == Some("main")))
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli: Cli = Cli::parse();
+    let emit = cli.emit.clone().unwrap_or(if cli.libc { Emit::Exe } else { Emit::Asm });
 
-    println!("file name is: {}", cli.file);
+    let mut units: Vec<(String, Vec<Stmt>)> = Vec::new();
+    let mut linkages: Vec<Sema::Linkage> = Vec::new();
+    let mut error_count = 0;
+    for path in cli.file.iter() {
+        let stem = Path::new(path).file_stem().unwrap().to_string_lossy().to_string();
+        if units.iter().any(|(name, _)| *name == stem) {
+            return Err(format!("two files would both be compiled to {}.o", stem).into());
+        }
+        let Some(tokens) = preprocess_file(path, &cli)? else {
+            error_count += 1;
+            continue;
+        };
+        if emit == Emit::Preprocessed {
+            let mut file = File::create(format!("{}.i", stem))?;
+            file.write_all(Preprocessor::to_source(&tokens).as_bytes())?;
+            continue;
+        }
+        let mut ast = parse_tokens(tokens)?;
+        let (errors, linkage) = check_ast(&mut ast, &cli);
+        error_count += errors;
+        linkages.push(linkage);
+        units.push((stem, ast));
+    }
+    for warning in Sema::Sema::unused_functions(&linkages) {
+        eprintln!("warning: {}", warning);
+    }
+    error_count += print_errors(Sema::Sema::check_linkage(&linkages));
 
-    let mut file = File::open(cli.file)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    println!("file contains: {}",&contents);
+    if error_count > 0 {
+        let plural = if error_count == 1 { "" } else { "s" };
+        return Err(format!("{} error{} found", error_count, plural).into());
+    }
+    if emit == Emit::Preprocessed {
+        return Ok(());
+    }
 
-    let mut tokenizer = Tokenizer::Tokenizer::new(contents);
-    tokenizer.tokenize();
-    println!("{}",tokenizer);
+    let main_count = units.iter().filter(|(_, ast)| defines_main(ast)).count();
+    if main_count > 1 {
+        return Err("main is defined in more than one file".into());
+    }
+    if emit == Emit::Exe && main_count == 0 {
+        return Err("none of the files defines main".into());
+    }
 
-    let mut parser = Parser::Parser::new(tokenizer.m_res);
-    let res = parser.parse();
-    
-    // to lazy to make normal debug print
-    println!("parse result\n{:#?}",res);
+    let passes = Mir::PassManager::new(cli.opt_level, cli.passes.as_deref(), &cli.print_after)?;
+    let mut objects: Vec<String> = Vec::new();
+    for (stem, ast) in units {
+        // only the file with main gets the _start stub, the others are
+        // plain objects resolved against it by the linker
+        let entry_stub = !cli.libc && emit != Emit::Obj && defines_main(&ast);
+        let mut generator = Gen::Gen::new(ast, entry_stub);
+        if emit == Emit::Header {
+            let guard = format!("{}_H", stem.to_uppercase().replace(|c: char| !c.is_alphanumeric(), "_"));
+            let header = generator.gen_header(&guard)?;
+            let mut file = File::create(format!("{}.h", stem))?;
+            file.write_all(header.as_bytes())?;
+            continue;
+        }
+        if emit == Emit::Ir {
+            let module = generator.gen_ir(&passes)?;
+            print_warnings(&mut generator);
+            let mut file = File::create(format!("{}.ir", stem))?;
+            file.write_all(module.to_string().as_bytes())?;
+            continue;
+        }
+        let asm = generator.gen_asm(&passes)?;
+        print_warnings(&mut generator);
+        let asm_file = format!("{}.asm", stem);
+        let mut file = File::create(&asm_file)?;
+        file.write_all(asm.as_bytes())?;
 
-    let mut generator = Gen::Gen::new(res);
-    let asm = generator.gen_asm()?;
-    let mut file = File::create("main.asm")?;
-    file.write(asm.as_bytes())?;
+        if emit == Emit::Obj || emit == Emit::Exe {
+            let object = format!("{}.o", stem);
+            let status = Command::new("nasm").args(["-f", "elf64", &asm_file, "-o", &object]).status()?;
+            if !status.success() {
+                return Err(format!("nasm failed to assemble {}", asm_file).into());
+            }
+            objects.push(object);
+        }
+    }
+
+    if emit == Emit::Exe {
+        // cc pulls in crt1.o and libc, which call our main
+        let linker = if cli.libc { "cc" } else { "ld" };
+        let status = Command::new(linker).args(&objects).args(["-o", &cli.output]).status()?;
+        if !status.success() {
+            return Err(format!("{} failed to link {}", linker, objects.join(" ")).into());
+        }
+    }
 
 
     Ok(())
-}
\ No newline at end of file
+}